@@ -0,0 +1,4 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.10" tiledversion="1.10.2" name="blocks" tilewidth="64" tileheight="64" tilecount="4" columns="2">
+ <image source="blocks.png" width="128" height="128"/>
+</tileset>
@@ -0,0 +1,271 @@
+use bevy::prelude::*;
+
+/// How an animated tile continues after its last frame.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AnimationMode {
+    /// Starts again with the first frame.
+    #[default]
+    Loop,
+    /// Plays the frames backwards until the first frame is reached and then forwards again.
+    PingPong,
+    /// Stops on the last frame.
+    Once,
+}
+
+/// Plays the atlas frames of an animated tile.
+#[derive(Component, Debug, Clone)]
+pub struct AnimatedTile {
+    frames: Vec<usize>,
+    timer: Timer,
+    mode: AnimationMode,
+    current: usize,
+    forward: bool,
+    finished: bool,
+}
+
+/// Global playback settings for all animated tiles.
+#[derive(Resource, Debug, Copy, Clone)]
+pub struct TileAnimationSettings {
+    pub paused: bool,
+    pub speed: f32,
+}
+
+impl AnimatedTile {
+    /// Creates a looping animation which shows each frame for the given amount of seconds.
+    /// Intervals which are not a positive number of seconds show a new frame every update in which time passes.
+    pub fn new(frames: Vec<usize>, seconds_per_frame: f32) -> Self {
+        let seconds_per_frame = match seconds_per_frame.is_finite() && seconds_per_frame > 0.0 {
+            true => seconds_per_frame,
+            false => 0.0,
+        };
+
+        Self {
+            frames,
+            timer: Timer::from_seconds(seconds_per_frame, TimerMode::Repeating),
+            mode: AnimationMode::Loop,
+            current: 0,
+            forward: true,
+            finished: false,
+        }
+    }
+
+    pub fn with_mode(mut self, mode: AnimationMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn mode(&self) -> AnimationMode {
+        self.mode
+    }
+
+    pub fn frames(&self) -> &[usize] {
+        &self.frames
+    }
+
+    /// The atlas index of the current frame.
+    pub fn current_index(&self) -> Option<usize> {
+        self.frames.get(self.current).copied()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Starts the animation again from the first frame.
+    pub fn restart(&mut self) {
+        self.current = 0;
+        self.forward = true;
+        self.finished = false;
+        self.timer.reset();
+    }
+
+    /// Moves the animation to its next frame.
+    pub fn step(&mut self) {
+        let len = self.frames.len();
+
+        if len < 2 || self.finished {
+            return;
+        }
+
+        match self.mode {
+            AnimationMode::Loop => self.current = (self.current + 1) % len,
+            AnimationMode::PingPong => {
+                if self.forward && self.current + 1 == len {
+                    self.forward = false;
+                } else if !self.forward && self.current == 0 {
+                    self.forward = true;
+                }
+
+                if self.forward {
+                    self.current += 1;
+                } else {
+                    self.current -= 1;
+                }
+            }
+            AnimationMode::Once => {
+                self.current += 1;
+                self.finished = self.current + 1 == len;
+            }
+        }
+    }
+
+    /// Moves the animation by the given number of frames. Whole cycles of the animation are skipped,
+    /// so huge counts like the ones of a timer without duration do not stall the update.
+    fn advance(&mut self, steps: u32) {
+        let len = self.frames.len() as u32;
+        let cycle = match self.mode {
+            AnimationMode::Loop => len,
+            AnimationMode::PingPong => 2 * len.saturating_sub(1),
+            AnimationMode::Once => len,
+        };
+        let steps = match self.mode {
+            AnimationMode::Once => steps.min(cycle),
+            _ => steps % cycle.max(1),
+        };
+
+        for _ in 0..steps {
+            self.step();
+        }
+    }
+}
+
+impl Default for TileAnimationSettings {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            speed: 1.0,
+        }
+    }
+}
+
+/// Advances the frames of all animated tiles.
+pub fn animate_tiles(
+    time: Res<Time>,
+    settings: Res<TileAnimationSettings>,
+    mut animated_tiles: Query<(&mut AnimatedTile, &mut TextureAtlasSprite)>,
+) {
+    if settings.paused {
+        return;
+    }
+
+    let delta = time.delta().mul_f32(settings.speed.max(0.0));
+
+    for (mut animation, mut sprite) in animated_tiles.iter_mut() {
+        if animation.finished {
+            continue;
+        }
+
+        animation.timer.tick(delta);
+
+        let steps = match animation.timer.duration().is_zero() {
+            true => u32::from(!delta.is_zero()),
+            false => animation.timer.times_finished_this_tick(),
+        };
+        animation.advance(steps);
+
+        if let Some(index) = animation.current_index() {
+            if sprite.index != index {
+                sprite.index = index;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{prelude::*, utils::Instant};
+
+    use crate::animation::{animate_tiles, AnimatedTile, AnimationMode, TileAnimationSettings};
+
+    fn play(mut animation: AnimatedTile, steps: usize) -> Vec<usize> {
+        let mut indices = vec![animation.current_index().unwrap()];
+
+        for _ in 0..steps {
+            animation.step();
+            indices.push(animation.current_index().unwrap());
+        }
+
+        indices
+    }
+
+    #[test]
+    fn test_loop() {
+        let animation = AnimatedTile::new(vec![4, 5, 6], 0.5);
+
+        assert_eq!(vec![4, 5, 6, 4, 5], play(animation, 4));
+    }
+
+    #[test]
+    fn test_ping_pong() {
+        let animation = AnimatedTile::new(vec![4, 5, 6], 0.5).with_mode(AnimationMode::PingPong);
+
+        assert_eq!(vec![4, 5, 6, 5, 4, 5, 6], play(animation, 6));
+    }
+
+    #[test]
+    fn test_once() {
+        let mut animation = AnimatedTile::new(vec![4, 5, 6], 0.5).with_mode(AnimationMode::Once);
+        animation.step();
+        animation.step();
+
+        assert!(animation.is_finished());
+        assert_eq!(Some(6), animation.current_index());
+
+        animation.step();
+        assert_eq!(Some(6), animation.current_index());
+
+        animation.restart();
+        assert_eq!(Some(4), animation.current_index());
+    }
+
+    #[test]
+    fn test_advance_skips_cycles() {
+        let mut animation = AnimatedTile::new(vec![4, 5, 6], 0.5);
+        animation.advance(u32::MAX);
+        assert_eq!(Some(4), animation.current_index());
+
+        let mut animation =
+            AnimatedTile::new(vec![4, 5, 6], 0.5).with_mode(AnimationMode::PingPong);
+        animation.advance(4 * 1000 + 3);
+        assert_eq!(Some(5), animation.current_index());
+    }
+
+    #[test]
+    fn test_zero_interval() {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .init_resource::<TileAnimationSettings>()
+            .add_systems(Update, animate_tiles);
+
+        let tile = app
+            .world
+            .spawn((
+                AnimatedTile::new(vec![4, 5, 6], 0.0),
+                TextureAtlasSprite::new(4),
+            ))
+            .id();
+        let start = Instant::now();
+
+        for (frame, index) in [4, 5, 6, 4].into_iter().enumerate() {
+            app.world
+                .resource_mut::<Time>()
+                .update_with_instant(start + Duration::from_millis(frame as u64 * 16));
+            app.update();
+
+            assert_eq!(
+                index,
+                app.world.get::<TextureAtlasSprite>(tile).unwrap().index
+            );
+        }
+    }
+
+    #[test]
+    fn test_negative_interval() {
+        let mut animation = AnimatedTile::new(vec![4, 5], -1.0);
+        animation.advance(1);
+
+        assert_eq!(Some(5), animation.current_index());
+    }
+}
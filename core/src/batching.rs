@@ -0,0 +1,726 @@
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+    utils::{HashMap, HashSet},
+};
+
+use crate::{tile::TileMarker, transition::GridRotationTransition};
+
+/// Draws the tiles of a grid as batched meshes instead of one sprite per tile.
+/// Every layer, or every chunk of a chunked grid, gets one mesh for each row of tiles which share a z order,
+/// so dynamic objects still sort between the rows. The tile entities stay around with hidden sprites,
+/// which are shown again when the component is removed.
+#[derive(Component, Default, Debug, Copy, Clone)]
+pub struct BatchedRendering;
+
+/// A mesh drawing all tiles of one row of a layer or chunk, which use the same texture atlas.
+/// Batches are children of the grid and are rebuilt whenever one of their tiles changes.
+#[derive(Component, Debug, Clone)]
+pub struct TileBatch {
+    /// The layer or chunk entity whose tiles are drawn.
+    pub source: Entity,
+    pub texture_atlas: Handle<TextureAtlas>,
+}
+
+/// A single tile sprite inside of a batched mesh.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct BatchQuad {
+    center: Vec2,
+    size: Vec2,
+    uv: Rect,
+    color: [f32; 4],
+    flip_x: bool,
+    flip_y: bool,
+}
+
+/// The rows which have to be rebuilt and the row every batched tile is drawn in.
+/// Rows are identified by their layer or chunk and the bits of their z order.
+#[derive(Default)]
+pub struct BatchRows {
+    dirty: HashMap<Entity, HashSet<u32>>,
+    tiles: HashMap<Entity, (Entity, u32)>,
+    /// Grids which are drawn with sprites while their rotation transition is running.
+    rotating: HashSet<Entity>,
+}
+
+type ChangedTileQuery<'w, 's> = Query<
+    'w,
+    's,
+    (Entity, &'static Parent, &'static Transform),
+    (
+        With<TileMarker>,
+        Or<(
+            Changed<Transform>,
+            Changed<TextureAtlasSprite>,
+            Changed<Handle<TextureAtlas>>,
+        )>,
+    ),
+>;
+
+type BatchedTileQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static Transform,
+        &'static TextureAtlasSprite,
+        &'static Handle<TextureAtlas>,
+        &'static mut Visibility,
+    ),
+    With<TileMarker>,
+>;
+
+type TileBatchQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static TileBatch,
+        &'static Transform,
+        &'static Parent,
+        &'static mut Visibility,
+    ),
+    Without<TileMarker>,
+>;
+
+/// Rebuilds the batched meshes of the rows whose tiles were added, changed or removed
+/// and hides the sprites of their tiles. While a grid rotates with a transition, its sprites are drawn instead
+/// and its rows are rebuilt once the transition finished. Grids which lose `BatchedRendering` get their sprites back.
+#[allow(clippy::too_many_arguments)]
+pub fn update_tile_batches(
+    mut commands: Commands,
+    mut rows: Local<BatchRows>,
+    mut atlas_materials: Local<HashMap<Handle<TextureAtlas>, Handle<ColorMaterial>>>,
+    texture_atlases: Res<Assets<TextureAtlas>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    batched_grids: Query<(Entity, Option<&GridRotationTransition>), With<BatchedRendering>>,
+    added_grids: Query<Entity, Added<BatchedRendering>>,
+    mut removed_grids: RemovedComponents<BatchedRendering>,
+    mut removed_tiles: RemovedComponents<TileMarker>,
+    changed_tiles: ChangedTileQuery,
+    parents: Query<&Parent>,
+    children: Query<&Children>,
+    mut tiles: BatchedTileQuery,
+    mut batches: TileBatchQuery,
+) {
+    let batched_grid = |source: Entity| {
+        parents
+            .iter_ancestors(source)
+            .find(|ancestor| batched_grids.contains(*ancestor))
+    };
+
+    for grid in removed_grids.iter() {
+        rows.rotating.remove(&grid);
+        show_sprites(
+            grid,
+            true,
+            &children,
+            &mut tiles,
+            &mut batches,
+            &mut commands,
+        );
+
+        for descendant in children.iter_descendants(grid) {
+            rows.tiles.remove(&descendant);
+            rows.dirty.remove(&descendant);
+        }
+    }
+
+    for tile in removed_tiles.iter() {
+        rows.remove(tile);
+    }
+
+    for (grid, transition) in batched_grids.iter() {
+        let running = transition.is_some_and(|transition| transition.is_running());
+
+        if running && rows.rotating.insert(grid) {
+            show_sprites(
+                grid,
+                false,
+                &children,
+                &mut tiles,
+                &mut batches,
+                &mut commands,
+            );
+        } else if (!running && rows.rotating.remove(&grid)) || added_grids.contains(grid) {
+            for descendant in children.iter_descendants(grid) {
+                if let (Ok((transform, ..)), Ok(parent)) =
+                    (tiles.get(descendant), parents.get(descendant))
+                {
+                    rows.insert(descendant, parent.get(), transform.translation.z);
+                }
+            }
+        }
+    }
+
+    let mut grids = HashMap::new();
+    for (tile, parent, transform) in changed_tiles.iter() {
+        let grid = *grids
+            .entry(parent.get())
+            .or_insert_with(|| batched_grid(parent.get()));
+
+        if grid.is_some_and(|grid| !rows.rotating.contains(&grid)) {
+            rows.insert(tile, parent.get(), transform.translation.z);
+        }
+    }
+
+    let mut rebuilt = HashMap::new();
+
+    for source in rows.dirty.keys().copied().collect::<Vec<_>>() {
+        let Some(grid) = batched_grid(source) else {
+            rows.dirty.remove(&source);
+            continue;
+        };
+
+        if rows.rotating.contains(&grid) {
+            continue;
+        }
+
+        let dirty_rows = &rows.dirty[&source];
+        let mut groups: HashMap<(u32, Handle<TextureAtlas>), Vec<BatchQuad>> = HashMap::new();
+        let mut incomplete = HashSet::new();
+
+        for child in children.get(source).into_iter().flatten() {
+            let Ok((transform, sprite, atlas_handle, mut visibility)) = tiles.get_mut(*child)
+            else {
+                continue;
+            };
+
+            let row = transform.translation.z.to_bits();
+            if !dirty_rows.contains(&row) {
+                continue;
+            }
+
+            if *visibility != Visibility::Hidden {
+                *visibility = Visibility::Hidden;
+            }
+
+            let Some(quad) = texture_atlases
+                .get(atlas_handle)
+                .and_then(|atlas| BatchQuad::new(transform, sprite, atlas))
+            else {
+                incomplete.insert(row);
+                continue;
+            };
+
+            groups
+                .entry((row, atlas_handle.clone()))
+                .or_default()
+                .push(quad);
+        }
+
+        // Keeps the old batches of a row until every texture atlas of it is loaded.
+        let complete = dirty_rows
+            .difference(&incomplete)
+            .copied()
+            .collect::<HashSet<_>>();
+        groups.retain(|(row, _), _| complete.contains(row));
+
+        match incomplete.is_empty() {
+            true => rows.dirty.remove(&source),
+            false => rows.dirty.insert(source, incomplete),
+        };
+        rebuilt.insert(source, (grid, complete, groups));
+    }
+
+    for (entity, batch, transform, ..) in batches.iter() {
+        let row = transform.translation.z.to_bits();
+
+        if rebuilt
+            .get(&batch.source)
+            .is_some_and(|(_, complete, _)| complete.contains(&row))
+            || parents.get(batch.source).is_err()
+        {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    for (source, (grid, _, groups)) in rebuilt {
+        for ((z, atlas_handle), quads) in groups {
+            let Some(atlas) = texture_atlases.get(&atlas_handle) else {
+                continue;
+            };
+            let material = atlas_materials
+                .entry(atlas_handle.clone())
+                .or_insert_with(|| materials.add(ColorMaterial::from(atlas.texture.clone())))
+                .clone();
+
+            let batch = commands
+                .spawn((
+                    MaterialMesh2dBundle {
+                        mesh: Mesh2dHandle(meshes.add(batch_mesh(&quads))),
+                        material,
+                        transform: Transform::from_xyz(0.0, 0.0, f32::from_bits(z)),
+                        ..default()
+                    },
+                    TileBatch {
+                        source,
+                        texture_atlas: atlas_handle,
+                    },
+                    Name::new("Tile batch"),
+                ))
+                .id();
+            commands.entity(grid).add_child(batch);
+        }
+    }
+}
+
+/// Draws the tiles of a grid as sprites again and despawns or hides its batches.
+fn show_sprites(
+    grid: Entity,
+    despawn_batches: bool,
+    children: &Query<&Children>,
+    tiles: &mut BatchedTileQuery,
+    batches: &mut TileBatchQuery,
+    commands: &mut Commands,
+) {
+    for descendant in children.iter_descendants(grid) {
+        if let Ok((.., mut visibility)) = tiles.get_mut(descendant) {
+            *visibility = Visibility::Inherited;
+        }
+    }
+
+    for (entity, _, _, parent, mut visibility) in batches.iter_mut() {
+        if parent.get() != grid {
+            continue;
+        }
+
+        match despawn_batches {
+            true => commands.entity(entity).despawn_recursive(),
+            false => *visibility = Visibility::Hidden,
+        }
+    }
+}
+
+impl BatchRows {
+    /// Moves the tile to the row with the given z order, marking both rows as dirty.
+    fn insert(&mut self, tile: Entity, source: Entity, z: f32) {
+        let row = z.to_bits();
+
+        if let Some((old_source, old_row)) = self.tiles.insert(tile, (source, row)) {
+            self.dirty.entry(old_source).or_default().insert(old_row);
+        }
+        self.dirty.entry(source).or_default().insert(row);
+    }
+
+    /// Removes the tile, marking its row as dirty.
+    fn remove(&mut self, tile: Entity) {
+        if let Some((source, row)) = self.tiles.remove(&tile) {
+            self.dirty.entry(source).or_default().insert(row);
+        }
+    }
+}
+
+impl BatchQuad {
+    /// The quad a sprite would be drawn with, relative to its layer.
+    fn new(
+        transform: &Transform,
+        sprite: &TextureAtlasSprite,
+        atlas: &TextureAtlas,
+    ) -> Option<Self> {
+        let rect = *atlas.textures.get(sprite.index)?;
+        let size = sprite.custom_size.unwrap_or(rect.size()) * transform.scale.truncate();
+
+        Some(Self {
+            center: transform.translation.truncate() - sprite.anchor.as_vec() * size,
+            size,
+            uv: Rect::from_corners(rect.min / atlas.size, rect.max / atlas.size),
+            color: sprite.color.as_linear_rgba_f32(),
+            flip_x: sprite.flip_x,
+            flip_y: sprite.flip_y,
+        })
+    }
+}
+
+/// Builds a mesh with one quad per tile, with the UVs of the tile in its texture atlas.
+fn batch_mesh(quads: &[BatchQuad]) -> Mesh {
+    let mut positions = Vec::with_capacity(quads.len() * 4);
+    let mut uvs = Vec::with_capacity(quads.len() * 4);
+    let mut colors = Vec::with_capacity(quads.len() * 4);
+    let mut indices = Vec::with_capacity(quads.len() * 6);
+
+    for (i, quad) in quads.iter().enumerate() {
+        let min = quad.center - quad.size / 2.0;
+        let max = quad.center + quad.size / 2.0;
+        let (left, right) = match quad.flip_x {
+            true => (quad.uv.max.x, quad.uv.min.x),
+            false => (quad.uv.min.x, quad.uv.max.x),
+        };
+        let (bottom, top) = match quad.flip_y {
+            true => (quad.uv.min.y, quad.uv.max.y),
+            false => (quad.uv.max.y, quad.uv.min.y),
+        };
+
+        positions.extend([
+            [min.x, min.y, 0.0],
+            [max.x, min.y, 0.0],
+            [max.x, max.y, 0.0],
+            [min.x, max.y, 0.0],
+        ]);
+        uvs.extend([[left, bottom], [right, bottom], [right, top], [left, top]]);
+        colors.extend([quad.color; 4]);
+
+        let first = i as u32 * 4;
+        indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use bevy::{
+        prelude::*,
+        render::mesh::{Indices, VertexAttributeValues},
+    };
+
+    use crate::{
+        batching::{batch_mesh, update_tile_batches, BatchQuad, BatchedRendering, TileBatch},
+        tile::TileMarker,
+        transition::{GridRotationTransition, LerpTransition},
+    };
+
+    fn atlas() -> TextureAtlas {
+        TextureAtlas::from_grid(Handle::default(), Vec2::new(64.0, 64.0), 2, 1, None, None)
+    }
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins((TaskPoolPlugin::default(), AssetPlugin::default()))
+            .add_asset::<Image>()
+            .add_asset::<TextureAtlas>()
+            .add_asset::<Mesh>()
+            .add_asset::<ColorMaterial>()
+            .add_systems(Update, update_tile_batches);
+        app
+    }
+
+    fn spawn_tile(layer: &mut WorldChildBuilder, atlas: &Handle<TextureAtlas>, position: Vec3) {
+        layer.spawn((
+            TileMarker,
+            SpriteSheetBundle {
+                texture_atlas: atlas.clone(),
+                transform: Transform::from_translation(position),
+                sprite: TextureAtlasSprite::new(1),
+                ..default()
+            },
+        ));
+    }
+
+    /// Spawns a batched grid with one layer and a tile at each of the given positions.
+    fn spawn_grid(app: &mut App, positions: &[Vec3]) -> (Entity, Vec<Entity>) {
+        let atlas = app
+            .world
+            .resource_mut::<Assets<TextureAtlas>>()
+            .add(atlas());
+
+        let mut tiles = Vec::new();
+        let grid = app
+            .world
+            .spawn((SpatialBundle::default(), BatchedRendering))
+            .with_children(|grid| {
+                grid.spawn(SpatialBundle::default()).with_children(|layer| {
+                    for position in positions {
+                        tiles.push(
+                            layer
+                                .spawn((
+                                    TileMarker,
+                                    SpriteSheetBundle {
+                                        texture_atlas: atlas.clone(),
+                                        transform: Transform::from_translation(*position),
+                                        ..default()
+                                    },
+                                ))
+                                .id(),
+                        );
+                    }
+                });
+            })
+            .id();
+
+        (grid, tiles)
+    }
+
+    /// The batches by their z order.
+    fn batches(app: &mut App) -> Vec<(f32, Entity)> {
+        let mut batches = app
+            .world
+            .query_filtered::<(Entity, &Transform), With<TileBatch>>()
+            .iter(&app.world)
+            .map(|(entity, transform)| (transform.translation.z, entity))
+            .collect::<Vec<_>>();
+        batches.sort_by(|a, b| a.0.total_cmp(&b.0));
+        batches
+    }
+
+    fn visibility(app: &App, entity: Entity) -> Visibility {
+        *app.world.get::<Visibility>(entity).unwrap()
+    }
+
+    #[test]
+    fn test_batch_mesh() {
+        let quad = BatchQuad {
+            center: Vec2::new(10.0, 20.0),
+            size: Vec2::new(64.0, 32.0),
+            uv: Rect::new(0.5, 0.0, 1.0, 1.0),
+            color: [1.0; 4],
+            flip_x: false,
+            flip_y: false,
+        };
+        let mesh = batch_mesh(&[
+            quad,
+            BatchQuad {
+                flip_x: true,
+                ..quad
+            },
+        ]);
+
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            panic!("Missing positions.");
+        };
+        assert_eq!(8, positions.len());
+        assert_eq!([-22.0, 4.0, 0.0], positions[0]);
+        assert_eq!([42.0, 36.0, 0.0], positions[2]);
+
+        let Some(VertexAttributeValues::Float32x2(uvs)) = mesh.attribute(Mesh::ATTRIBUTE_UV_0)
+        else {
+            panic!("Missing uvs.");
+        };
+        assert_eq!([0.5, 1.0], uvs[0]);
+        assert_eq!([1.0, 0.0], uvs[2]);
+        assert_eq!([1.0, 1.0], uvs[4]);
+
+        let Some(Indices::U32(indices)) = mesh.indices() else {
+            panic!("Missing indices.");
+        };
+        assert_eq!(&[4, 5, 6, 4, 6, 7], &indices[6..]);
+    }
+
+    #[test]
+    fn test_batches_per_row() {
+        let mut app = app();
+        let atlas = app
+            .world
+            .resource_mut::<Assets<TextureAtlas>>()
+            .add(atlas());
+
+        let mut layer = Entity::PLACEHOLDER;
+        let grid = app
+            .world
+            .spawn((SpatialBundle::default(), BatchedRendering))
+            .with_children(|grid| {
+                layer = grid
+                    .spawn(SpatialBundle::default())
+                    .with_children(|layer| {
+                        spawn_tile(layer, &atlas, Vec3::new(0.0, 0.0, 900.0));
+                        spawn_tile(layer, &atlas, Vec3::new(-32.0, 16.0, 899.9));
+                        spawn_tile(layer, &atlas, Vec3::new(32.0, 16.0, 899.9));
+                    })
+                    .id();
+            })
+            .id();
+
+        app.update();
+        app.update();
+
+        let mut batches = app.world.query::<(&TileBatch, &Transform, &Parent)>();
+        let mut batches = batches
+            .iter(&app.world)
+            .map(|(batch, transform, parent)| {
+                assert_eq!(layer, batch.source);
+                assert_eq!(grid, parent.get());
+                transform.translation.z
+            })
+            .collect::<Vec<_>>();
+        batches.sort_by(f32::total_cmp);
+        assert_eq!(2, batches.len());
+        assert_relative_eq!(899.9, batches[0]);
+        assert_relative_eq!(900.0, batches[1]);
+
+        let mut tiles = app.world.query_filtered::<&Visibility, With<TileMarker>>();
+        assert!(tiles
+            .iter(&app.world)
+            .all(|visibility| *visibility == Visibility::Hidden));
+    }
+
+    #[test]
+    fn test_rebuild_after_removal() {
+        let mut app = app();
+        let atlas = app
+            .world
+            .resource_mut::<Assets<TextureAtlas>>()
+            .add(atlas());
+
+        let mut tile = Entity::PLACEHOLDER;
+        app.world
+            .spawn((SpatialBundle::default(), BatchedRendering))
+            .with_children(|grid| {
+                grid.spawn(SpatialBundle::default()).with_children(|layer| {
+                    spawn_tile(layer, &atlas, Vec3::new(0.0, 0.0, 0.0));
+                    tile = layer
+                        .spawn((
+                            TileMarker,
+                            SpriteSheetBundle {
+                                texture_atlas: atlas.clone(),
+                                transform: Transform::from_xyz(0.0, 32.0, 899.8),
+                                ..default()
+                            },
+                        ))
+                        .id();
+                });
+            });
+
+        app.update();
+        app.update();
+
+        let mut batches = app.world.query::<&TileBatch>();
+        assert_eq!(2, batches.iter(&app.world).count());
+
+        app.world.entity_mut(tile).despawn_recursive();
+        app.update();
+        app.update();
+
+        assert_eq!(1, batches.iter(&app.world).count());
+    }
+
+    #[test]
+    fn test_sprites_without_batched_rendering() {
+        let mut app = app();
+        let atlas = app
+            .world
+            .resource_mut::<Assets<TextureAtlas>>()
+            .add(atlas());
+
+        app.world
+            .spawn(SpatialBundle::default())
+            .with_children(|grid| {
+                grid.spawn(SpatialBundle::default()).with_children(|layer| {
+                    spawn_tile(layer, &atlas, Vec3::ZERO);
+                });
+            });
+
+        app.update();
+
+        let mut batches = app.world.query::<&TileBatch>();
+        assert_eq!(0, batches.iter(&app.world).count());
+
+        let mut tiles = app.world.query_filtered::<&Visibility, With<TileMarker>>();
+        assert_eq!(Visibility::Inherited, *tiles.single(&app.world));
+    }
+
+    #[test]
+    fn test_rebuild_changed_row_only() {
+        let mut app = app();
+        let (_, tiles) = spawn_grid(
+            &mut app,
+            &[Vec3::new(0.0, 0.0, 900.0), Vec3::new(0.0, 16.0, 899.9)],
+        );
+
+        app.update();
+        app.update();
+
+        let old_batches = batches(&mut app);
+        assert_eq!(2, old_batches.len());
+
+        app.world
+            .get_mut::<TextureAtlasSprite>(tiles[0])
+            .unwrap()
+            .index = 1;
+        app.update();
+
+        let new_batches = batches(&mut app);
+        assert_eq!(old_batches[0], new_batches[0]);
+        assert_ne!(old_batches[1].1, new_batches[1].1);
+
+        // Moving a tile to another row rebuilds both rows.
+        app.world
+            .get_mut::<Transform>(tiles[0])
+            .unwrap()
+            .translation
+            .z = 899.9;
+        app.update();
+
+        let moved_batches = batches(&mut app);
+        assert_eq!(1, moved_batches.len());
+        assert_ne!(new_batches[0].1, moved_batches[0].1);
+    }
+
+    #[test]
+    fn test_sprites_during_rotation() {
+        let mut app = app();
+        let (grid, tiles) = spawn_grid(&mut app, &[Vec3::new(0.0, 0.0, 900.0)]);
+
+        app.update();
+        app.update();
+
+        let old_batches = batches(&mut app);
+        let mut transition = GridRotationTransition::new(LerpTransition { duration: 1.0 });
+        transition.start(Vec::new());
+        app.world.entity_mut(grid).insert(transition);
+        app.update();
+
+        app.world
+            .get_mut::<Transform>(tiles[0])
+            .unwrap()
+            .translation
+            .x = 16.0;
+        app.update();
+
+        assert_eq!(old_batches, batches(&mut app));
+        assert_eq!(Visibility::Hidden, visibility(&app, old_batches[0].1));
+        assert_eq!(Visibility::Inherited, visibility(&app, tiles[0]));
+
+        app.world
+            .entity_mut(grid)
+            .insert(GridRotationTransition::new(LerpTransition {
+                duration: 1.0,
+            }));
+        app.update();
+
+        let new_batches = batches(&mut app);
+        assert_eq!(1, new_batches.len());
+        assert_ne!(old_batches[0].1, new_batches[0].1);
+        assert_eq!(Visibility::Inherited, visibility(&app, new_batches[0].1));
+        assert_eq!(Visibility::Hidden, visibility(&app, tiles[0]));
+    }
+
+    #[test]
+    fn test_remove_batched_rendering() {
+        let mut app = app();
+        let (grid, tiles) = spawn_grid(
+            &mut app,
+            &[Vec3::new(0.0, 0.0, 900.0), Vec3::new(0.0, 16.0, 899.9)],
+        );
+
+        app.update();
+        app.update();
+
+        assert_eq!(2, batches(&mut app).len());
+
+        app.world.entity_mut(grid).remove::<BatchedRendering>();
+        app.update();
+
+        assert!(batches(&mut app).is_empty());
+        for tile in tiles.iter() {
+            assert_eq!(Visibility::Inherited, visibility(&app, *tile));
+        }
+
+        // Batching again.
+        app.world.entity_mut(grid).insert(BatchedRendering);
+        app.update();
+
+        assert_eq!(2, batches(&mut app).len());
+        for tile in tiles.iter() {
+            assert_eq!(Visibility::Hidden, visibility(&app, *tile));
+        }
+    }
+}
@@ -0,0 +1,529 @@
+use bevy::{
+    input::mouse::{MouseMotion, MouseWheel},
+    prelude::*,
+};
+
+use crate::{
+    grid::{Grid, GridOffset, GridSize, TileSize},
+    loading::tilemap::TilemapDefinition,
+    projection::GridProjection,
+    WorldScale,
+};
+
+/// A 2d camera which can be panned with the keyboard or by dragging the mouse, zoomed with the mouse wheel
+/// and which can smoothly follow a target. The camera is kept inside of the bounds of its grid.
+#[derive(Component, Debug, Clone)]
+pub struct IsoCamera {
+    /// Panning speed with the keyboard in world units per second at a zoom of 1.
+    pub pan_speed: f32,
+    /// The mouse button which pans the camera while it is held. `None` disables panning with the mouse.
+    pub drag_button: Option<MouseButton>,
+    /// The current zoom. Zoom levels are whole numbers, so every texture pixel covers a whole number of screen pixels.
+    pub zoom: u32,
+    pub min_zoom: u32,
+    pub max_zoom: u32,
+    /// The entity which is followed. The camera can not be panned while it follows a target.
+    pub target: Option<Entity>,
+    /// How fast the camera catches up with its target. Higher values follow more tightly.
+    pub follow_speed: f32,
+    /// The grid whose bounds the camera is kept in. Uses the only grid if none is set.
+    pub grid: Option<Entity>,
+}
+
+/// The area covered by the tiles of a grid in world space, including the height of its highest layer.
+/// Updated whenever the size of the grid changes, for example after a rotation.
+#[derive(Component, Debug, Copy, Clone, PartialEq)]
+pub struct MapBounds(pub Rect);
+
+type BoundsGridQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Grid,
+        &'static GridSize,
+        &'static TileSize,
+        &'static WorldScale,
+        &'static GridProjection,
+        Option<&'static GridOffset>,
+        &'static GlobalTransform,
+    ),
+    Or<(
+        Changed<GridSize>,
+        Changed<TileSize>,
+        Changed<WorldScale>,
+        Changed<GridProjection>,
+    )>,
+>;
+
+type IsoCameraQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut IsoCamera,
+        &'static mut Transform,
+        &'static mut OrthographicProjection,
+    ),
+>;
+
+/// Calculates the bounds of grids whose size or projection changed.
+pub fn update_map_bounds(
+    mut commands: Commands,
+    tilemaps: Res<Assets<TilemapDefinition>>,
+    grids: BoundsGridQuery,
+) {
+    for (entity, grid, size, tile_size, scale, projection, offset, transform) in grids.iter() {
+        let top_layer = tilemaps
+            .get(&grid.tilemap_handle)
+            .and_then(|definition| {
+                definition
+                    .layers()
+                    .iter()
+                    .map(|layer| layer.ordering_id())
+                    .max()
+            })
+            .unwrap_or_default();
+        let offset = offset.map(|o| o.0).unwrap_or_default() + transform.translation().truncate();
+
+        commands.entity(entity).insert(MapBounds::new(
+            *size,
+            tile_size,
+            scale,
+            projection,
+            offset,
+            top_layer as usize,
+        ));
+    }
+}
+
+/// Pans, zooms and moves iso cameras towards their targets and keeps them inside of the map bounds.
+#[allow(clippy::too_many_arguments)]
+pub fn update_iso_cameras(
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    mut mouse_motion: EventReader<MouseMotion>,
+    mut mouse_wheel: EventReader<MouseWheel>,
+    mut cameras: IsoCameraQuery,
+    targets: Query<&GlobalTransform>,
+    bounds: Query<&MapBounds>,
+) {
+    let drag = mouse_motion.iter().map(|motion| motion.delta).sum::<Vec2>();
+    let scroll = mouse_wheel.iter().map(|wheel| wheel.y).sum::<f32>();
+    let direction = key_direction(&keys);
+
+    for (mut camera, mut transform, mut projection) in cameras.iter_mut() {
+        // The visible area without the zoom, which only gets updated by bevy after the projection changed.
+        let viewport = projection.area.size() / projection.scale;
+
+        if scroll != 0.0 {
+            camera.zoom = match scroll > 0.0 {
+                true => camera.zoom.saturating_add(1),
+                false => camera.zoom.saturating_sub(1),
+            };
+        }
+        camera.zoom = camera
+            .zoom
+            .clamp(camera.min_zoom.max(1), camera.max_zoom.max(1));
+
+        let scale = 1.0 / camera.zoom as f32;
+        if projection.scale != scale {
+            projection.scale = scale;
+        }
+
+        let mut position = transform.translation.truncate();
+
+        match camera.target.and_then(|target| targets.get(target).ok()) {
+            Some(target) => {
+                let factor = 1.0 - (-camera.follow_speed * time.delta_seconds()).exp();
+                position = position.lerp(target.translation().truncate(), factor);
+            }
+            None => {
+                position += direction * camera.pan_speed * scale * time.delta_seconds();
+
+                if camera
+                    .drag_button
+                    .is_some_and(|button| mouse_buttons.pressed(button))
+                {
+                    position += Vec2::new(-drag.x, drag.y) * scale;
+                }
+            }
+        }
+
+        let bounds = match camera.grid {
+            Some(grid) => bounds.get(grid).ok(),
+            None => bounds.get_single().ok(),
+        };
+        if let Some(bounds) = bounds {
+            position = bounds.clamp(position, viewport * scale / 2.0);
+        }
+
+        if position != transform.translation.truncate() {
+            transform.translation.x = position.x;
+            transform.translation.y = position.y;
+        }
+    }
+}
+
+/// The panning direction of the arrow and WASD keys.
+fn key_direction(keys: &Input<KeyCode>) -> Vec2 {
+    let mut direction = Vec2::ZERO;
+
+    if keys.any_pressed([KeyCode::Left, KeyCode::A]) {
+        direction.x -= 1.0;
+    }
+    if keys.any_pressed([KeyCode::Right, KeyCode::D]) {
+        direction.x += 1.0;
+    }
+    if keys.any_pressed([KeyCode::Down, KeyCode::S]) {
+        direction.y -= 1.0;
+    }
+    if keys.any_pressed([KeyCode::Up, KeyCode::W]) {
+        direction.y += 1.0;
+    }
+
+    direction.normalize_or_zero()
+}
+
+impl IsoCamera {
+    pub fn new() -> Self {
+        Self {
+            pan_speed: 500.0,
+            drag_button: Some(MouseButton::Middle),
+            zoom: 1,
+            min_zoom: 1,
+            max_zoom: 4,
+            target: None,
+            follow_speed: 5.0,
+            grid: None,
+        }
+    }
+
+    pub fn with_pan_speed(mut self, pan_speed: f32) -> Self {
+        self.pan_speed = pan_speed;
+        self
+    }
+
+    pub fn with_drag_button(mut self, drag_button: Option<MouseButton>) -> Self {
+        self.drag_button = drag_button;
+        self
+    }
+
+    pub fn with_zoom(mut self, zoom: u32, min_zoom: u32, max_zoom: u32) -> Self {
+        self.zoom = zoom;
+        self.min_zoom = min_zoom;
+        self.max_zoom = max_zoom;
+        self
+    }
+
+    pub fn with_target(mut self, target: Entity, follow_speed: f32) -> Self {
+        self.target = Some(target);
+        self.follow_speed = follow_speed;
+        self
+    }
+
+    pub fn with_grid(mut self, grid: Entity) -> Self {
+        self.grid = Some(grid);
+        self
+    }
+}
+
+impl Default for IsoCamera {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MapBounds {
+    /// The bounds of a grid with the given size in the current view.
+    /// Tiles are drawn centered on their position and twice as high as their surface.
+    pub fn new(
+        size: GridSize,
+        tile_size: &TileSize,
+        scale: &WorldScale,
+        projection: &GridProjection,
+        offset: Vec2,
+        top_layer: usize,
+    ) -> Self {
+        let tile_width = tile_size.width() * scale.0;
+        let tile_height = tile_size.height() * scale.0;
+
+        if size.width == 0 || size.height == 0 {
+            return Self(Rect::from_center_size(offset, Vec2::ZERO));
+        }
+
+        // The outermost tiles of every layout are at the border of the grid.
+        let (min, max) = (0..size.width)
+            .flat_map(|x| [(x, 0), (x, size.height - 1)])
+            .chain((0..size.height).flat_map(|y| [(0, y), (size.width - 1, y)]))
+            .map(|(x, y)| {
+                projection
+                    .layout
+                    .to_world(Vec2::new(x as f32, y as f32), tile_width, tile_height)
+            })
+            .fold(
+                (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+                |(min, max), position| (min.min(position), max.max(position)),
+            );
+        let elevation = (0..=top_layer)
+            .map(|layer| projection.layer_height(layer as f32, tile_height))
+            .fold(0.0, f32::max);
+
+        Self(Rect::from_corners(
+            Vec2::new(min.x - tile_width / 2.0, min.y - tile_height) + offset,
+            Vec2::new(max.x + tile_width / 2.0, max.y + elevation + tile_height) + offset,
+        ))
+    }
+
+    /// Keeps a view with the given half size inside of the bounds. Views bigger than the bounds are centered.
+    pub fn clamp(&self, center: Vec2, half_size: Vec2) -> Vec2 {
+        let min = self.0.min + half_size;
+        let max = self.0.max - half_size;
+        let middle = self.0.center();
+
+        Vec2::new(
+            match min.x <= max.x {
+                true => center.x.clamp(min.x, max.x),
+                false => middle.x,
+            },
+            match min.y <= max.y {
+                true => center.y.clamp(min.y, max.y),
+                false => middle.y,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{
+        input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel},
+        prelude::*,
+        utils::Instant,
+    };
+
+    use crate::{
+        camera::{update_iso_cameras, update_map_bounds, IsoCamera, MapBounds},
+        grid::{Grid, GridBundle, GridSize, TileSize},
+        loading::tilemap::TilemapDefinition,
+        rotate::{rotate_grid, GridRotationEvent, GridRotationFinished},
+        WorldScale,
+    };
+
+    fn app() -> (App, Instant) {
+        let mut app = App::new();
+        app.add_plugins((TaskPoolPlugin::default(), AssetPlugin::default()))
+            .add_asset::<TilemapDefinition>()
+            .add_event::<MouseMotion>()
+            .add_event::<MouseWheel>()
+            .add_event::<GridRotationEvent>()
+            .add_event::<GridRotationFinished>()
+            .init_resource::<Time>()
+            .init_resource::<Input<KeyCode>>()
+            .init_resource::<Input<MouseButton>>()
+            .add_systems(
+                Update,
+                (
+                    rotate_grid,
+                    update_map_bounds.after(rotate_grid),
+                    update_iso_cameras.after(update_map_bounds),
+                ),
+            );
+
+        let start = Instant::now();
+        app.world.resource_mut::<Time>().update_with_instant(start);
+        (app, start)
+    }
+
+    fn spawn_grid(app: &mut App, width: usize, height: usize) -> Entity {
+        app.world
+            .spawn((
+                GridBundle::new(Grid {
+                    tilemap_handle: Handle::default(),
+                    texture_atlas_handle: None,
+                }),
+                TileSize::new(32.0, 16.0),
+                GridSize::new(width, height),
+                WorldScale(1.0),
+            ))
+            .id()
+    }
+
+    fn spawn_camera(app: &mut App, camera: IsoCamera, viewport: Vec2) -> Entity {
+        app.world
+            .spawn((
+                camera,
+                Transform::default(),
+                OrthographicProjection {
+                    area: Rect::from_center_size(Vec2::ZERO, viewport),
+                    ..default()
+                },
+            ))
+            .id()
+    }
+
+    fn update_at(app: &mut App, start: Instant, seconds: f32) {
+        app.world
+            .resource_mut::<Time>()
+            .update_with_instant(start + Duration::from_secs_f32(seconds));
+        app.update();
+    }
+
+    fn translation(app: &App, camera: Entity) -> Vec2 {
+        app.world
+            .get::<Transform>(camera)
+            .unwrap()
+            .translation
+            .truncate()
+    }
+
+    #[test]
+    fn test_bounds_update_after_rotation() {
+        let (mut app, _) = app();
+        let grid = spawn_grid(&mut app, 3, 2);
+
+        app.update();
+
+        assert_eq!(
+            MapBounds(Rect::new(-32.0, -16.0, 48.0, 40.0)),
+            *app.world.get::<MapBounds>(grid).unwrap()
+        );
+
+        app.world.send_event(GridRotationEvent::clockwise());
+        app.update();
+
+        assert_eq!(
+            GridSize::new(2, 3),
+            *app.world.get::<GridSize>(grid).unwrap()
+        );
+        assert_eq!(
+            MapBounds(Rect::new(-48.0, -16.0, 32.0, 40.0)),
+            *app.world.get::<MapBounds>(grid).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_zoom_snaps_to_whole_numbers() {
+        let (mut app, _) = app();
+        let camera = spawn_camera(&mut app, IsoCamera::new(), Vec2::new(800.0, 600.0));
+
+        let scroll = |app: &mut App, y| {
+            app.world.send_event(MouseWheel {
+                unit: MouseScrollUnit::Pixel,
+                x: 0.0,
+                y,
+                window: Entity::PLACEHOLDER,
+            });
+            app.update();
+        };
+
+        scroll(&mut app, 0.3);
+        assert_eq!(2, app.world.get::<IsoCamera>(camera).unwrap().zoom);
+        assert_eq!(
+            0.5,
+            app.world
+                .get::<OrthographicProjection>(camera)
+                .unwrap()
+                .scale
+        );
+
+        for _ in 0..5 {
+            scroll(&mut app, 3.0);
+        }
+        assert_eq!(4, app.world.get::<IsoCamera>(camera).unwrap().zoom);
+
+        for _ in 0..5 {
+            scroll(&mut app, -1.0);
+        }
+        assert_eq!(1, app.world.get::<IsoCamera>(camera).unwrap().zoom);
+        assert_eq!(
+            1.0,
+            app.world
+                .get::<OrthographicProjection>(camera)
+                .unwrap()
+                .scale
+        );
+    }
+
+    #[test]
+    fn test_keyboard_and_drag_panning() {
+        let (mut app, start) = app();
+        let camera = spawn_camera(
+            &mut app,
+            IsoCamera::new().with_pan_speed(100.0).with_zoom(2, 1, 4),
+            Vec2::new(800.0, 600.0),
+        );
+
+        app.world
+            .resource_mut::<Input<KeyCode>>()
+            .press(KeyCode::Right);
+        update_at(&mut app, start, 1.0);
+
+        assert_eq!(Vec2::new(50.0, 0.0), translation(&app, camera));
+
+        app.world
+            .resource_mut::<Input<KeyCode>>()
+            .release(KeyCode::Right);
+        app.world
+            .resource_mut::<Input<MouseButton>>()
+            .press(MouseButton::Middle);
+        app.world.send_event(MouseMotion {
+            delta: Vec2::new(20.0, 10.0),
+        });
+        update_at(&mut app, start, 2.0);
+
+        assert_eq!(Vec2::new(40.0, 5.0), translation(&app, camera));
+    }
+
+    #[test]
+    fn test_follow_target() {
+        let (mut app, start) = app();
+        let target = app
+            .world
+            .spawn(GlobalTransform::from_xyz(100.0, 50.0, 0.0))
+            .id();
+        let camera = spawn_camera(
+            &mut app,
+            IsoCamera::new().with_target(target, 2.0),
+            Vec2::new(800.0, 600.0),
+        );
+
+        update_at(&mut app, start, 0.5);
+
+        let halfway = translation(&app, camera);
+        assert!(halfway.x > 0.0 && halfway.x < 100.0);
+        assert!(halfway.y > 0.0 && halfway.y < 50.0);
+
+        update_at(&mut app, start, 10.0);
+
+        assert!(translation(&app, camera).distance(Vec2::new(100.0, 50.0)) < 0.01);
+    }
+
+    #[test]
+    fn test_clamp_to_map_bounds() {
+        let (mut app, start) = app();
+        spawn_grid(&mut app, 20, 20);
+        let camera = spawn_camera(
+            &mut app,
+            IsoCamera::new().with_pan_speed(10000.0),
+            Vec2::new(200.0, 100.0),
+        );
+        app.update();
+
+        let mut keys = app.world.resource_mut::<Input<KeyCode>>();
+        keys.press(KeyCode::Left);
+        keys.press(KeyCode::Up);
+        update_at(&mut app, start, 1.0);
+
+        // The grid spans from -320 to 320 horizontally and from -16 to 320 vertically.
+        assert_eq!(Vec2::new(-220.0, 270.0), translation(&app, camera));
+
+        let mut bounds = app.world.query::<&mut MapBounds>();
+        bounds.single_mut(&mut app.world).0 = Rect::new(-50.0, -50.0, 50.0, 50.0);
+        update_at(&mut app, start, 2.0);
+
+        assert_eq!(Vec2::ZERO, translation(&app, camera));
+    }
+}
@@ -0,0 +1,660 @@
+use std::{collections::BTreeMap, ops::Range};
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    grid::{Grid, GridOffset, GridOrientation, GridPosition, GridSize, TileSize},
+    loading::tilemap::{
+        LayerDefinition, TileIdentifier, TilemapDefinition, TilemapDefinitionBuilder,
+    },
+    projection::GridProjection,
+    reloading::despawn_keeping_objects,
+    spawning::TilemapSpawner,
+    tilemap::TilemapOrderId,
+    transition::GridRotationTransition,
+    DynamicObject, WorldScale,
+};
+
+/// Splits the layers of a grid into square chunks, which are only spawned while they are near a camera.
+/// Has to be added to the grid entity before its tilemap is spawned.
+#[derive(Component, Debug, Copy, Clone, PartialEq)]
+pub struct TilemapChunking {
+    /// Width and height of a chunk in tiles.
+    pub chunk_size: usize,
+    /// Chunks whose center is closer to a camera than this distance are spawned.
+    pub load_distance: f32,
+    /// Spawned chunks whose center is further away from every camera than this distance are despawned.
+    pub unload_distance: f32,
+}
+
+/// Position of a chunk in the grid as it was authored, counted in chunks.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ChunkPosition {
+    pub x: usize,
+    pub y: usize,
+}
+
+/// A chunk of a tilemap layer. The tiles inside of the chunk are its children.
+#[derive(Component, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TileChunk {
+    pub position: ChunkPosition,
+    pub layer: usize,
+}
+
+/// The currently spawned chunks of a grid, with the chunk entity of every layer.
+#[derive(Component, Default, Debug, Clone)]
+pub struct LoadedChunks(HashMap<ChunkPosition, Vec<Entity>>);
+
+/// Bundle for creating chunk entities as children of a tilemap layer.
+#[derive(Bundle)]
+pub struct TileChunkBundle {
+    chunk: TileChunk,
+    name: Name,
+    spatial: SpatialBundle,
+}
+
+/// Where the tiles of a grid are placed in the world.
+struct ChunkPlacement<'a> {
+    grid: Entity,
+    definition: &'a TilemapDefinition,
+    orientation: GridOrientation,
+    canonical_size: GridSize,
+    tile_size: TileSize,
+    scale: WorldScale,
+    offset: GridOffset,
+    projection: &'a GridProjection,
+}
+
+type ChunkedGridQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Grid,
+        &'static TilemapChunking,
+        &'static mut LoadedChunks,
+        &'static GridOrientation,
+        &'static GridSize,
+        &'static TileSize,
+        &'static WorldScale,
+        &'static GridProjection,
+        Option<&'static GridOffset>,
+        &'static GlobalTransform,
+        Option<&'static GridRotationTransition>,
+    ),
+>;
+
+impl Default for TilemapChunking {
+    fn default() -> Self {
+        Self {
+            chunk_size: 16,
+            load_distance: 1024.0,
+            unload_distance: 1280.0,
+        }
+    }
+}
+
+impl TilemapChunking {
+    pub fn new(chunk_size: usize) -> Self {
+        Self {
+            chunk_size,
+            ..default()
+        }
+    }
+
+    /// Sets the distances for spawning and despawning chunks.
+    /// The unload distance should be larger, so chunks at the border are not spawned and despawned every frame.
+    pub fn with_distances(mut self, load_distance: f32, unload_distance: f32) -> Self {
+        self.load_distance = load_distance;
+        self.unload_distance = unload_distance;
+        self
+    }
+
+    /// Number of chunks along the width and height of a grid with the given canonical size.
+    pub fn chunk_count(&self, canonical_size: GridSize) -> (usize, usize) {
+        let chunk_size = self.chunk_size.max(1);
+
+        (
+            canonical_size.width.div_ceil(chunk_size),
+            canonical_size.height.div_ceil(chunk_size),
+        )
+    }
+
+    /// The chunk a canonical grid position belongs to. Positions at negative coordinates belong to no chunk.
+    pub fn chunk_of(&self, canonical: GridPosition) -> Option<ChunkPosition> {
+        let chunk_size = self.chunk_size.max(1);
+        let (x, y, _) = canonical.indices()?;
+
+        Some(ChunkPosition::new(x / chunk_size, y / chunk_size))
+    }
+
+    /// The canonical columns and rows of the chunk, clamped to the grid.
+    fn bounds(
+        &self,
+        chunk: ChunkPosition,
+        canonical_size: GridSize,
+    ) -> (Range<usize>, Range<usize>) {
+        let chunk_size = self.chunk_size.max(1);
+        let (x, y) = (chunk.x * chunk_size, chunk.y * chunk_size);
+
+        (
+            x..(x + chunk_size).min(canonical_size.width),
+            y..(y + chunk_size).min(canonical_size.height),
+        )
+    }
+}
+
+impl ChunkPosition {
+    pub fn new(x: usize, y: usize) -> Self {
+        Self { x, y }
+    }
+}
+
+impl LoadedChunks {
+    pub fn contains(&self, chunk: ChunkPosition) -> bool {
+        self.0.contains_key(&chunk)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ChunkPosition> {
+        self.0.keys()
+    }
+
+    /// Forgets all chunks and returns their entities.
+    pub(crate) fn drain(&mut self) -> Vec<Entity> {
+        self.0.drain().flat_map(|(_, entities)| entities).collect()
+    }
+}
+
+impl TileChunkBundle {
+    pub fn new(position: ChunkPosition, layer: usize) -> Self {
+        Self {
+            chunk: TileChunk { position, layer },
+            name: Name::new(format!("Chunk ({},{},{})", position.x, position.y, layer)),
+            spatial: SpatialBundle::default(),
+        }
+    }
+}
+
+/// Spawns the chunks of chunked grids that came close to a camera and despawns the ones far away from all cameras.
+/// Chunks are spawned in the current view of the grid, grids which are rotating are left as they are.
+#[allow(clippy::too_many_arguments)]
+pub fn update_chunks(
+    mut commands: Commands,
+    spawner: Res<TilemapSpawner>,
+    tilemaps: Res<Assets<TilemapDefinition>>,
+    cameras: Query<&GlobalTransform, With<Camera>>,
+    mut grids: ChunkedGridQuery,
+    children: Query<&Children>,
+    layers: Query<&TilemapOrderId>,
+    dynamic_objects: Query<(), With<DynamicObject>>,
+) {
+    let cameras = cameras
+        .iter()
+        .map(|transform| transform.translation().truncate())
+        .collect::<Vec<_>>();
+
+    for (
+        grid_entity,
+        grid,
+        chunking,
+        mut loaded_chunks,
+        orientation,
+        size,
+        tile_size,
+        scale,
+        projection,
+        offset,
+        grid_transform,
+        transition,
+    ) in grids.iter_mut()
+    {
+        if transition.is_some_and(|t| t.is_running()) {
+            continue;
+        }
+
+        let Some(definition) = tilemaps.get(&grid.tilemap_handle) else {
+            continue;
+        };
+
+        let placement = ChunkPlacement {
+            grid: grid_entity,
+            definition,
+            orientation: *orientation,
+            canonical_size: orientation.canonical_size(*size),
+            tile_size: *tile_size,
+            scale: *scale,
+            offset: offset.copied().unwrap_or(GridOffset(Vec2::default())),
+            projection,
+        };
+        let layer_entities = children
+            .get(grid_entity)
+            .map(|children| {
+                children
+                    .iter()
+                    .filter_map(|child| Some((layers.get(*child).ok()?.id(), *child)))
+                    .collect::<HashMap<_, _>>()
+            })
+            .unwrap_or_default();
+        let (columns, rows) = chunking.chunk_count(placement.canonical_size);
+
+        for y in 0..rows {
+            for x in 0..columns {
+                let chunk = ChunkPosition::new(x, y);
+                let center = grid_transform
+                    .transform_point(chunk_center(chunking, chunk, &placement))
+                    .truncate();
+                let distance = cameras
+                    .iter()
+                    .map(|camera| camera.distance(center))
+                    .fold(f32::INFINITY, f32::min);
+
+                if !loaded_chunks.contains(chunk) && distance <= chunking.load_distance {
+                    let entities = spawn_chunk(
+                        &mut commands,
+                        &spawner,
+                        chunking,
+                        chunk,
+                        &placement,
+                        &layer_entities,
+                    );
+                    loaded_chunks.0.insert(chunk, entities);
+                } else if loaded_chunks.contains(chunk) && distance > chunking.unload_distance {
+                    for entity in loaded_chunks.0.remove(&chunk).unwrap_or_default() {
+                        despawn_keeping_objects(
+                            &mut commands,
+                            entity,
+                            grid_entity,
+                            &children,
+                            &dynamic_objects,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Completes a tilemap extracted from a chunked grid with the tiles of its unloaded chunks,
+/// which are taken from the definition the grid was spawned from.
+pub fn with_unloaded_chunks(
+    extracted: TilemapDefinition,
+    base: &TilemapDefinition,
+    chunking: &TilemapChunking,
+    loaded_chunks: &LoadedChunks,
+) -> TilemapDefinition {
+    let mut layers = extracted
+        .layers()
+        .iter()
+        .map(|layer| {
+            let elevations = (0..layer.tiles().len())
+                .map(|y| {
+                    (0..layer.tiles()[y].len())
+                        .map(|x| layer.elevation(x, y))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            (layer.ordering_id(), (layer.tiles().to_vec(), elevations))
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    for layer in base.layers() {
+        let (rows, elevations) = layers.entry(layer.ordering_id()).or_default();
+
+        for (y, row) in layer.tiles().iter().enumerate() {
+            for (x, identifier) in row.iter().enumerate() {
+                if chunking
+                    .chunk_of(GridPosition::from_indices(x, y, 0))
+                    .is_some_and(|chunk| loaded_chunks.contains(chunk))
+                {
+                    continue;
+                }
+
+                if rows.len() <= y {
+                    rows.resize(y + 1, Vec::new());
+                    elevations.resize(y + 1, Vec::new());
+                }
+
+                if rows[y].len() <= x {
+                    rows[y].resize(x + 1, TileIdentifier::empty());
+                    elevations[y].resize(x + 1, 0.0);
+                }
+
+                rows[y][x] = identifier.clone();
+                elevations[y][x] = layer.elevation(x, y);
+            }
+        }
+    }
+
+    let tile_size = extracted.tile_size();
+    let mut builder = TilemapDefinitionBuilder::new(extracted.name())
+        .with_tile_size(tile_size.width(), tile_size.height());
+
+    for link in extracted.tilesets() {
+        builder = builder.add_tileset(link.clone());
+    }
+
+    for (layer_id, (mut rows, mut elevations)) in layers {
+        let width = rows.iter().map(Vec::len).max().unwrap_or_default();
+
+        for row in rows.iter_mut() {
+            row.resize(width, TileIdentifier::empty());
+        }
+
+        for row in elevations.iter_mut() {
+            row.resize(width, 0.0);
+        }
+
+        builder = builder.add_layer(
+            LayerDefinition::new(layer_id)
+                .with_tiles(rows)
+                .with_elevations(elevations),
+        );
+    }
+
+    builder.build()
+}
+
+/// The center of a chunk on the ground layer, relative to its grid.
+fn chunk_center(
+    chunking: &TilemapChunking,
+    chunk: ChunkPosition,
+    placement: &ChunkPlacement,
+) -> Vec3 {
+    let (columns, rows) = chunking.bounds(chunk, placement.canonical_size);
+    let min = GridPosition::from_indices(columns.start, rows.start, 0);
+    let last = GridPosition::from_indices(
+        columns.end.max(columns.start + 1) - 1,
+        rows.end.max(rows.start + 1) - 1,
+        0,
+    );
+    let center = (Vec3::from(placement.orientation.to_view(min, placement.canonical_size))
+        + Vec3::from(
+            placement
+                .orientation
+                .to_view(last, placement.canonical_size),
+        ))
+        / 2.0;
+
+    placement.projection.grid_to_world(
+        center,
+        placement.tile_size.width() * placement.scale.0,
+        placement.tile_size.height() * placement.scale.0,
+    ) + placement.offset.0.extend(0.0)
+}
+
+/// Spawns a chunk entity for every layer with tiles inside of the chunk and returns them.
+fn spawn_chunk(
+    commands: &mut Commands,
+    spawner: &TilemapSpawner,
+    chunking: &TilemapChunking,
+    chunk: ChunkPosition,
+    placement: &ChunkPlacement,
+    layer_entities: &HashMap<usize, Entity>,
+) -> Vec<Entity> {
+    let (columns, rows) = chunking.bounds(chunk, placement.canonical_size);
+    let mut chunk_entities = Vec::new();
+
+    for layer in placement.definition.layers() {
+        let layer_id = layer.ordering_id() as usize;
+        let Some(layer_entity) = layer_entities.get(&layer_id) else {
+            continue;
+        };
+        let Some(chunk_rows) = layer
+            .tiles()
+            .get(rows.start..rows.end.min(layer.tiles().len()))
+            .filter(|chunk_rows| chunk_rows.iter().any(|row| row.len() > columns.start))
+        else {
+            continue;
+        };
+
+        let chunk_entity = commands.spawn(TileChunkBundle::new(chunk, layer_id)).id();
+        commands.entity(*layer_entity).add_child(chunk_entity);
+        commands.entity(chunk_entity).with_children(|tiles| {
+            for (y, row) in chunk_rows.iter().enumerate() {
+                for (x, identifier) in row.iter().enumerate().take(columns.end).skip(columns.start)
+                {
+                    let canonical = GridPosition::from_indices(x, rows.start + y, layer_id);
+
+                    spawner.spawn_tile(
+                        tiles,
+                        placement.grid,
+                        placement.definition,
+                        identifier,
+                        placement
+                            .orientation
+                            .to_view(canonical, placement.canonical_size),
+                        layer.elevation(x, rows.start + y),
+                        placement.orientation,
+                        placement.tile_size,
+                        placement.scale,
+                        placement.offset,
+                        placement.projection,
+                    );
+                }
+            }
+        });
+        chunk_entities.push(chunk_entity);
+    }
+
+    chunk_entities
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, time::Instant};
+
+    use bevy::prelude::*;
+
+    use crate::{
+        chunking::{
+            update_chunks, with_unloaded_chunks, ChunkPosition, LoadedChunks, TileChunk,
+            TilemapChunking,
+        },
+        grid::{Grid, GridBundle, GridPosition, GridSize},
+        loading::{
+            tilemap::{
+                LayerDefinition, TileIdentifier, TilemapDefinition, TilemapDefinitionBuilder,
+                TilesetLink,
+            },
+            tileset::{
+                ImageDimensions, SourceDefinition, TileDefinition, TilesetDefinition,
+                TilesetDefinitionBuilder,
+            },
+        },
+        spawning::{spawn_tilemap, TilemapSpawner},
+        tile::TileMarker,
+    };
+
+    fn definition(size: usize) -> TilemapDefinition {
+        TilemapDefinitionBuilder::new("testmap")
+            .with_tile_size(64, 64)
+            .add_tileset(TilesetLink::new(Path::new("tiles.its"), 't'))
+            .add_layer(
+                LayerDefinition::new(0).with_tiles(
+                    (0..size)
+                        .map(|y| {
+                            (0..size)
+                                .map(|x| TileIdentifier::new(((x + y) % 2) as u32, 't'))
+                                .collect()
+                        })
+                        .collect(),
+                ),
+            )
+            .build()
+    }
+
+    fn app(definition: TilemapDefinition, chunking: TilemapChunking) -> (App, Entity, Entity) {
+        let tileset = TilesetDefinitionBuilder::new(SourceDefinition::new(
+            Path::new("tiles.png"),
+            ImageDimensions::new(128, 64),
+        ))
+        .with_tile_size(64, 64)
+        .add_tile(TileDefinition::new_standard(0, 0, 0))
+        .add_tile(TileDefinition::new_standard(1, 1, 0))
+        .build()
+        .unwrap();
+
+        let mut spawner = TilemapSpawner::new();
+        spawner.add_tileset(Path::new("tiles.its"), tileset, Handle::default());
+
+        let mut app = App::new();
+        app.add_plugins((TaskPoolPlugin::default(), AssetPlugin::default()))
+            .add_asset::<TilemapDefinition>()
+            .add_asset::<TilesetDefinition>()
+            .insert_resource(spawner)
+            .add_systems(Update, (spawn_tilemap, update_chunks.after(spawn_tilemap)));
+
+        let handle = app
+            .world
+            .resource_mut::<Assets<TilemapDefinition>>()
+            .add(definition);
+        let grid = app
+            .world
+            .spawn((
+                GridBundle::new(Grid {
+                    tilemap_handle: handle,
+                    texture_atlas_handle: None,
+                }),
+                chunking,
+            ))
+            .id();
+        let camera = app
+            .world
+            .spawn((Camera::default(), GlobalTransform::default()))
+            .id();
+
+        (app, grid, camera)
+    }
+
+    fn tile_count(app: &mut App) -> usize {
+        app.world
+            .query_filtered::<(), With<TileMarker>>()
+            .iter(&app.world)
+            .count()
+    }
+
+    #[test]
+    fn test_chunk_count() {
+        let chunking = TilemapChunking::new(16);
+
+        assert_eq!((2, 1), chunking.chunk_count(GridSize::new(17, 16)));
+        assert_eq!(
+            Some(ChunkPosition::new(1, 0)),
+            chunking.chunk_of(GridPosition::new(16, 15, 3))
+        );
+        assert_eq!(None, chunking.chunk_of(GridPosition::new(-1, 15, 3)));
+    }
+
+    #[test]
+    fn test_spawn_near_chunks() {
+        let chunking = TilemapChunking::new(4).with_distances(100.0, 150.0);
+        let (mut app, grid, _) = app(definition(16), chunking);
+
+        app.update();
+        app.update();
+
+        let loaded = app.world.get::<LoadedChunks>(grid).unwrap().clone();
+        let mut chunks = loaded.iter().copied().collect::<Vec<_>>();
+        chunks.sort();
+
+        // The first chunk is centered at (1.5, 1.5), 48 units away from the camera. The next ones are at least 170 away.
+        assert_eq!(vec![ChunkPosition::new(0, 0)], chunks);
+        assert_eq!(16, tile_count(&mut app));
+
+        let chunk = app
+            .world
+            .query::<&TileChunk>()
+            .single(&app.world)
+            .to_owned();
+        assert_eq!(0, chunk.layer);
+    }
+
+    #[test]
+    fn test_despawn_far_chunks() {
+        let chunking = TilemapChunking::new(4).with_distances(100.0, 150.0);
+        let (mut app, grid, camera) = app(definition(16), chunking);
+
+        app.update();
+        app.update();
+        assert_eq!(1, app.world.get::<LoadedChunks>(grid).unwrap().len());
+
+        // Moves the camera to the center of chunk (3, 3), which is at grid position (13.5, 13.5).
+        app.world
+            .entity_mut(camera)
+            .insert(GlobalTransform::from_xyz(0.0, 432.0, 0.0));
+        app.update();
+        app.update();
+
+        let loaded = app.world.get::<LoadedChunks>(grid).unwrap();
+        assert!(!loaded.contains(ChunkPosition::new(0, 0)));
+        assert!(loaded.contains(ChunkPosition::new(3, 3)));
+        assert_eq!(loaded.len() * 16, tile_count(&mut app));
+    }
+
+    #[test]
+    fn test_with_unloaded_chunks() {
+        let base = definition(4);
+        let chunking = TilemapChunking::new(2);
+        let extracted = TilemapDefinitionBuilder::new("testmap")
+            .with_tile_size(64, 64)
+            .add_tileset(TilesetLink::new(Path::new("tiles.its"), 't'))
+            .add_layer(LayerDefinition::new(0).with_tiles(vec![
+                vec![TileIdentifier::empty(), TileIdentifier::new(0, 't')],
+                vec![TileIdentifier::new(1, 't'), TileIdentifier::new(1, 't')],
+            ]))
+            .build();
+        let mut loaded = LoadedChunks::default();
+        loaded.0.insert(ChunkPosition::new(0, 0), Vec::new());
+
+        let merged = with_unloaded_chunks(extracted, &base, &chunking, &loaded);
+        let tiles = merged.layers()[0].tiles();
+
+        assert_eq!(4, tiles.len());
+        assert!(tiles.iter().all(|row| row.len() == 4));
+        assert!(tiles[0][0].is_empty());
+        assert_eq!(TileIdentifier::new(0, 't'), tiles[0][1]);
+        assert_eq!(base.layers()[0].tiles()[3][3], tiles[3][3]);
+        assert_eq!(base.layers()[0].tiles()[0][2], tiles[0][2]);
+    }
+
+    #[test]
+    fn test_large_map_spawns_loaded_chunks() {
+        let definition = definition(512);
+        let chunking = TilemapChunking::new(16).with_distances(2048.0, 2560.0);
+        let (mut app, grid, _) = app(definition, chunking);
+
+        app.update();
+        app.update();
+
+        let loaded = app.world.get::<LoadedChunks>(grid).unwrap().len();
+        let tiles = tile_count(&mut app);
+
+        assert!(loaded > 0);
+        assert_eq!(loaded * 16 * 16, tiles);
+        assert!(tiles < 512 * 512 / 20, "{} tiles were spawned", tiles);
+    }
+
+    /// Timing depends on the machine, run with `cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn test_stress_large_map() {
+        let definition = definition(512);
+        let chunking = TilemapChunking::new(16).with_distances(2048.0, 2560.0);
+        let (mut app, _, _) = app(definition, chunking);
+
+        let start = Instant::now();
+        app.update();
+        app.update();
+        let elapsed = start.elapsed();
+
+        assert!(elapsed.as_secs_f32() < 10.0, "spawning took {:?}", elapsed);
+    }
+}
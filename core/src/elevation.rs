@@ -0,0 +1,206 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    grid::{GridOrientation, GridPosition, GridPositionF},
+    movement::GridDirection,
+    tile::TileProperties,
+};
+
+/// Height of a tile in tile heights, on top of the elevation of its layer.
+/// Dynamic objects get the elevation of the surface they stand on while they move.
+#[derive(Component, Default, Debug, Copy, Clone, PartialEq)]
+pub struct TileElevation {
+    pub height: f32,
+    pub ramp: Option<Ramp>,
+}
+
+/// A tile whose surface rises from the height of the tile at one edge to `rise` tile heights above it
+/// at the opposite edge, so objects walking over it rise smoothly.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ramp {
+    /// The direction in which the ramp rises.
+    pub direction: GridDirection,
+    pub rise: f32,
+}
+
+impl TileElevation {
+    pub fn new(height: f32) -> Self {
+        Self { height, ramp: None }
+    }
+
+    pub fn with_ramp(mut self, direction: GridDirection, rise: f32) -> Self {
+        self.ramp = Some(Ramp { direction, rise });
+        self
+    }
+
+    /// The elevation of a tile with the given properties.
+    /// Ramps are authored with a `ramp` property naming the direction in which they rise, one of
+    /// `"north"`, `"east"`, `"south"` or `"west"`, and an optional `ramp_rise` property which defaults to 1.
+    pub fn from_properties(height: f32, properties: &TileProperties) -> Self {
+        let direction = properties.get_str("ramp").and_then(|name| match name {
+            "north" => Some(GridDirection::North),
+            "east" => Some(GridDirection::East),
+            "south" => Some(GridDirection::South),
+            "west" => Some(GridDirection::West),
+            _ => {
+                warn!("Unknown ramp direction '{}'.", name);
+                None
+            }
+        });
+
+        match direction {
+            Some(direction) => Self::new(height).with_ramp(
+                direction,
+                properties.get_float("ramp_rise").unwrap_or(1.0) as f32,
+            ),
+            None => Self::new(height),
+        }
+    }
+
+    /// The elevation of the surface at an offset from the center of the tile in grid space.
+    /// Offsets beyond the edges of the tile are clamped to them.
+    pub fn surface(&self, offset: Vec2) -> f32 {
+        let Some(ramp) = self.ramp else {
+            return self.height;
+        };
+        let along = match ramp.direction {
+            GridDirection::North => -offset.y,
+            GridDirection::East => offset.x,
+            GridDirection::South => offset.y,
+            GridDirection::West => -offset.x,
+        };
+
+        self.height + ramp.rise * (along.clamp(-0.5, 0.5) + 0.5)
+    }
+
+    /// The elevation of the surface at the center of the tile, halfway up a ramp.
+    pub fn center(&self) -> f32 {
+        self.surface(Vec2::ZERO)
+    }
+
+    /// Rotates the ramp together with the grid of the tile.
+    pub fn rotated(mut self, clockwise: bool) -> Self {
+        if let Some(ramp) = self.ramp.as_mut() {
+            ramp.direction = match clockwise {
+                true => ramp.direction.rotated_c(),
+                false => ramp.direction.rotated_cc(),
+            };
+        }
+
+        self
+    }
+
+    /// Converts the elevation of a tile of the canonical grid into the view with the given orientation.
+    pub fn to_view(self, orientation: GridOrientation) -> Self {
+        (0..orientation.quarter_turns()).fold(self, |elevation, _| elevation.rotated(true))
+    }
+
+    /// Whether the tile is placed like a tile without an elevation.
+    pub fn is_flat_ground(&self) -> bool {
+        self.height == 0.0 && self.ramp.is_none()
+    }
+}
+
+/// The elevation of the surface at a position in grid space, given by the tile at the nearest cell of the same layer.
+/// Positions without an elevated tile are on the ground of their layer.
+pub fn surface_elevation(
+    position: GridPositionF,
+    tiles: &HashMap<GridPosition, TileElevation>,
+) -> f32 {
+    let Ok(cell) = position.round() else {
+        return 0.0;
+    };
+
+    tiles
+        .get(&cell)
+        .map(|elevation| {
+            elevation.surface(Vec2::new(
+                position.x - cell.x as f32,
+                position.y - cell.y as f32,
+            ))
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{prelude::*, utils::HashMap};
+
+    use crate::{
+        elevation::{surface_elevation, TileElevation},
+        grid::{GridOrientation, GridPosition, GridPositionF},
+        movement::GridDirection,
+        tile::TileProperties,
+    };
+
+    #[test]
+    fn test_ramp_surface() {
+        let ramp = TileElevation::new(1.0).with_ramp(GridDirection::East, 2.0);
+
+        assert_eq!(1.0, ramp.surface(Vec2::new(-0.5, 0.3)));
+        assert_eq!(2.0, ramp.center());
+        assert_eq!(3.0, ramp.surface(Vec2::new(0.5, -0.3)));
+        assert_eq!(3.0, ramp.surface(Vec2::new(2.0, 0.0)));
+        assert_eq!(1.0, TileElevation::new(1.0).surface(Vec2::new(0.4, 0.4)));
+    }
+
+    #[test]
+    fn test_ramp_from_properties() {
+        let properties = TileProperties::new()
+            .with("ramp", "north")
+            .with("ramp_rise", 0.5);
+
+        assert_eq!(
+            TileElevation::new(2.0).with_ramp(GridDirection::North, 0.5),
+            TileElevation::from_properties(2.0, &properties)
+        );
+        assert_eq!(
+            TileElevation::new(2.0),
+            TileElevation::from_properties(2.0, &TileProperties::new())
+        );
+    }
+
+    #[test]
+    fn test_rotate_ramp() {
+        let ramp = TileElevation::new(0.0).with_ramp(GridDirection::East, 1.0);
+
+        assert_eq!(
+            Some(GridDirection::North),
+            ramp.rotated(true).ramp.map(|r| r.direction)
+        );
+        assert_eq!(
+            Some(GridDirection::South),
+            ramp.to_view(GridOrientation::Deg270)
+                .ramp
+                .map(|r| r.direction)
+        );
+        assert_eq!(ramp, ramp.rotated(false).rotated(true));
+    }
+
+    #[test]
+    fn test_surface_elevation() {
+        let mut tiles = HashMap::default();
+        tiles.insert(
+            GridPosition::new(1, 0, 0),
+            TileElevation::new(0.0).with_ramp(GridDirection::East, 1.0),
+        );
+        tiles.insert(GridPosition::new(2, 0, 0), TileElevation::new(1.0));
+
+        assert_eq!(
+            0.0,
+            surface_elevation(GridPositionF::new(0.2, 0.0, 0.0), &tiles)
+        );
+        assert_eq!(
+            0.75,
+            surface_elevation(GridPositionF::new(1.25, 0.0, 0.0), &tiles)
+        );
+        assert_eq!(
+            1.0,
+            surface_elevation(GridPositionF::new(2.0, 0.0, 0.0), &tiles)
+        );
+        assert_eq!(
+            0.0,
+            surface_elevation(GridPositionF::new(1.0, 0.0, 1.0), &tiles)
+        );
+    }
+}
@@ -1,6 +1,8 @@
+use std::fmt;
+
 use bevy::prelude::*;
 
-use crate::loading::tilemap::TilemapDefinition;
+use crate::{loading::tilemap::TilemapDefinition, projection::GridProjection};
 
 //use crate::loading::TilemapFile;
 
@@ -15,12 +17,36 @@ pub struct Grid {
     pub texture_atlas_handle: Option<Handle<TextureAtlas>>,
 }
 
-/// Identifies a position in the grid.
-#[derive(Default, Component, Clone, PartialEq, Copy, Debug,)]
+/// The grid an object belongs to. Objects without it belong to the grid they are a descendant of.
+/// Lets objects outside of the hierarchy of a grid, like dynamic objects, be part of one of several grids.
+#[derive(Component, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct GridMember(pub Entity);
+
+/// Identifies a position in the grid. Positions outside of the grid, even negative ones, are valid.
+#[derive(Default, Component, Clone, PartialEq, Eq, Hash, Copy, Debug)]
 pub struct GridPosition {
-    pub x: usize,
-    pub y: usize,
-    pub layer: usize,
+    pub x: i32,
+    pub y: i32,
+    pub layer: i32,
+}
+
+/// A position in grid space which can lie between cells, like the position of a moving object.
+#[derive(Default, Clone, PartialEq, Copy, Debug)]
+pub struct GridPositionF {
+    pub x: f32,
+    pub y: f32,
+    pub layer: f32,
+}
+
+/// Why a position in grid space is no grid position.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GridPositionError {
+    /// A coordinate is infinite or not a number.
+    NotFinite,
+    /// A coordinate is too big for a grid position.
+    OutOfRange,
+    /// A coordinate lies between two cells.
+    Fractional,
 }
 
 /// Bundle for creating grid entities.
@@ -28,6 +54,27 @@ pub struct GridPosition {
 pub struct GridBundle {
     _g: GridMarker,
     grid: Grid,
+    orientation: GridOrientation,
+    projection: GridProjection,
+    spatial: SpatialBundle,
+}
+
+/// Width and height of a grid in tiles, as seen from the current view.
+#[derive(Default, Component, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct GridSize {
+    pub width: usize,
+    pub height: usize,
+}
+
+/// The clockwise rotation of the current view relative to the grid as it was authored.
+/// Positions in the authored grid are called canonical, positions in the rotated grid view positions.
+#[derive(Default, Component, Copy, Clone, PartialEq, Eq, Debug)]
+pub enum GridOrientation {
+    #[default]
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
 }
 
 /// Offset a grid from the center of the world.
@@ -46,41 +93,273 @@ impl GridBundle {
         Self {
             _g: GridMarker,
             grid,
+            orientation: GridOrientation::default(),
+            projection: GridProjection::default(),
+            spatial: SpatialBundle::default(),
         }
     }
 }
 
 impl GridPosition {
-    pub fn new(x: usize, y: usize, layer: usize) -> Self {
+    pub fn new(x: i32, y: i32, layer: i32) -> Self {
         Self { x, y, layer }
     }
 
+    /// The position of a cell at the given indices of the rows and columns of a layer.
+    /// Indices which are too big for a grid position are clamped.
+    pub fn from_indices(x: usize, y: usize, layer: usize) -> Self {
+        let index = |value: usize| i32::try_from(value).unwrap_or(i32::MAX);
+
+        Self::new(index(x), index(y), index(layer))
+    }
+
+    pub fn as_ivec3(self) -> IVec3 {
+        IVec3::new(self.x, self.y, self.layer)
+    }
+
+    /// The indices of the column, row and layer of the position, if it is not negative.
+    pub fn indices(self) -> Option<(usize, usize, usize)> {
+        Some((
+            usize::try_from(self.x).ok()?,
+            usize::try_from(self.y).ok()?,
+            usize::try_from(self.layer).ok()?,
+        ))
+    }
+
+    /// Whether the position is inside of a grid with the given size, ignoring its layer.
+    pub fn is_inside(self, size: GridSize) -> bool {
+        usize::try_from(self.x).is_ok_and(|x| x < size.width)
+            && usize::try_from(self.y).is_ok_and(|y| y < size.height)
+    }
+
     /// Rotates the grid position clockwise
     pub fn rotate_c(self, n: usize) -> Self {
+        self.rotate_c_within(GridSize::new(n, n))
+    }
+
+    /// rotates the grid position counterclockwise
+    pub fn rotate_cc(self, n: usize) -> Self {
+        self.rotate_cc_within(GridSize::new(n, n))
+    }
+
+    /// Rotates the grid position clockwise inside of a grid with the given size.
+    /// The rotated position belongs to a grid of the size `size.rotated()`.
+    /// Positions outside of the grid are rotated around the same center. Coordinates which do not fit
+    /// into a grid position wrap around, so rotating back always gives the original position.
+    pub fn rotate_c_within(self, size: GridSize) -> Self {
         Self {
             x: self.y,
-            y: n - self.x - 1,
+            y: (size.width as i32).wrapping_sub(self.x).wrapping_sub(1),
             layer: self.layer,
         }
     }
 
-    /// rotates the grid position counterclockwise
-    pub fn rotate_cc(self, n: usize) -> Self {
+    /// Rotates the grid position counterclockwise inside of a grid with the given size.
+    /// The rotated position belongs to a grid of the size `size.rotated()`.
+    /// Positions outside of the grid are rotated like in `rotate_c_within`.
+    pub fn rotate_cc_within(self, size: GridSize) -> Self {
         Self {
-            x: n - self.y - 1,
+            x: (size.height as i32).wrapping_sub(self.y).wrapping_sub(1),
             y: self.x,
             layer: self.layer,
         }
     }
 }
 
-impl From<Vec3> for GridPosition {
-    fn from(value: Vec3) -> Self {
+impl GridPositionF {
+    pub fn new(x: f32, y: f32, layer: f32) -> Self {
+        Self { x, y, layer }
+    }
+
+    /// The cell which contains the position.
+    pub fn floor(self) -> Result<GridPosition, GridPositionError> {
+        GridPosition::try_from(Vec3::from(self).floor())
+    }
+
+    /// The cell closest to the position.
+    pub fn round(self) -> Result<GridPosition, GridPositionError> {
+        GridPosition::try_from(Vec3::from(self).round())
+    }
+
+    /// The position at the given part of the way to the other position.
+    pub fn lerp(self, other: GridPositionF, t: f32) -> Self {
+        Self::from(Vec3::from(self).lerp(Vec3::from(other), t))
+    }
+
+    pub fn distance(self, other: GridPositionF) -> f32 {
+        Vec3::from(self).distance(Vec3::from(other))
+    }
+
+    /// Rotates the position clockwise inside of a grid with the given size, like `GridPosition::rotate_c_within`.
+    pub fn rotate_c_within(self, size: GridSize) -> Self {
+        Self::new(self.y, size.width as f32 - self.x - 1.0, self.layer)
+    }
+
+    /// Rotates the position counterclockwise inside of a grid with the given size, like `GridPosition::rotate_cc_within`.
+    pub fn rotate_cc_within(self, size: GridSize) -> Self {
+        Self::new(size.height as f32 - self.y - 1.0, self.x, self.layer)
+    }
+}
+
+impl GridMember {
+    /// Finds the grid of an object, first by its membership and then by its ancestors.
+    pub fn find_grid(
+        entity: Entity,
+        members: &Query<&GridMember>,
+        parents: &Query<&Parent>,
+        is_grid: impl Fn(Entity) -> bool,
+    ) -> Option<Entity> {
+        match members.get(entity) {
+            Ok(member) => Some(member.0),
+            Err(_) => parents
+                .iter_ancestors(entity)
+                .find(|ancestor| is_grid(*ancestor)),
+        }
+    }
+}
+
+impl GridSize {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self { width, height }
+    }
+
+    /// The size of the grid after a rotation by 90 degrees.
+    pub fn rotated(self) -> Self {
         Self {
-            x: value.x as usize,
-            y: value.y as usize,
-            layer: value.z as usize,
+            width: self.height,
+            height: self.width,
+        }
+    }
+}
+
+impl GridOrientation {
+    pub fn degrees(&self) -> u32 {
+        self.quarter_turns() * 90
+    }
+
+    /// Number of clockwise quarter turns from the canonical grid.
+    pub fn quarter_turns(&self) -> u32 {
+        match self {
+            GridOrientation::Deg0 => 0,
+            GridOrientation::Deg90 => 1,
+            GridOrientation::Deg180 => 2,
+            GridOrientation::Deg270 => 3,
+        }
+    }
+
+    pub fn from_quarter_turns(turns: u32) -> Self {
+        match turns % 4 {
+            0 => GridOrientation::Deg0,
+            1 => GridOrientation::Deg90,
+            2 => GridOrientation::Deg180,
+            _ => GridOrientation::Deg270,
+        }
+    }
+
+    /// The orientation after a clockwise rotation.
+    pub fn rotated_c(self) -> Self {
+        Self::from_quarter_turns(self.quarter_turns() + 1)
+    }
+
+    /// The orientation after a counterclockwise rotation.
+    pub fn rotated_cc(self) -> Self {
+        Self::from_quarter_turns(self.quarter_turns() + 3)
+    }
+
+    /// The size of the grid in the current view.
+    pub fn view_size(&self, canonical_size: GridSize) -> GridSize {
+        match self.quarter_turns() % 2 {
+            0 => canonical_size,
+            _ => canonical_size.rotated(),
+        }
+    }
+
+    /// The size of the grid as it was authored.
+    pub fn canonical_size(&self, view_size: GridSize) -> GridSize {
+        self.view_size(view_size)
+    }
+
+    /// Converts a canonical position into the position in the current view.
+    pub fn to_view(&self, canonical: GridPosition, canonical_size: GridSize) -> GridPosition {
+        let mut size = canonical_size;
+        let mut position = canonical;
+
+        for _ in 0..self.quarter_turns() {
+            position = position.rotate_c_within(size);
+            size = size.rotated();
+        }
+
+        position
+    }
+
+    /// Converts a position in the current view into the canonical position.
+    pub fn to_canonical(&self, view: GridPosition, canonical_size: GridSize) -> GridPosition {
+        let mut size = self.view_size(canonical_size);
+        let mut position = view;
+
+        for _ in 0..self.quarter_turns() {
+            position = position.rotate_cc_within(size);
+            size = size.rotated();
         }
+
+        position
+    }
+}
+
+impl From<&TilemapDefinition> for GridSize {
+    fn from(value: &TilemapDefinition) -> Self {
+        let width = value
+            .layers()
+            .iter()
+            .flat_map(|layer| layer.tiles().iter().map(|row| row.len()))
+            .max()
+            .unwrap_or_default();
+        let height = value
+            .layers()
+            .iter()
+            .map(|layer| layer.tiles().len())
+            .max()
+            .unwrap_or_default();
+
+        Self { width, height }
+    }
+}
+
+impl TryFrom<Vec3> for GridPosition {
+    type Error = GridPositionError;
+
+    /// Converts a position in grid space, where the z axis is the layer, into the cell at it.
+    /// Fails for positions between cells, use `GridPositionF::floor` or `GridPositionF::round` for those.
+    fn try_from(value: Vec3) -> Result<Self, Self::Error> {
+        let coordinate = |value: f32| {
+            if !value.is_finite() {
+                Err(GridPositionError::NotFinite)
+            } else if value.fract() != 0.0 {
+                Err(GridPositionError::Fractional)
+            } else if value < i32::MIN as f32 || value >= i32::MAX as f32 {
+                Err(GridPositionError::OutOfRange)
+            } else {
+                Ok(value as i32)
+            }
+        };
+
+        Ok(Self::new(
+            coordinate(value.x)?,
+            coordinate(value.y)?,
+            coordinate(value.z)?,
+        ))
+    }
+}
+
+impl From<IVec3> for GridPosition {
+    fn from(value: IVec3) -> Self {
+        Self::new(value.x, value.y, value.z)
+    }
+}
+
+impl From<GridPosition> for IVec3 {
+    fn from(value: GridPosition) -> Self {
+        value.as_ivec3()
     }
 }
 
@@ -94,6 +373,36 @@ impl From<GridPosition> for Vec3 {
     }
 }
 
+impl From<GridPosition> for GridPositionF {
+    fn from(value: GridPosition) -> Self {
+        Self::new(value.x as f32, value.y as f32, value.layer as f32)
+    }
+}
+
+impl From<Vec3> for GridPositionF {
+    fn from(value: Vec3) -> Self {
+        Self::new(value.x, value.y, value.z)
+    }
+}
+
+impl From<GridPositionF> for Vec3 {
+    fn from(value: GridPositionF) -> Self {
+        Self::new(value.x, value.y, value.layer)
+    }
+}
+
+impl fmt::Display for GridPositionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GridPositionError::NotFinite => write!(f, "coordinate is not finite"),
+            GridPositionError::OutOfRange => write!(f, "coordinate is out of range"),
+            GridPositionError::Fractional => write!(f, "coordinate lies between two cells"),
+        }
+    }
+}
+
+impl std::error::Error for GridPositionError {}
+
 impl TileSize {
     pub fn new(width: f32, height: f32) -> Self {
         Self { width, height }
@@ -110,7 +419,9 @@ impl TileSize {
 
 #[cfg(test)]
 mod tests {
-    use crate::grid::GridPosition;
+    use bevy::prelude::*;
+
+    use crate::grid::{GridOrientation, GridPosition, GridPositionError, GridPositionF, GridSize};
 
     #[test]
     fn test_rotate_clockwise() {
@@ -135,4 +446,174 @@ mod tests {
         assert_eq!(GridPosition::new(3, 0, 0), r1);
         assert_eq!(GridPosition::new(2, 2, 0), r2);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_rotate_rectangular_clockwise() {
+        let size = GridSize::new(4, 2);
+        let p1 = GridPosition::new(0, 0, 0);
+        let p2 = GridPosition::new(3, 1, 1);
+
+        assert_eq!(GridPosition::new(0, 3, 0), p1.rotate_c_within(size));
+        assert_eq!(GridPosition::new(1, 0, 1), p2.rotate_c_within(size));
+    }
+
+    #[test]
+    fn test_rotate_rectangular_counter_clockwise() {
+        let size = GridSize::new(4, 2);
+        let p1 = GridPosition::new(0, 0, 0);
+        let p2 = GridPosition::new(3, 1, 1);
+
+        assert_eq!(GridPosition::new(1, 0, 0), p1.rotate_cc_within(size));
+        assert_eq!(GridPosition::new(0, 3, 1), p2.rotate_cc_within(size));
+    }
+
+    #[test]
+    fn test_rotate_rectangular_full_turn() {
+        let mut size = GridSize::new(5, 3);
+        let start = GridPosition::new(4, 1, 0);
+        let mut position = start;
+
+        for _ in 0..4 {
+            position = position.rotate_c_within(size);
+            size = size.rotated();
+
+            assert!(position.is_inside(size));
+        }
+
+        assert_eq!(start, position);
+        assert_eq!(GridSize::new(5, 3), size);
+    }
+
+    #[test]
+    fn test_orientation_rotation() {
+        let orientation = GridOrientation::default();
+
+        assert_eq!(GridOrientation::Deg90, orientation.rotated_c());
+        assert_eq!(GridOrientation::Deg270, orientation.rotated_cc());
+        assert_eq!(180, orientation.rotated_c().rotated_c().degrees());
+        assert_eq!(orientation, orientation.rotated_c().rotated_cc());
+    }
+
+    #[test]
+    fn test_orientation_view_position() {
+        let size = GridSize::new(4, 2);
+        let canonical = GridPosition::new(3, 1, 0);
+
+        assert_eq!(canonical, GridOrientation::Deg0.to_view(canonical, size));
+        assert_eq!(
+            GridPosition::new(1, 0, 0),
+            GridOrientation::Deg90.to_view(canonical, size)
+        );
+        assert_eq!(
+            GridPosition::new(0, 0, 0),
+            GridOrientation::Deg180.to_view(canonical, size)
+        );
+        assert_eq!(
+            GridPosition::new(0, 3, 0),
+            GridOrientation::Deg270.to_view(canonical, size)
+        );
+    }
+
+    #[test]
+    fn test_orientation_canonical_round_trip() {
+        let size = GridSize::new(5, 3);
+
+        for turns in 0..4 {
+            let orientation = GridOrientation::from_quarter_turns(turns);
+
+            for x in 0..size.width {
+                for y in 0..size.height {
+                    let canonical = GridPosition::from_indices(x, y, 1);
+                    let view = orientation.to_view(canonical, size);
+                    let view_size = orientation.view_size(size);
+
+                    assert!(view.is_inside(view_size));
+                    assert_eq!(canonical, orientation.to_canonical(view, size));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rotate_outside_of_grid() {
+        let size = GridSize::new(4, 2);
+
+        for start in [
+            GridPosition::new(-3, 1, 0),
+            GridPosition::new(6, -2, 1),
+            GridPosition::new(i32::MIN, i32::MAX, 2),
+        ] {
+            assert_eq!(
+                start,
+                start.rotate_c_within(size).rotate_cc_within(size.rotated())
+            );
+            assert_eq!(
+                start,
+                start.rotate_cc_within(size).rotate_c_within(size.rotated())
+            );
+        }
+
+        assert_eq!(
+            GridPosition::new(0, -1, 0),
+            GridPosition::new(0, 0, 0).rotate_c(0)
+        );
+        assert_eq!(
+            GridPosition::new(1, 4, 0),
+            GridPosition::new(-1, 1, 0).rotate_c(4)
+        );
+    }
+
+    #[test]
+    fn test_position_conversions() {
+        assert_eq!(
+            Ok(GridPosition::new(-2, 3, 1)),
+            GridPosition::try_from(Vec3::new(-2.0, 3.0, 1.0))
+        );
+        assert_eq!(
+            Err(GridPositionError::Fractional),
+            GridPosition::try_from(Vec3::new(0.5, 0.0, 0.0))
+        );
+        assert_eq!(
+            Err(GridPositionError::NotFinite),
+            GridPosition::try_from(Vec3::new(0.0, f32::NAN, 0.0))
+        );
+        assert_eq!(
+            Err(GridPositionError::OutOfRange),
+            GridPosition::try_from(Vec3::new(0.0, 0.0, 1e10))
+        );
+
+        let position = GridPosition::new(-4, 7, 2);
+        assert_eq!(position, GridPosition::from(IVec3::from(position)));
+        assert_eq!(None, position.indices());
+        assert_eq!(Some((4, 7, 2)), GridPosition::new(4, 7, 2).indices());
+        assert_eq!(
+            GridPosition::new(i32::MAX, 1, 0),
+            GridPosition::from_indices(usize::MAX, 1, 0)
+        );
+    }
+
+    #[test]
+    fn test_fractional_position() {
+        let position = GridPositionF::new(-0.25, 2.5, 1.0);
+
+        assert_eq!(Ok(GridPosition::new(-1, 2, 1)), position.floor());
+        assert_eq!(Ok(GridPosition::new(0, 3, 1)), position.round());
+        assert_eq!(
+            GridPositionF::new(0.5, 2.5, 1.0),
+            GridPositionF::from(GridPosition::new(1, 2, 1))
+                .lerp(GridPositionF::new(0.0, 3.0, 1.0), 0.5)
+        );
+        assert_eq!(
+            Err(GridPositionError::NotFinite),
+            GridPositionF::new(f32::INFINITY, 0.0, 0.0).floor()
+        );
+
+        let size = GridSize::new(5, 3);
+        assert_eq!(
+            position,
+            position
+                .rotate_c_within(size)
+                .rotate_cc_within(size.rotated())
+        );
+    }
+}
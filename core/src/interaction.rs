@@ -0,0 +1,325 @@
+use bevy::prelude::*;
+
+use crate::{
+    animation::AnimatedTile,
+    picking::{TileHoverEnded, TileHovered},
+    tile::TileLift,
+};
+
+/// Tints the sprite of a tile while it is hovered.
+#[derive(Component, Debug, Copy, Clone, PartialEq)]
+pub struct HoverTint(pub Color);
+
+/// Shows another atlas index while a tile is hovered. Animated tiles keep showing their animation.
+#[derive(Component, Debug, Copy, Clone, PartialEq)]
+pub struct HoverSprite(pub usize);
+
+/// Lifts a tile by the given height while it is hovered.
+#[derive(Component, Debug, Copy, Clone, PartialEq)]
+pub struct HoverLift(pub f32);
+
+/// Marks a hovered tile and keeps the sprite state from before the hover effects were applied.
+/// The atlas index of animated tiles is left to their animation.
+#[derive(Component, Debug, Copy, Clone, PartialEq)]
+pub struct Hovered {
+    color: Color,
+    index: Option<usize>,
+}
+
+/// Settings for the built in tile interactions.
+#[derive(Resource, Debug, Copy, Clone)]
+pub struct TileInteractionSettings {
+    /// Speed of the hover lift in world units per second.
+    pub lift_speed: f32,
+}
+
+impl Default for TileInteractionSettings {
+    fn default() -> Self {
+        Self { lift_speed: 64.0 }
+    }
+}
+
+type HoverEffectQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut TextureAtlasSprite,
+        Option<&'static HoverTint>,
+        Option<&'static HoverSprite>,
+        Option<&'static Hovered>,
+        Option<&'static AnimatedTile>,
+    ),
+>;
+
+/// Applies the tint and sprite effects of hovered tiles and reverts them once the hover ended.
+pub fn apply_hover_effects(
+    mut commands: Commands,
+    mut hovered_events: EventReader<TileHovered>,
+    mut hover_ended_events: EventReader<TileHoverEnded>,
+    mut tiles: HoverEffectQuery,
+) {
+    for event in hover_ended_events.iter() {
+        let Ok((mut sprite, _, _, Some(hovered), _)) = tiles.get_mut(event.entity) else {
+            continue;
+        };
+
+        sprite.color = hovered.color;
+        if let Some(index) = hovered.index {
+            sprite.index = index;
+        }
+        commands.entity(event.entity).remove::<Hovered>();
+    }
+
+    for event in hovered_events.iter() {
+        let Ok((mut sprite, tint, hover_sprite, hovered, animation)) = tiles.get_mut(event.entity)
+        else {
+            continue;
+        };
+
+        if hovered.is_none() {
+            commands.entity(event.entity).insert(Hovered {
+                color: sprite.color,
+                index: animation.is_none().then_some(sprite.index),
+            });
+        }
+
+        if let Some(tint) = tint {
+            sprite.color = tint.0;
+        }
+
+        if let (Some(hover_sprite), None) = (hover_sprite, animation) {
+            sprite.index = hover_sprite.0;
+        }
+    }
+}
+
+/// Adds the lift state to tiles which can be lifted.
+pub fn prepare_hover_lift(
+    mut commands: Commands,
+    tiles: Query<Entity, (Added<HoverLift>, Without<TileLift>)>,
+) {
+    for entity in tiles.iter() {
+        commands.entity(entity).insert(TileLift::default());
+    }
+}
+
+/// Moves lifted tiles towards their hover height, or back down when they are no longer hovered.
+pub fn animate_hover_lift(
+    time: Res<Time>,
+    settings: Res<TileInteractionSettings>,
+    mut tiles: Query<(&HoverLift, &mut TileLift, &mut Transform, Option<&Hovered>)>,
+) {
+    let max_step = settings.lift_speed * time.delta_seconds();
+
+    for (hover_lift, mut lift, mut transform, hovered) in tiles.iter_mut() {
+        let target = match hovered {
+            Some(_) => hover_lift.0,
+            None => 0.0,
+        };
+
+        if lift.0 == target {
+            continue;
+        }
+
+        let new_lift = lift.0 + (target - lift.0).clamp(-max_step, max_step);
+        transform.translation.y += new_lift - lift.0;
+        lift.0 = new_lift;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{prelude::*, utils::Instant};
+
+    use crate::{
+        animation::{animate_tiles, AnimatedTile, TileAnimationSettings},
+        grid::GridPosition,
+        interaction::{
+            animate_hover_lift, apply_hover_effects, prepare_hover_lift, HoverLift, HoverSprite,
+            HoverTint, TileInteractionSettings,
+        },
+        ordering::{order_static_tile_z, DepthKey, DepthOrdering, ZOffset},
+        picking::{TileHoverEnded, TileHovered},
+        tile::TileLift,
+        StaticObject,
+    };
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_event::<TileHovered>()
+            .add_event::<TileHoverEnded>()
+            .init_resource::<Time>()
+            .init_resource::<TileInteractionSettings>()
+            .add_systems(
+                Update,
+                (
+                    apply_hover_effects,
+                    prepare_hover_lift,
+                    animate_hover_lift.after(apply_hover_effects),
+                ),
+            );
+        app
+    }
+
+    fn hover(app: &mut App, entity: Entity) {
+        app.world.send_event(TileHovered {
+            entity,
+            grid: Entity::PLACEHOLDER,
+            grid_position: GridPosition::default(),
+        });
+    }
+
+    fn end_hover(app: &mut App, entity: Entity) {
+        app.world.send_event(TileHoverEnded {
+            entity,
+            grid: Entity::PLACEHOLDER,
+            grid_position: GridPosition::default(),
+        });
+    }
+
+    #[test]
+    fn test_tint_and_sprite() {
+        let mut app = app();
+        let tile = app
+            .world
+            .spawn((
+                TextureAtlasSprite::new(3),
+                HoverTint(Color::RED),
+                HoverSprite(7),
+            ))
+            .id();
+
+        hover(&mut app, tile);
+        app.update();
+
+        let sprite = app.world.get::<TextureAtlasSprite>(tile).unwrap();
+        assert_eq!(Color::RED, sprite.color);
+        assert_eq!(7, sprite.index);
+
+        end_hover(&mut app, tile);
+        app.update();
+
+        let sprite = app.world.get::<TextureAtlasSprite>(tile).unwrap();
+        assert_eq!(Color::WHITE, sprite.color);
+        assert_eq!(3, sprite.index);
+    }
+
+    #[test]
+    fn test_animated_tile_keeps_animation() {
+        let mut app = app();
+        app.init_resource::<TileAnimationSettings>()
+            .add_systems(Update, animate_tiles.before(apply_hover_effects));
+
+        let tile = app
+            .world
+            .spawn((
+                TextureAtlasSprite::new(0),
+                AnimatedTile::new(vec![0, 1, 2], 1.0),
+                HoverTint(Color::RED),
+                HoverSprite(7),
+            ))
+            .id();
+        let index = |app: &App| app.world.get::<TextureAtlasSprite>(tile).unwrap().index;
+
+        let start = Instant::now();
+        app.world.resource_mut::<Time>().update_with_instant(start);
+        hover(&mut app, tile);
+        app.update();
+
+        assert_eq!(0, index(&app));
+        assert_eq!(
+            Color::RED,
+            app.world.get::<TextureAtlasSprite>(tile).unwrap().color
+        );
+
+        app.world
+            .resource_mut::<Time>()
+            .update_with_instant(start + Duration::from_secs_f32(1.0));
+        hover(&mut app, tile);
+        app.update();
+
+        assert_eq!(1, index(&app));
+
+        app.world
+            .resource_mut::<Time>()
+            .update_with_instant(start + Duration::from_secs_f32(2.0));
+        end_hover(&mut app, tile);
+        app.update();
+
+        assert_eq!(2, index(&app));
+        assert_eq!(
+            Some(2),
+            app.world.get::<AnimatedTile>(tile).unwrap().current_index()
+        );
+        assert_eq!(
+            Color::WHITE,
+            app.world.get::<TextureAtlasSprite>(tile).unwrap().color
+        );
+    }
+
+    #[test]
+    fn test_lift_keeps_ordering() {
+        let mut app = app();
+        app.init_resource::<DepthOrdering>()
+            .add_systems(Update, order_static_tile_z.after(animate_hover_lift));
+
+        let tile = app
+            .world
+            .spawn((
+                TextureAtlasSprite::new(0),
+                Transform::from_xyz(0.0, 50.0, 0.0),
+                GridPosition::new(2, 3, 1),
+                HoverLift(16.0),
+                ZOffset::default(),
+            ))
+            .id();
+
+        let start = Instant::now();
+        app.world.resource_mut::<Time>().update_with_instant(start);
+        hover(&mut app, tile);
+        app.update();
+
+        app.world
+            .resource_mut::<Time>()
+            .update_with_instant(start + Duration::from_secs_f32(0.125));
+        app.update();
+
+        assert_eq!(TileLift(8.0), *app.world.get::<TileLift>(tile).unwrap());
+        assert_eq!(
+            58.0,
+            app.world.get::<Transform>(tile).unwrap().translation.y
+        );
+
+        app.world
+            .resource_mut::<Time>()
+            .update_with_instant(start + Duration::from_secs_f32(1.0));
+        app.world.entity_mut(tile).insert(StaticObject);
+        app.update();
+
+        let translation = app.world.get::<Transform>(tile).unwrap().translation;
+        assert_eq!(66.0, translation.y);
+        assert_eq!(
+            DepthOrdering::default().z_order(DepthKey {
+                row: 5.0,
+                layer: 1.0
+            }),
+            translation.z
+        );
+
+        end_hover(&mut app, tile);
+        app.update();
+
+        app.world
+            .resource_mut::<Time>()
+            .update_with_instant(start + Duration::from_secs_f32(2.0));
+        app.update();
+
+        assert_eq!(TileLift(0.0), *app.world.get::<TileLift>(tile).unwrap());
+        assert_eq!(
+            50.0,
+            app.world.get::<Transform>(tile).unwrap().translation.y
+        );
+    }
+}
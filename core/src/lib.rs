@@ -10,6 +10,19 @@ pub mod loading;
 pub mod rotate;
 pub mod spawning;
 pub mod plugins;
+pub mod animation;
+pub mod transition;
+pub mod picking;
+pub mod interaction;
+pub mod movement;
+pub mod pathfinding;
+pub mod saving;
+pub mod reloading;
+pub mod chunking;
+pub mod batching;
+pub mod camera;
+pub mod projection;
+pub mod elevation;
 
 /// Multiplier for calculation in case the world should be bigger or smaller.
 #[derive(Component, Debug, Copy, Clone)]
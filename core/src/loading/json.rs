@@ -0,0 +1,280 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::tile::TileProperties;
+
+use super::{
+    tilemap::{LayerDefinition, TilemapDefinition, TilemapDefinitionBuilder, TilesetLink},
+    tileset::{
+        ImageDimensions, SourceDefinition, TileDefinition, TilePosition, TileSize,
+        TilesetDefinition,
+    },
+};
+
+/// Tilemap in the JSON format of `sampletilemap.itm.json`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TilemapFile {
+    tilemap_name: String,
+    tilesets: Vec<TilesetLinkFile>,
+    tile_size: SizeFile,
+    layers: Vec<LayerDefinition>,
+}
+
+/// Tileset in the JSON format of `sampletileset.its.json`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TilesetFile {
+    tileset_name: String,
+    image: ImageFile,
+    tile_size: SizeFile,
+    tiles: Vec<TileFile>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct TilesetLinkFile {
+    tileset_name: PathBuf,
+    alias: char,
+}
+
+/// Sizes are written as floats in the JSON files.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+struct SizeFile {
+    width: f32,
+    height: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct ImageFile {
+    path: PathBuf,
+    dimensions: SizeFile,
+}
+
+/// Tiles are told apart by their fields, animated tiles have `positions` and `speed` instead of `position`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+enum TileFile {
+    Standard {
+        tile_id: u32,
+        position: TilePosition,
+        #[serde(default, skip_serializing_if = "TileProperties::is_empty")]
+        properties: TileProperties,
+    },
+    Animated {
+        tile_id: u32,
+        positions: Vec<TilePosition>,
+        speed: f32,
+        #[serde(default, skip_serializing_if = "TileProperties::is_empty")]
+        properties: TileProperties,
+    },
+}
+
+/// Reads a tilemap definition from JSON.
+pub fn tilemap_from_json(bytes: &[u8]) -> Result<TilemapDefinition, serde_json::Error> {
+    serde_json::from_slice::<TilemapFile>(bytes).map(TilemapDefinition::from)
+}
+
+/// Writes a tilemap definition as JSON.
+pub fn tilemap_to_json(definition: &TilemapDefinition) -> Result<String, serde_json::Error> {
+    serde_json::to_string_pretty(&TilemapFile::from(definition))
+}
+
+/// Reads a tileset definition from JSON. Like tilesets read from RON, it has to be validated before it is used.
+pub fn tileset_from_json(bytes: &[u8]) -> Result<TilesetDefinition, serde_json::Error> {
+    serde_json::from_slice::<TilesetFile>(bytes).map(TilesetDefinition::from)
+}
+
+/// Writes a tileset definition as JSON.
+pub fn tileset_to_json(definition: &TilesetDefinition) -> Result<String, serde_json::Error> {
+    serde_json::to_string_pretty(&TilesetFile::from(definition))
+}
+
+impl From<SizeFile> for TileSize {
+    fn from(value: SizeFile) -> Self {
+        TileSize::new(value.width as usize, value.height as usize)
+    }
+}
+
+impl From<TileSize> for SizeFile {
+    fn from(value: TileSize) -> Self {
+        Self {
+            width: value.width() as f32,
+            height: value.height() as f32,
+        }
+    }
+}
+
+impl From<TilemapFile> for TilemapDefinition {
+    fn from(value: TilemapFile) -> Self {
+        let tile_size = TileSize::from(value.tile_size);
+        let mut builder = TilemapDefinitionBuilder::new(&value.tilemap_name)
+            .with_tile_size(tile_size.width(), tile_size.height());
+
+        for link in value.tilesets {
+            builder = builder.add_tileset(TilesetLink::new(&link.tileset_name, link.alias));
+        }
+
+        for layer in value.layers {
+            builder = builder.add_layer(layer);
+        }
+
+        builder.build()
+    }
+}
+
+impl From<&TilemapDefinition> for TilemapFile {
+    fn from(value: &TilemapDefinition) -> Self {
+        Self {
+            tilemap_name: value.name().to_owned(),
+            tilesets: value
+                .tilesets()
+                .iter()
+                .map(|link| TilesetLinkFile {
+                    tileset_name: link.path().to_owned(),
+                    alias: link.alias(),
+                })
+                .collect(),
+            tile_size: SizeFile::from(value.tile_size()),
+            layers: value.layers().to_vec(),
+        }
+    }
+}
+
+impl From<TilesetFile> for TilesetDefinition {
+    fn from(value: TilesetFile) -> Self {
+        let dimensions = TileSize::from(value.image.dimensions);
+
+        Self {
+            name: value.tileset_name,
+            tile_size: TileSize::from(value.tile_size),
+            source: SourceDefinition::new(
+                &value.image.path,
+                ImageDimensions::new(dimensions.width(), dimensions.height()),
+            ),
+            tiles: value
+                .tiles
+                .into_iter()
+                .map(|tile| match tile {
+                    TileFile::Standard {
+                        tile_id,
+                        position,
+                        properties,
+                    } => TileDefinition::new_standard(tile_id, position.x(), position.y())
+                        .with_properties(properties),
+                    TileFile::Animated {
+                        tile_id,
+                        positions,
+                        speed,
+                        properties,
+                    } => TileDefinition::Animated {
+                        id: tile_id,
+                        positions,
+                        interval_per_sec: speed,
+                        properties,
+                    },
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&TilesetDefinition> for TilesetFile {
+    fn from(value: &TilesetDefinition) -> Self {
+        let dimensions = value.source().dimensions();
+
+        Self {
+            tileset_name: value.name().to_owned(),
+            image: ImageFile {
+                path: value.source().path().to_owned(),
+                dimensions: SizeFile::from(TileSize::new(dimensions.width(), dimensions.height())),
+            },
+            tile_size: SizeFile::from(value.tile_size()),
+            tiles: value
+                .tiles()
+                .iter()
+                .map(|tile| match tile {
+                    TileDefinition::Standard {
+                        id,
+                        x,
+                        y,
+                        properties,
+                    } => TileFile::Standard {
+                        tile_id: *id,
+                        position: TilePosition::new(*x, *y),
+                        properties: properties.clone(),
+                    },
+                    TileDefinition::Animated {
+                        id,
+                        positions,
+                        interval_per_sec,
+                        properties,
+                    } => TileFile::Animated {
+                        tile_id: *id,
+                        positions: positions.clone(),
+                        speed: *interval_per_sec,
+                        properties: properties.clone(),
+                    },
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::loading::{
+        json::{tilemap_from_json, tilemap_to_json, tileset_from_json, tileset_to_json},
+        tilemap::TilemapDefinition,
+        tileset::{Error, TilesetDefinition},
+    };
+
+    const SAMPLE_TILEMAP_JSON: &str = include_str!("../../../sampletilemap.itm.json");
+    const SAMPLE_TILEMAP_RON: &str = include_str!("../../../sampletilemap.itm");
+    const SAMPLE_TILESET_JSON: &str = include_str!("../../../sampletileset.its.json");
+    const SAMPLE_TILESET_RON: &str = include_str!("../../../sampletileset.its");
+
+    #[test]
+    fn test_sample_tilemap_matches_ron() {
+        let from_json = tilemap_from_json(SAMPLE_TILEMAP_JSON.as_bytes()).unwrap();
+        let from_ron = ron::from_str::<TilemapDefinition>(SAMPLE_TILEMAP_RON).unwrap();
+
+        assert_eq!(from_ron, from_json);
+        assert_eq!(1, from_json.layers().len());
+        assert_eq!(Some('t'), from_json.tileset('t').map(|link| link.alias()));
+    }
+
+    #[test]
+    fn test_sample_tileset_matches_ron() {
+        let from_json = tileset_from_json(SAMPLE_TILESET_JSON.as_bytes()).unwrap();
+        let from_ron = ron::from_str::<TilesetDefinition>(SAMPLE_TILESET_RON).unwrap();
+
+        assert_eq!(from_ron, from_json);
+        assert!(from_json.tiles()[0].is_standard());
+        assert!(from_json.tiles()[1].is_animated());
+    }
+
+    #[test]
+    fn test_sample_tileset_validation() {
+        let definition = tileset_from_json(SAMPLE_TILESET_JSON.as_bytes()).unwrap();
+
+        assert!(matches!(
+            definition.validate(),
+            Err(Error::DublicatedTileIds(ids)) if ids == vec![(0, 2)]
+        ));
+    }
+
+    #[test]
+    fn test_tilemap_round_trip() {
+        let definition = tilemap_from_json(SAMPLE_TILEMAP_JSON.as_bytes()).unwrap();
+        let json = tilemap_to_json(&definition).unwrap();
+
+        assert_eq!(definition, tilemap_from_json(json.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_tileset_round_trip() {
+        let definition = tileset_from_json(SAMPLE_TILESET_JSON.as_bytes()).unwrap();
+        let json = tileset_to_json(&definition).unwrap();
+
+        assert_eq!(definition, tileset_from_json(json.as_bytes()).unwrap());
+    }
+}
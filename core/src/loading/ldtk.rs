@@ -0,0 +1,621 @@
+use std::{fmt, path::PathBuf};
+
+use bevy::reflect::{TypePath, TypeUuid};
+use serde::Deserialize;
+
+use crate::{
+    grid::GridPosition,
+    tile::{TileProperties, TileProperty},
+};
+
+use super::{
+    tiled::ALIASES,
+    tilemap::{
+        LayerDefinition, TileIdentifier, TilemapDefinition, TilemapDefinitionBuilder, TilesetLink,
+    },
+    tileset::{
+        self, ImageDimensions, SourceDefinition, TileDefinition, TilesetDefinition,
+        TilesetDefinitionBuilder,
+    },
+};
+
+/// An LDtk project converted into one tilemap definition per level.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LdtkImport {
+    pub levels: Vec<LdtkLevel>,
+    /// Tilesets of the project, together with the path of their `TilesetLink`.
+    pub tilesets: Vec<(PathBuf, TilesetDefinition)>,
+}
+
+/// A single level of an LDtk project. The tilemap is named after the level identifier.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LdtkLevel {
+    pub tilemap: TilemapDefinition,
+    pub spawn_points: Vec<SpawnPoint>,
+}
+
+/// Position and fields of an entity instance, for spawning an object at the start of a level.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpawnPoint {
+    pub identifier: String,
+    pub position: GridPosition,
+    pub fields: TileProperties,
+}
+
+/// The spawn points of a level, loaded as labeled asset `{level}/spawn_points` next to the tilemap of the level.
+#[derive(TypeUuid, TypePath, Default, Debug, Clone, PartialEq)]
+#[uuid = "5d0c7f1e-3a8b-4f63-9d26-7b1e0a4c8f52"]
+pub struct SpawnPoints(pub Vec<SpawnPoint>);
+
+#[derive(Debug)]
+pub enum Error {
+    Json(serde_json::Error),
+    /// Levels saved in separate `.ldtkl` files are not supported.
+    ExternalLevels,
+    /// All tile layers of a level need the same grid size.
+    MixedGridSizes {
+        level: String,
+    },
+    UnknownTileset(i64),
+    /// Embedded atlases and tilesets with padding or spacing can not be turned into a texture atlas.
+    UnsupportedTileset(String),
+    TooManyTilesets(usize),
+    Tileset(tileset::Error),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LdtkProject {
+    default_grid_size: usize,
+    #[serde(default)]
+    external_levels: bool,
+    defs: LdtkDefinitions,
+    levels: Vec<LdtkLevelJson>,
+}
+
+#[derive(Deserialize)]
+struct LdtkDefinitions {
+    layers: Vec<LdtkLayerDefinition>,
+    tilesets: Vec<LdtkTileset>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LdtkLayerDefinition {
+    uid: i64,
+    #[serde(default)]
+    int_grid_values: Vec<LdtkIntGridValue>,
+}
+
+#[derive(Deserialize)]
+struct LdtkIntGridValue {
+    value: i64,
+    #[serde(default)]
+    tile: Option<LdtkTileRect>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LdtkTileRect {
+    tileset_uid: i64,
+    x: usize,
+    y: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LdtkTileset {
+    uid: i64,
+    identifier: String,
+    rel_path: Option<PathBuf>,
+    px_wid: usize,
+    px_hei: usize,
+    tile_grid_size: usize,
+    #[serde(default)]
+    spacing: usize,
+    #[serde(default)]
+    padding: usize,
+    #[serde(default)]
+    enum_tags: Vec<LdtkEnumTag>,
+    #[serde(default)]
+    custom_data: Vec<LdtkCustomData>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LdtkEnumTag {
+    enum_value_id: String,
+    tile_ids: Vec<u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LdtkCustomData {
+    tile_id: u32,
+    data: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LdtkLevelJson {
+    identifier: String,
+    px_wid: usize,
+    px_hei: usize,
+    layer_instances: Option<Vec<LdtkLayerInstance>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LdtkLayerInstance {
+    #[serde(rename = "__type")]
+    kind: String,
+    #[serde(rename = "__cWid")]
+    width: usize,
+    #[serde(rename = "__cHei")]
+    height: usize,
+    #[serde(rename = "__gridSize")]
+    grid_size: usize,
+    #[serde(rename = "__tilesetDefUid")]
+    tileset_uid: Option<i64>,
+    layer_def_uid: i64,
+    #[serde(default)]
+    int_grid_csv: Vec<i64>,
+    #[serde(default)]
+    grid_tiles: Vec<LdtkTile>,
+    #[serde(default)]
+    auto_layer_tiles: Vec<LdtkTile>,
+    #[serde(default)]
+    entity_instances: Vec<LdtkEntity>,
+}
+
+#[derive(Deserialize)]
+struct LdtkTile {
+    px: [i64; 2],
+    t: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LdtkEntity {
+    #[serde(rename = "__identifier")]
+    identifier: String,
+    #[serde(rename = "__grid")]
+    grid: [i64; 2],
+    #[serde(default)]
+    field_instances: Vec<LdtkField>,
+}
+
+#[derive(Deserialize)]
+struct LdtkField {
+    #[serde(rename = "__identifier")]
+    identifier: String,
+    #[serde(rename = "__value")]
+    value: serde_json::Value,
+}
+
+/// Imports every level of an LDtk project.
+///
+/// Tiles and IntGrid layers become layers of the tilemap, ordered from the bottom most layer of the level.
+/// IntGrid layers use their auto-layer tiles, without a tileset every value is replaced by the tile
+/// it is shown with in the editor. Values without a tile are left empty.
+/// LDtk draws the first row at the top while this crate draws it at the bottom, so tiles are mirrored
+/// along the diagonal of the level like maps imported from Tiled.
+///
+/// Entity instances become spawn points on the tile layer below their entity layer, with their fields as properties.
+/// Tilesets get the aliases `a` to `z` and `A` to `Z` in the order of the project and are linked as
+/// `{file_name}#tilesets/{identifier}`, which the asset loader provides as labeled assets.
+pub fn import_ldtk_project(bytes: &[u8], file_name: &str) -> Result<LdtkImport, Error> {
+    let project = serde_json::from_slice::<LdtkProject>(bytes).map_err(Error::Json)?;
+
+    if project.external_levels {
+        return Err(Error::ExternalLevels);
+    }
+
+    if project.defs.tilesets.len() > ALIASES.len() {
+        return Err(Error::TooManyTilesets(project.defs.tilesets.len()));
+    }
+
+    let tilesets = project
+        .defs
+        .tilesets
+        .iter()
+        .zip(ALIASES.chars())
+        .map(|(tileset, alias)| {
+            let path = PathBuf::from(format!("{}#tilesets/{}", file_name, tileset.identifier));
+            (tileset, alias, path, convert_tileset(tileset))
+        })
+        .collect::<Vec<_>>();
+    let mut levels = Vec::new();
+
+    for level in project.levels.iter() {
+        let layer_instances = level.layer_instances.as_deref().unwrap_or_default();
+        let grid_size = match layer_instances
+            .iter()
+            .filter(|layer| layer.kind != "Entities")
+            .map(|layer| layer.grid_size)
+            .reduce(|a, b| if a == b { a } else { 0 })
+        {
+            Some(0) => {
+                return Err(Error::MixedGridSizes {
+                    level: level.identifier.clone(),
+                })
+            }
+            Some(grid_size) => grid_size,
+            None => project.default_grid_size,
+        };
+        let width = level.px_wid.div_ceil(grid_size);
+        let height = level.px_hei.div_ceil(grid_size);
+        let mut builder =
+            TilemapDefinitionBuilder::new(&level.identifier).with_tile_size(grid_size, grid_size);
+        let mut used_tilesets = Vec::new();
+        let mut spawn_points = Vec::new();
+        let mut layer_count = 0u32;
+
+        // Layer instances are listed from the top most layer.
+        for layer in layer_instances.iter().rev() {
+            if layer.kind == "Entities" {
+                let layer_id = layer_count.saturating_sub(1) as usize;
+
+                for entity in layer.entity_instances.iter() {
+                    let x = entity.grid[0] * layer.grid_size as i64 / grid_size as i64;
+                    let y = entity.grid[1] * layer.grid_size as i64 / grid_size as i64;
+
+                    let Some(position) = mirror(x, y, width, height) else {
+                        continue;
+                    };
+
+                    spawn_points.push(SpawnPoint {
+                        identifier: entity.identifier.clone(),
+                        position: GridPosition::from_indices(position.0, position.1, layer_id),
+                        fields: entity.field_instances.iter().fold(
+                            TileProperties::new(),
+                            |fields, field| match json_property(&field.value) {
+                                Some(value) => fields.with(&field.identifier, value),
+                                None => fields,
+                            },
+                        ),
+                    });
+                }
+
+                continue;
+            }
+
+            let mut rows = vec![vec![TileIdentifier::empty(); layer.height]; layer.width];
+            let mut place = |x: i64, y: i64, tileset_uid: i64, id: u32| -> Result<(), Error> {
+                let (tileset, alias, _, definition) = tilesets
+                    .iter()
+                    .find(|(tileset, ..)| tileset.uid == tileset_uid)
+                    .ok_or(Error::UnknownTileset(tileset_uid))?;
+
+                if definition.is_err() {
+                    return Err(Error::UnsupportedTileset(tileset.identifier.clone()));
+                }
+
+                if let Some((x, y)) = mirror(x, y, layer.width, layer.height) {
+                    rows[y][x] = TileIdentifier::new(id, *alias);
+
+                    if !used_tilesets.contains(&tileset_uid) {
+                        used_tilesets.push(tileset_uid);
+                    }
+                }
+
+                Ok(())
+            };
+
+            let tiles = match layer.kind.as_str() {
+                "Tiles" => &layer.grid_tiles,
+                _ => &layer.auto_layer_tiles,
+            };
+
+            if let (Some(tileset_uid), false) = (layer.tileset_uid, tiles.is_empty()) {
+                for tile in tiles {
+                    let x = tile.px[0] / layer.grid_size as i64;
+                    let y = tile.px[1] / layer.grid_size as i64;
+
+                    place(x, y, tileset_uid, tile.t)?;
+                }
+            } else if layer.kind == "IntGrid" {
+                let values = project
+                    .defs
+                    .layers
+                    .iter()
+                    .find(|definition| definition.uid == layer.layer_def_uid)
+                    .map(|definition| definition.int_grid_values.as_slice())
+                    .unwrap_or_default();
+
+                for (i, value) in layer.int_grid_csv.iter().enumerate() {
+                    let Some(rect) = values
+                        .iter()
+                        .find(|definition| definition.value == *value)
+                        .and_then(|definition| definition.tile.as_ref())
+                    else {
+                        continue;
+                    };
+                    let (tileset, ..) = tilesets
+                        .iter()
+                        .find(|(tileset, ..)| tileset.uid == rect.tileset_uid)
+                        .ok_or(Error::UnknownTileset(rect.tileset_uid))?;
+                    let columns = tileset.px_wid / tileset.tile_grid_size.max(1);
+                    let id = rect.y / tileset.tile_grid_size.max(1) * columns
+                        + rect.x / tileset.tile_grid_size.max(1);
+                    let (x, y) = (i % layer.width, i / layer.width);
+
+                    place(x as i64, y as i64, rect.tileset_uid, id as u32)?;
+                }
+            }
+
+            builder = builder.add_layer(LayerDefinition::new(layer_count).with_tiles(rows));
+            layer_count += 1;
+        }
+
+        for (tileset, alias, path, _) in tilesets.iter() {
+            if used_tilesets.contains(&tileset.uid) {
+                builder = builder.add_tileset(TilesetLink::new(path, *alias));
+            }
+        }
+
+        levels.push(LdtkLevel {
+            tilemap: builder.build(),
+            spawn_points,
+        });
+    }
+
+    Ok(LdtkImport {
+        levels,
+        tilesets: tilesets
+            .into_iter()
+            .filter_map(|(_, _, path, definition)| Some((path, definition.ok()?)))
+            .collect(),
+    })
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Json(error) => write!(f, "invalid json: {}", error),
+            Error::ExternalLevels => write!(f, "external levels are not supported"),
+            Error::MixedGridSizes { level } => {
+                write!(f, "layers of level '{}' have different grid sizes", level)
+            }
+            Error::UnknownTileset(uid) => write!(f, "no tileset with uid {}", uid),
+            Error::UnsupportedTileset(name) => write!(f, "unsupported tileset '{}'", name),
+            Error::TooManyTilesets(count) => write!(f, "too many tilesets ({})", count),
+            Error::Tileset(error) => write!(f, "invalid tileset: {:?}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Mirrors a cell of a level with the given size along its diagonal, cells outside of the level are dropped.
+fn mirror(x: i64, y: i64, width: usize, height: usize) -> Option<(usize, usize)> {
+    let (x, y) = (usize::try_from(x).ok()?, usize::try_from(y).ok()?);
+
+    if x >= width || y >= height {
+        return None;
+    }
+
+    Some((height - 1 - y, width - 1 - x))
+}
+
+/// Converts a tileset of the project, tiles get their enum tags as flags and the fields of
+/// their custom data if it is a JSON object.
+fn convert_tileset(tileset: &LdtkTileset) -> Result<TilesetDefinition, Error> {
+    let Some(path) = tileset.rel_path.as_ref() else {
+        return Err(Error::UnsupportedTileset(tileset.identifier.clone()));
+    };
+
+    if tileset.padding != 0 || tileset.spacing != 0 || tileset.tile_grid_size == 0 {
+        return Err(Error::UnsupportedTileset(tileset.identifier.clone()));
+    }
+
+    let columns = tileset.px_wid / tileset.tile_grid_size;
+    let rows = tileset.px_hei / tileset.tile_grid_size;
+    let mut builder = TilesetDefinitionBuilder::new(SourceDefinition::new(
+        path,
+        ImageDimensions::new(tileset.px_wid, tileset.px_hei),
+    ))
+    .with_name(&tileset.identifier)
+    .with_tile_size(tileset.tile_grid_size, tileset.tile_grid_size);
+
+    for id in 0..(columns * rows) as u32 {
+        let mut tile =
+            TileDefinition::new_standard(id, id as usize % columns, id as usize / columns);
+
+        for tag in tileset.enum_tags.iter() {
+            if tag.tile_ids.contains(&id) {
+                tile = tile.with_property(&tag.enum_value_id, true);
+            }
+        }
+
+        for custom in tileset.custom_data.iter().filter(|data| data.tile_id == id) {
+            match serde_json::from_str::<serde_json::Value>(&custom.data) {
+                Ok(serde_json::Value::Object(fields)) => {
+                    for (key, value) in fields.iter() {
+                        if let Some(value) = json_property(value) {
+                            tile = tile.with_property(key, value);
+                        }
+                    }
+                }
+                _ => tile = tile.with_property("data", custom.data.as_str()),
+            }
+        }
+
+        builder = builder.add_tile(tile);
+    }
+
+    builder.build().map_err(Error::Tileset)
+}
+
+/// Converts a field value, values which are not set are skipped and lists or points are kept as JSON text.
+fn json_property(value: &serde_json::Value) -> Option<TileProperty> {
+    Some(match value {
+        serde_json::Value::Null => return None,
+        serde_json::Value::Bool(value) => TileProperty::Bool(*value),
+        serde_json::Value::Number(number) => match number.as_i64() {
+            Some(value) => TileProperty::Int(value),
+            None => TileProperty::Float(number.as_f64()?),
+        },
+        serde_json::Value::String(value) => TileProperty::String(value.clone()),
+        value => TileProperty::String(value.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{
+        grid::GridPosition,
+        loading::{
+            ldtk::{import_ldtk_project, Error},
+            tilemap::{TileIdentifier, TileReference},
+            tileset::TileDefinition,
+        },
+    };
+
+    const PROJECT: &str = include_str!("../../fixtures/ldtk/world.ldtk");
+
+    #[test]
+    fn test_import_levels() {
+        let import = import_ldtk_project(PROJECT.as_bytes(), "world.ldtk").unwrap();
+
+        assert_eq!(2, import.levels.len());
+        assert_eq!("Level_0", import.levels[0].tilemap.name());
+        assert_eq!("Level_1", import.levels[1].tilemap.name());
+        assert_eq!(32, import.levels[0].tilemap.tile_size().width());
+        assert_eq!(32, import.levels[0].tilemap.tile_size().height());
+    }
+
+    #[test]
+    fn test_import_tile_layers() {
+        let import = import_ldtk_project(PROJECT.as_bytes(), "world.ldtk").unwrap();
+        let tilemap = &import.levels[0].tilemap;
+
+        assert_eq!(1, tilemap.tilesets().len());
+        assert_eq!(
+            Path::new("world.ldtk#tilesets/Terrain"),
+            tilemap.tileset('a').unwrap().path()
+        );
+
+        // Ground, Collision and Decoration, from the bottom most layer.
+        assert_eq!(3, tilemap.layers().len());
+
+        // The level is 3x2 cells, so the top left cell is the last cell of the imported grid.
+        let ground = tilemap.layers()[0].tiles();
+        assert_eq!(3, ground.len());
+        assert_eq!(2, ground[0].len());
+        assert_eq!(TileIdentifier::new(0, 'a'), ground[2][1]);
+        assert_eq!(TileIdentifier::new(1, 'a'), ground[1][1]);
+        assert_eq!(TileIdentifier::new(2, 'a'), ground[2][0]);
+        assert_eq!(TileIdentifier::new(1, 'a'), ground[0][0]);
+
+        let decoration = tilemap.layers()[2].tiles();
+        assert_eq!(
+            Some(TileReference::Tile { alias: 'a', id: 3 }),
+            decoration[0][0].parse()
+        );
+        assert!(decoration[1][0].is_empty());
+    }
+
+    #[test]
+    fn test_import_int_grid() {
+        let import = import_ldtk_project(PROJECT.as_bytes(), "world.ldtk").unwrap();
+        let collision = import.levels[0].tilemap.layers()[1].tiles();
+
+        // Walls are shown with tile 3, holes have no tile.
+        assert_eq!(TileIdentifier::new(3, 'a'), collision[2][1]);
+        assert!(collision[1][0].is_empty());
+        assert_eq!(
+            5,
+            collision
+                .iter()
+                .flatten()
+                .filter(|identifier| identifier.is_empty())
+                .count()
+        );
+    }
+
+    #[test]
+    fn test_import_spawn_points() {
+        let import = import_ldtk_project(PROJECT.as_bytes(), "world.ldtk").unwrap();
+        let spawn_points = &import.levels[0].spawn_points;
+
+        assert_eq!(2, spawn_points.len());
+        assert_eq!("Player", spawn_points[0].identifier);
+        assert_eq!(GridPosition::new(0, 1, 2), spawn_points[0].position);
+        assert_eq!(Some(10), spawn_points[0].fields.get_int("health"));
+        assert_eq!(Some("Hero"), spawn_points[0].fields.get_str("name"));
+        assert_eq!(None, spawn_points[0].fields.get("target"));
+
+        assert_eq!("Chest", spawn_points[1].identifier);
+        assert_eq!(GridPosition::new(1, 0, 2), spawn_points[1].position);
+        assert_eq!(Some(0.5), spawn_points[1].fields.get_float("loot"));
+        assert!(import.levels[1].spawn_points.is_empty());
+    }
+
+    #[test]
+    fn test_import_tilesets() {
+        let import = import_ldtk_project(PROJECT.as_bytes(), "world.ldtk").unwrap();
+
+        // The icon tileset is embedded into the editor and unused, so it is skipped.
+        assert_eq!(1, import.tilesets.len());
+
+        let (path, terrain) = &import.tilesets[0];
+        assert_eq!(Path::new("world.ldtk#tilesets/Terrain"), path);
+        assert_eq!("Terrain", terrain.name());
+        assert_eq!(Path::new("terrain.png"), terrain.source().path());
+        assert_eq!(4, terrain.tiles().len());
+        assert_eq!(
+            Some(&TileDefinition::new_standard(3, 1, 1)),
+            terrain.tile(3)
+        );
+        assert_eq!(
+            Some(true),
+            terrain.tile(1).unwrap().properties().get_bool("Water")
+        );
+        assert_eq!(
+            Some(3),
+            terrain.tile(2).unwrap().properties().get_int("cost")
+        );
+    }
+
+    #[test]
+    fn test_imported_levels_validate() {
+        let import = import_ldtk_project(PROJECT.as_bytes(), "world.ldtk").unwrap();
+
+        for level in import.levels.iter() {
+            assert!(level
+                .tilemap
+                .validate(|link| import
+                    .tilesets
+                    .iter()
+                    .find(|(path, _)| path == link.path())
+                    .map(|(_, tileset)| tileset))
+                .is_ok());
+        }
+    }
+
+    #[test]
+    fn test_mixed_grid_sizes() {
+        let project = PROJECT.replace(
+            "\"__identifier\": \"Clouds\", \"__type\": \"Tiles\", \"__gridSize\": 32",
+            "\"__identifier\": \"Clouds\", \"__type\": \"Tiles\", \"__gridSize\": 16",
+        );
+
+        assert!(matches!(
+            import_ldtk_project(project.as_bytes(), "world.ldtk"),
+            Err(Error::MixedGridSizes { level }) if level == "Level_1"
+        ));
+    }
+
+    #[test]
+    fn test_external_levels() {
+        let project = PROJECT.replace("\"externalLevels\": false", "\"externalLevels\": true");
+
+        assert!(matches!(
+            import_ldtk_project(project.as_bytes(), "world.ldtk"),
+            Err(Error::ExternalLevels)
+        ));
+    }
+}
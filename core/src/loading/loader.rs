@@ -1,20 +1,53 @@
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
 
-use bevy::asset::{AssetLoader, LoadedAsset};
+use bevy::asset::{AssetLoader, AssetPath, LoadContext, LoadedAsset};
 
-use super::{Error, tilemap::TilemapDefinition};
+use super::{
+    json::{tilemap_from_json, tileset_from_json},
+    ldtk::{import_ldtk_project, SpawnPoints},
+    tiled::{import_tiled_map, import_tiled_tileset, TiledFormat},
+    tilemap::TilemapDefinition,
+    tileset::TilesetDefinition,
+    Error,
+};
+
+/// Label of the texture atlas asset which is created while loading a tileset.
+pub const TILESET_ATLAS_LABEL: &str = "atlas";
 
 pub trait Loader {
     fn load(&mut self, path: &Path) -> Result<(), Error>;
     fn unload(&mut self, name: &str);
 }
 
-
 pub struct TilemapDefinitionCollection();
 
 #[derive(Default)]
 pub struct TilemapAssetLoader;
 
+#[derive(Default)]
+pub struct TilesetAssetLoader;
+
+/// Loads tilemaps in the JSON format of `sampletilemap.itm.json`.
+#[derive(Default)]
+pub struct JsonTilemapAssetLoader;
+
+/// Loads tilesets in the JSON format of `sampletileset.its.json`.
+#[derive(Default)]
+pub struct JsonTilesetAssetLoader;
+
+/// Imports isometric Tiled maps. Embedded tilesets are added as labeled assets named after the tileset.
+#[derive(Default)]
+pub struct TiledMapAssetLoader;
+
+/// Imports external Tiled tilesets.
+#[derive(Default)]
+pub struct TiledTilesetAssetLoader;
+
+/// Imports LDtk projects. Every level is a labeled tilemap named after the level identifier, with its
+/// spawn points labeled as `{level}/spawn_points`. The first level is the default asset.
+#[derive(Default)]
+pub struct LdtkProjectAssetLoader;
+
 impl AssetLoader for TilemapAssetLoader {
     fn load<'a>(
         &'a self,
@@ -23,7 +56,7 @@ impl AssetLoader for TilemapAssetLoader {
     ) -> bevy::utils::BoxedFuture<'a, Result<(), anyhow::Error>> {
         Box::pin(async move {
             let custom_asset = ron::de::from_bytes::<TilemapDefinition>(bytes)?;
-            load_context.set_default_asset(LoadedAsset::new(custom_asset));
+            set_tilemap_asset(custom_asset, load_context);
             Ok(())
         })
     }
@@ -31,4 +64,366 @@ impl AssetLoader for TilemapAssetLoader {
     fn extensions(&self) -> &[&str] {
         &["itm"]
     }
-}
\ No newline at end of file
+}
+
+impl AssetLoader for TilesetAssetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> bevy::utils::BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let custom_asset = ron::de::from_bytes::<TilesetDefinition>(bytes)?;
+            custom_asset.validate()?;
+            set_tileset_asset(custom_asset, load_context);
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["its"]
+    }
+}
+
+impl AssetLoader for JsonTilemapAssetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> bevy::utils::BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let custom_asset = tilemap_from_json(bytes)?;
+            set_tilemap_asset(custom_asset, load_context);
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["itm.json"]
+    }
+}
+
+impl AssetLoader for JsonTilesetAssetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> bevy::utils::BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let custom_asset = tileset_from_json(bytes)?;
+            custom_asset.validate()?;
+            set_tileset_asset(custom_asset, load_context);
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["its.json"]
+    }
+}
+
+impl AssetLoader for TiledMapAssetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> bevy::utils::BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let format = TiledFormat::from_path(load_context.path()).unwrap_or(TiledFormat::Xml);
+            let file_name = load_context
+                .path()
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let import = import_tiled_map(bytes, format, &file_name)?;
+
+            for (path, tileset) in import.tilesets {
+                let (tileset_path, _) = tileset_asset_paths(load_context.path(), &path);
+                add_tileset_assets(tileset, tileset_path.label(), load_context);
+            }
+
+            set_tilemap_asset(import.tilemap, load_context);
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tmx", "tmj"]
+    }
+}
+
+impl AssetLoader for TiledTilesetAssetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> bevy::utils::BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let format = TiledFormat::from_path(load_context.path()).unwrap_or(TiledFormat::Xml);
+            set_tileset_asset(import_tiled_tileset(bytes, format)?, load_context);
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tsx", "tsj"]
+    }
+}
+
+impl AssetLoader for LdtkProjectAssetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> bevy::utils::BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let file_name = load_context
+                .path()
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let import = import_ldtk_project(bytes, &file_name)?;
+
+            for (path, tileset) in import.tilesets {
+                let (tileset_path, _) = tileset_asset_paths(load_context.path(), &path);
+                add_tileset_assets(tileset, tileset_path.label(), load_context);
+            }
+
+            if let Some(level) = import.levels.first() {
+                set_tilemap_asset(level.tilemap.clone(), load_context);
+            }
+
+            for level in import.levels {
+                let name = level.tilemap.name().to_owned();
+
+                load_context.set_labeled_asset(
+                    &format!("{}/spawn_points", name),
+                    LoadedAsset::new(SpawnPoints(level.spawn_points)),
+                );
+                add_tilemap_asset(level.tilemap, Some(&name), load_context);
+            }
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ldtk"]
+    }
+}
+
+/// Sets the tilemap as default asset, depending on all of its tilesets in other files.
+fn set_tilemap_asset(definition: TilemapDefinition, load_context: &mut LoadContext) {
+    add_tilemap_asset(definition, None, load_context);
+}
+
+/// Adds the tilemap to the loaded assets, as default asset without a label.
+fn add_tilemap_asset(
+    definition: TilemapDefinition,
+    label: Option<&str>,
+    load_context: &mut LoadContext,
+) {
+    let dependencies = definition
+        .tilesets()
+        .iter()
+        .map(|link| tileset_asset_paths(load_context.path(), link.path()).0)
+        .filter(|path| path.path() != load_context.path())
+        .map(|path| AssetPath::new(path.path().to_owned(), None))
+        .collect();
+    let tilemap = LoadedAsset::new(definition).with_dependencies(dependencies);
+
+    match label {
+        Some(label) => {
+            load_context.set_labeled_asset(label, tilemap);
+        }
+        None => load_context.set_default_asset(tilemap),
+    }
+}
+
+/// Sets the tileset as default asset and its texture atlas as labeled asset, both depending on the source image.
+fn set_tileset_asset(definition: TilesetDefinition, load_context: &mut LoadContext) {
+    add_tileset_assets(definition, None, load_context);
+}
+
+/// Adds the tileset and its texture atlas to the loaded assets.
+/// Without a label the tileset is the default asset, otherwise both are labeled assets.
+fn add_tileset_assets(
+    definition: TilesetDefinition,
+    label: Option<&str>,
+    load_context: &mut LoadContext,
+) {
+    let image_path = AssetPath::new(
+        resolve_path(load_context.path(), definition.source().path()),
+        None,
+    );
+
+    let texture_atlas = definition.texture_atlas(load_context.get_handle(image_path.clone()));
+    load_context.set_labeled_asset(
+        &atlas_label(label),
+        LoadedAsset::new(texture_atlas).with_dependency(image_path.clone()),
+    );
+
+    let tileset = LoadedAsset::new(definition).with_dependency(image_path);
+    match label {
+        Some(label) => {
+            load_context.set_labeled_asset(label, tileset);
+        }
+        None => load_context.set_default_asset(tileset),
+    }
+}
+
+/// Label of the texture atlas of a tileset, which itself may be a labeled asset.
+fn atlas_label(tileset_label: Option<&str>) -> String {
+    match tileset_label {
+        Some(label) => format!("{}/{}", label, TILESET_ATLAS_LABEL),
+        None => TILESET_ATLAS_LABEL.to_owned(),
+    }
+}
+
+/// Gets the asset paths of a linked tileset and its texture atlas, relative to the tilemap file.
+/// Links like `level.tmx#name` point to a tileset which is a labeled asset of another file.
+pub fn tileset_asset_paths(
+    tilemap_path: &Path,
+    link: &Path,
+) -> (AssetPath<'static>, AssetPath<'static>) {
+    let link = link.to_string_lossy();
+    let (file, label) = match link.split_once('#') {
+        Some((file, label)) => (file, Some(label)),
+        None => (link.as_ref(), None),
+    };
+    let path = resolve_path(tilemap_path, Path::new(file));
+
+    (
+        AssetPath::new(path.clone(), label.map(str::to_owned)),
+        AssetPath::new(path, Some(atlas_label(label))),
+    )
+}
+
+/// Resolves a path relative to the directory of the file it is referenced from.
+pub fn resolve_path(file_path: &Path, relative_path: &Path) -> PathBuf {
+    let joined = match file_path.parent() {
+        Some(parent) => parent.join(relative_path),
+        None => relative_path.to_owned(),
+    };
+
+    let mut resolved = PathBuf::new();
+
+    for component in joined.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    resolved.push(component);
+                }
+            }
+            _ => resolved.push(component),
+        }
+    }
+
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::{Path, PathBuf},
+        thread,
+        time::Duration,
+    };
+
+    use bevy::{
+        asset::{AssetPath, LoadState},
+        prelude::*,
+    };
+
+    use crate::loading::{
+        loader::{
+            resolve_path, tileset_asset_paths, JsonTilemapAssetLoader, JsonTilesetAssetLoader,
+        },
+        tilemap::TilemapDefinition,
+        tileset::TilesetDefinition,
+    };
+
+    #[test]
+    fn test_resolve_path() {
+        let resolved = resolve_path(Path::new("maps/level.itm"), Path::new("./tiles.its"));
+
+        assert_eq!(PathBuf::from("maps/tiles.its"), resolved);
+    }
+
+    #[test]
+    fn test_resolve_parent_path() {
+        let resolved = resolve_path(
+            Path::new("maps/level.itm"),
+            Path::new("../tilesets/tiles.its"),
+        );
+
+        assert_eq!(PathBuf::from("tilesets/tiles.its"), resolved);
+    }
+
+    #[test]
+    fn test_tileset_asset_paths() {
+        assert_eq!(
+            (
+                AssetPath::new(PathBuf::from("maps/tiles.its"), None),
+                AssetPath::new(PathBuf::from("maps/tiles.its"), Some("atlas".to_owned())),
+            ),
+            tileset_asset_paths(Path::new("maps/level.itm"), Path::new("tiles.its"))
+        );
+    }
+
+    #[test]
+    fn test_embedded_tileset_asset_paths() {
+        assert_eq!(
+            (
+                AssetPath::new(PathBuf::from("maps/level.tmx"), Some("water".to_owned())),
+                AssetPath::new(
+                    PathBuf::from("maps/level.tmx"),
+                    Some("water/atlas".to_owned())
+                ),
+            ),
+            tileset_asset_paths(Path::new("maps/level.tmx"), Path::new("level.tmx#water"))
+        );
+    }
+
+    #[test]
+    fn test_load_json_samples() {
+        let mut app = App::new();
+        app.add_plugins((
+            TaskPoolPlugin::default(),
+            AssetPlugin {
+                asset_folder: "..".to_owned(),
+                ..default()
+            },
+        ))
+        .add_asset::<Image>()
+        .add_asset::<TextureAtlas>()
+        .add_asset::<TilemapDefinition>()
+        .add_asset::<TilesetDefinition>()
+        .init_asset_loader::<JsonTilemapAssetLoader>()
+        .init_asset_loader::<JsonTilesetAssetLoader>();
+
+        let asset_server = app.world.resource::<AssetServer>().clone();
+        let tilemap: Handle<TilemapDefinition> = asset_server.load("sampletilemap.itm.json");
+        let tileset: Handle<TilesetDefinition> = asset_server.load("sampletileset.its.json");
+        let done = |state| matches!(state, LoadState::Loaded | LoadState::Failed);
+
+        for _ in 0..200 {
+            app.update();
+
+            if done(asset_server.get_load_state(&tilemap))
+                && done(asset_server.get_load_state(&tileset))
+            {
+                break;
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let tilemaps = app.world.resource::<Assets<TilemapDefinition>>();
+        assert_eq!(Some("Name"), tilemaps.get(&tilemap).map(|t| t.name()));
+
+        // The sample tileset defines the tile id 0 twice, which the loader reports instead of loading it.
+        assert_eq!(LoadState::Failed, asset_server.get_load_state(&tileset));
+    }
+}
@@ -1,6 +1,9 @@
 pub mod tileset;
 pub mod tilemap;
 pub mod loader;
+pub mod json;
+pub mod tiled;
+pub mod ldtk;
 
 #[derive(Debug)]
 pub enum Error {
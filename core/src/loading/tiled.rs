@@ -0,0 +1,945 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::Deserialize;
+use xml::reader::{EventReader, XmlEvent};
+
+use crate::tile::{TileProperties, TileProperty};
+
+use super::tilemap::{
+    LayerDefinition, TileIdentifier, TilemapDefinition, TilemapDefinitionBuilder, TilesetLink,
+};
+use super::tileset::{
+    self, AnimatedTileDefBuilder, ImageDimensions, SourceDefinition, TileDefinition, TilePosition,
+    TilesetDefinition, TilesetDefinitionBuilder,
+};
+
+/// Aliases given to the tilesets of an imported map, in the order of the tilesets.
+pub(super) const ALIASES: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+/// Bits of a global tile id which are used for flipping and rotating the tile.
+const GID_FLAGS: u32 = 0xF000_0000;
+
+/// The two file formats of Tiled.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TiledFormat {
+    /// `.tmx` maps and `.tsx` tilesets.
+    Xml,
+    /// `.tmj` maps and `.tsj` tilesets.
+    Json,
+}
+
+/// A Tiled map converted into a tilemap definition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TiledImport {
+    pub tilemap: TilemapDefinition,
+    /// Tilesets embedded into the map, together with the path of their `TilesetLink`.
+    /// External tilesets are linked by their own path and have to be imported separately.
+    pub tilesets: Vec<(PathBuf, TilesetDefinition)>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    IO(std::io::Error),
+    Xml(String),
+    Json(serde_json::Error),
+    MissingElement(&'static str),
+    MissingAttribute {
+        element: String,
+        attribute: &'static str,
+    },
+    InvalidValue {
+        attribute: String,
+        value: String,
+    },
+    UnsupportedOrientation(String),
+    UnsupportedEncoding(String),
+    InfiniteMap,
+    /// A tile layer whose data does not have one tile for each of its cells.
+    InvalidLayerSize {
+        width: usize,
+        height: usize,
+        tiles: usize,
+    },
+    /// Tilesets made of single images or with margin and spacing can not be turned into a texture atlas.
+    UnsupportedTileset(String),
+    TooManyTilesets(usize),
+    UnknownGid(u32),
+    Tileset(tileset::Error),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct TiledMap {
+    orientation: String,
+    infinite: bool,
+    tile_width: usize,
+    tile_height: usize,
+    tilesets: Vec<TiledTilesetRef>,
+    layers: Vec<TiledLayer>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TiledTilesetRef {
+    External {
+        first_gid: u32,
+        source: PathBuf,
+    },
+    Embedded {
+        first_gid: u32,
+        tileset: TiledTileset,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct TiledTileset {
+    name: String,
+    tile_width: usize,
+    tile_height: usize,
+    tile_count: usize,
+    columns: usize,
+    margin: usize,
+    spacing: usize,
+    image: Option<TiledImage>,
+    tiles: Vec<TiledTile>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct TiledImage {
+    source: PathBuf,
+    width: usize,
+    height: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct TiledTile {
+    id: u32,
+    /// Tile id and duration in milliseconds of every frame.
+    animation: Vec<(u32, u32)>,
+    properties: TileProperties,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct TiledLayer {
+    width: usize,
+    height: usize,
+    data: Vec<u32>,
+}
+
+/// Imports a Tiled map with isometric orientation.
+///
+/// Every tile layer becomes a layer with its index as ordering id, layers in groups are added in the order they are drawn.
+/// Tiled draws the first row at the top while this crate draws it at the bottom, so tiles are mirrored
+/// along the diagonal of the map to keep the map looking the same. Flipped tiles are imported unflipped.
+/// The tile size of the map is the size of the tile surface, so its height is doubled for the tile blocks of the definition.
+///
+/// Tilesets get the aliases `a` to `z` and `A` to `Z` in the order of the map. Embedded tilesets are linked as
+/// `{file_name}#{tileset name}`, which the asset loader provides as labeled assets.
+pub fn import_tiled_map(
+    bytes: &[u8],
+    format: TiledFormat,
+    file_name: &str,
+) -> Result<TiledImport, Error> {
+    let map = match format {
+        TiledFormat::Xml => parse_tmx(bytes)?,
+        TiledFormat::Json => parse_tmj(bytes)?,
+    };
+
+    if map.orientation != "isometric" {
+        return Err(Error::UnsupportedOrientation(map.orientation));
+    }
+
+    if map.infinite {
+        return Err(Error::InfiniteMap);
+    }
+
+    if map.tilesets.len() > ALIASES.len() {
+        return Err(Error::TooManyTilesets(map.tilesets.len()));
+    }
+
+    let name = Path::new(file_name)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| file_name.to_owned());
+    let mut builder =
+        TilemapDefinitionBuilder::new(&name).with_tile_size(map.tile_width, map.tile_height * 2);
+    let mut tilesets = Vec::new();
+    let mut first_gids = Vec::new();
+
+    for (tileset_ref, alias) in map.tilesets.iter().zip(ALIASES.chars()) {
+        let (first_gid, path) = match tileset_ref {
+            TiledTilesetRef::External { first_gid, source } => (*first_gid, source.clone()),
+            TiledTilesetRef::Embedded { first_gid, tileset } => {
+                let path = PathBuf::from(format!("{}#{}", file_name, tileset.name));
+                tilesets.push((path.clone(), convert_tileset(tileset)?));
+                (*first_gid, path)
+            }
+        };
+
+        first_gids.push((first_gid, alias));
+        builder = builder.add_tileset(TilesetLink::new(&path, alias));
+    }
+
+    for (index, layer) in map.layers.iter().enumerate() {
+        if layer.width == 0 || layer.height == 0 || layer.data.len() != layer.width * layer.height {
+            return Err(Error::InvalidLayerSize {
+                width: layer.width,
+                height: layer.height,
+                tiles: layer.data.len(),
+            });
+        }
+
+        let mut rows = vec![vec![TileIdentifier::empty(); layer.height]; layer.width];
+
+        for (i, gid) in layer.data.iter().enumerate() {
+            let gid = gid & !GID_FLAGS;
+
+            if gid == 0 {
+                continue;
+            }
+
+            let (first_gid, alias) = first_gids
+                .iter()
+                .filter(|(first_gid, _)| *first_gid <= gid)
+                .max_by_key(|(first_gid, _)| *first_gid)
+                .ok_or(Error::UnknownGid(gid))?;
+            let (x, y) = (i % layer.width, i / layer.width);
+
+            rows[layer.width - 1 - x][layer.height - 1 - y] =
+                TileIdentifier::new(gid - first_gid, *alias);
+        }
+
+        builder = builder.add_layer(LayerDefinition::new(index as u32).with_tiles(rows));
+    }
+
+    Ok(TiledImport {
+        tilemap: builder.build(),
+        tilesets,
+    })
+}
+
+/// Imports an external Tiled tileset.
+/// Animated tiles use the duration of their first frame for every frame.
+pub fn import_tiled_tileset(bytes: &[u8], format: TiledFormat) -> Result<TilesetDefinition, Error> {
+    let tileset = match format {
+        TiledFormat::Xml => parse_tileset_element(&parse_xml(bytes)?)?,
+        TiledFormat::Json => serde_json::from_slice::<TmjTileset>(bytes)
+            .map_err(Error::Json)?
+            .into(),
+    };
+
+    convert_tileset(&tileset)
+}
+
+/// Imports a Tiled map from the filesystem, together with all of its external tilesets.
+pub fn import_tiled_file(file_path: &Path) -> Result<TiledImport, Error> {
+    let format = TiledFormat::from_path(file_path)
+        .ok_or_else(|| Error::UnsupportedEncoding(file_path.display().to_string()))?;
+    let file_name = file_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut import =
+        import_tiled_map(&fs::read(file_path).map_err(Error::IO)?, format, &file_name)?;
+    let directory = file_path.parent().unwrap_or(Path::new(""));
+
+    for link in import.tilemap.tilesets() {
+        if import.tilesets.iter().any(|(path, _)| path == link.path()) {
+            continue;
+        }
+
+        let tileset_path = directory.join(link.path());
+        let tileset_format = TiledFormat::from_path(&tileset_path)
+            .ok_or_else(|| Error::UnsupportedEncoding(tileset_path.display().to_string()))?;
+        let tileset =
+            import_tiled_tileset(&fs::read(&tileset_path).map_err(Error::IO)?, tileset_format)?;
+
+        import.tilesets.push((link.path().to_owned(), tileset));
+    }
+
+    Ok(import)
+}
+
+impl TiledFormat {
+    /// Gets the format from the extension of a map or tileset file.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "tmx" | "tsx" => Some(TiledFormat::Xml),
+            "tmj" | "tsj" | "json" => Some(TiledFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::IO(error) => write!(f, "{}", error),
+            Error::Xml(error) => write!(f, "invalid xml: {}", error),
+            Error::Json(error) => write!(f, "invalid json: {}", error),
+            Error::MissingElement(element) => write!(f, "missing element <{}>", element),
+            Error::MissingAttribute { element, attribute } => {
+                write!(f, "missing attribute '{}' on <{}>", attribute, element)
+            }
+            Error::InvalidValue { attribute, value } => {
+                write!(f, "invalid value '{}' for '{}'", value, attribute)
+            }
+            Error::UnsupportedOrientation(orientation) => {
+                write!(f, "unsupported orientation '{}'", orientation)
+            }
+            Error::UnsupportedEncoding(encoding) => {
+                write!(f, "unsupported encoding '{}'", encoding)
+            }
+            Error::InfiniteMap => write!(f, "infinite maps are not supported"),
+            Error::InvalidLayerSize {
+                width,
+                height,
+                tiles,
+            } => write!(f, "layer of {}x{} cells has {} tiles", width, height, tiles),
+            Error::UnsupportedTileset(name) => write!(f, "unsupported tileset '{}'", name),
+            Error::TooManyTilesets(count) => write!(f, "too many tilesets ({})", count),
+            Error::UnknownGid(gid) => write!(f, "no tileset for tile {}", gid),
+            Error::Tileset(error) => write!(f, "invalid tileset: {:?}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+fn convert_tileset(tileset: &TiledTileset) -> Result<TilesetDefinition, Error> {
+    let Some(image) = tileset.image.as_ref() else {
+        return Err(Error::UnsupportedTileset(tileset.name.clone()));
+    };
+
+    if tileset.margin != 0
+        || tileset.spacing != 0
+        || tileset.tile_width == 0
+        || tileset.tile_height == 0
+    {
+        return Err(Error::UnsupportedTileset(tileset.name.clone()));
+    }
+
+    let columns = match tileset.columns {
+        0 => image.width / tileset.tile_width,
+        columns => columns,
+    };
+
+    // Images narrower than a single tile have no columns to place the tiles in.
+    if columns == 0 {
+        return Err(Error::UnsupportedTileset(tileset.name.clone()));
+    }
+
+    let tile_count = match tileset.tile_count {
+        0 => columns * (image.height / tileset.tile_height),
+        tile_count => tile_count,
+    };
+    let position = |id: u32| TilePosition::new(id as usize % columns, id as usize / columns);
+
+    let mut builder = TilesetDefinitionBuilder::new(SourceDefinition::new(
+        &image.source,
+        ImageDimensions::new(image.width, image.height),
+    ))
+    .with_name(&tileset.name)
+    .with_tile_size(tileset.tile_width, tileset.tile_height);
+
+    for id in 0..tile_count as u32 {
+        let tile = tileset.tiles.iter().find(|tile| tile.id == id);
+        let definition = match tile {
+            Some(tile) if !tile.animation.is_empty() => {
+                let interval = tile.animation[0].1 as f32 / 1000.0;
+
+                tile.animation
+                    .iter()
+                    .fold(
+                        AnimatedTileDefBuilder::new(id).with_interval(interval),
+                        |builder, (frame, _)| builder.add_position(position(*frame)),
+                    )
+                    .build()
+            }
+            _ => {
+                let position = position(id);
+                TileDefinition::new_standard(id, position.x(), position.y())
+            }
+        };
+
+        builder = builder.add_tile(match tile {
+            Some(tile) => definition.with_properties(tile.properties.clone()),
+            None => definition,
+        });
+    }
+
+    builder.build().map_err(Error::Tileset)
+}
+
+fn property(kind: &str, value: &str) -> Result<TileProperty, Error> {
+    let invalid = || Error::InvalidValue {
+        attribute: kind.to_owned(),
+        value: value.to_owned(),
+    };
+
+    Ok(match kind {
+        "bool" => TileProperty::Bool(value.parse().map_err(|_| invalid())?),
+        "int" | "object" => TileProperty::Int(value.parse().map_err(|_| invalid())?),
+        "float" => TileProperty::Float(value.parse().map_err(|_| invalid())?),
+        _ => TileProperty::String(value.to_owned()),
+    })
+}
+
+/// A parsed xml element, the Tiled files are small enough to be read at once.
+struct XmlElement {
+    name: String,
+    attributes: HashMap<String, String>,
+    children: Vec<XmlElement>,
+    text: String,
+}
+
+impl XmlElement {
+    fn attribute(&self, attribute: &'static str) -> Result<&str, Error> {
+        self.attributes
+            .get(attribute)
+            .map(String::as_str)
+            .ok_or_else(|| Error::MissingAttribute {
+                element: self.name.clone(),
+                attribute,
+            })
+    }
+
+    fn parse<T: FromStr>(&self, attribute: &'static str) -> Result<T, Error> {
+        let value = self.attribute(attribute)?;
+
+        value.trim().parse().map_err(|_| Error::InvalidValue {
+            attribute: attribute.to_owned(),
+            value: value.to_owned(),
+        })
+    }
+
+    fn parse_or<T: FromStr>(&self, attribute: &'static str, default: T) -> Result<T, Error> {
+        match self.attributes.contains_key(attribute) {
+            true => self.parse(attribute),
+            false => Ok(default),
+        }
+    }
+
+    fn child(&self, name: &str) -> Option<&XmlElement> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a XmlElement> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+}
+
+fn parse_xml(bytes: &[u8]) -> Result<XmlElement, Error> {
+    let mut stack: Vec<XmlElement> = Vec::new();
+
+    for event in EventReader::new(bytes) {
+        match event.map_err(|error| Error::Xml(error.to_string()))? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => stack.push(XmlElement {
+                name: name.local_name,
+                attributes: attributes
+                    .into_iter()
+                    .map(|attribute| (attribute.name.local_name, attribute.value))
+                    .collect(),
+                children: Vec::new(),
+                text: String::new(),
+            }),
+            XmlEvent::EndElement { .. } => {
+                let Some(element) = stack.pop() else {
+                    continue;
+                };
+
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Ok(element),
+                }
+            }
+            XmlEvent::Characters(text) | XmlEvent::CData(text) => {
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(&text);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Err(Error::MissingElement("map"))
+}
+
+fn parse_tmx(bytes: &[u8]) -> Result<TiledMap, Error> {
+    let map = parse_xml(bytes)?;
+    let mut tilesets = Vec::new();
+    let mut layers = Vec::new();
+
+    for tileset in map.children("tileset") {
+        let first_gid = tileset.parse("firstgid")?;
+
+        tilesets.push(match tileset.attributes.get("source") {
+            Some(source) => TiledTilesetRef::External {
+                first_gid,
+                source: PathBuf::from(source),
+            },
+            None => TiledTilesetRef::Embedded {
+                first_gid,
+                tileset: parse_tileset_element(tileset)?,
+            },
+        });
+    }
+
+    parse_tmx_layers(&map, &mut layers)?;
+
+    Ok(TiledMap {
+        orientation: map.attribute("orientation")?.to_owned(),
+        infinite: map.parse_or::<u8>("infinite", 0)? != 0,
+        tile_width: map.parse("tilewidth")?,
+        tile_height: map.parse("tileheight")?,
+        tilesets,
+        layers,
+    })
+}
+
+fn parse_tmx_layers(parent: &XmlElement, layers: &mut Vec<TiledLayer>) -> Result<(), Error> {
+    for child in parent.children.iter() {
+        match child.name.as_str() {
+            "layer" => {
+                let data = child.child("data").ok_or(Error::MissingElement("data"))?;
+                let encoding = data.attributes.get("encoding").map(String::as_str);
+
+                if encoding != Some("csv") {
+                    return Err(Error::UnsupportedEncoding(
+                        encoding.unwrap_or("xml").to_owned(),
+                    ));
+                }
+
+                layers.push(TiledLayer {
+                    width: child.parse("width")?,
+                    height: child.parse("height")?,
+                    data: data
+                        .text
+                        .split(',')
+                        .map(|gid| {
+                            gid.trim().parse().map_err(|_| Error::InvalidValue {
+                                attribute: "data".to_owned(),
+                                value: gid.to_owned(),
+                            })
+                        })
+                        .collect::<Result<_, _>>()?,
+                });
+            }
+            "group" => parse_tmx_layers(child, layers)?,
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_tileset_element(tileset: &XmlElement) -> Result<TiledTileset, Error> {
+    let image = match tileset.child("image") {
+        Some(image) => Some(TiledImage {
+            source: PathBuf::from(image.attribute("source")?),
+            width: image.parse("width")?,
+            height: image.parse("height")?,
+        }),
+        None => None,
+    };
+
+    let tiles = tileset
+        .children("tile")
+        .map(|tile| {
+            let animation = tile
+                .child("animation")
+                .map(|animation| {
+                    animation
+                        .children("frame")
+                        .map(|frame| Ok((frame.parse("tileid")?, frame.parse("duration")?)))
+                        .collect::<Result<Vec<_>, Error>>()
+                })
+                .transpose()?
+                .unwrap_or_default();
+            let mut properties = TileProperties::new();
+
+            for element in tile
+                .child("properties")
+                .into_iter()
+                .flat_map(|properties| properties.children("property"))
+            {
+                let kind = element.attributes.get("type").map(String::as_str);
+                let value = element
+                    .attributes
+                    .get("value")
+                    .cloned()
+                    .unwrap_or_else(|| element.text.clone());
+
+                properties.insert(
+                    element.attribute("name")?,
+                    property(kind.unwrap_or("string"), &value)?,
+                );
+            }
+
+            Ok(TiledTile {
+                id: tile.parse("id")?,
+                animation,
+                properties,
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    Ok(TiledTileset {
+        name: tileset.attribute("name")?.to_owned(),
+        tile_width: tileset.parse("tilewidth")?,
+        tile_height: tileset.parse("tileheight")?,
+        tile_count: tileset.parse_or("tilecount", 0)?,
+        columns: tileset.parse_or("columns", 0)?,
+        margin: tileset.parse_or("margin", 0)?,
+        spacing: tileset.parse_or("spacing", 0)?,
+        image,
+        tiles,
+    })
+}
+
+#[derive(Deserialize)]
+struct TmjMap {
+    orientation: String,
+    #[serde(default)]
+    infinite: bool,
+    tilewidth: usize,
+    tileheight: usize,
+    tilesets: Vec<TmjTilesetRef>,
+    layers: Vec<TmjLayer>,
+}
+
+#[derive(Deserialize)]
+struct TmjTilesetRef {
+    firstgid: u32,
+    source: Option<PathBuf>,
+    #[serde(flatten)]
+    tileset: TmjTileset,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct TmjTileset {
+    name: String,
+    tilewidth: usize,
+    tileheight: usize,
+    tilecount: usize,
+    columns: usize,
+    margin: usize,
+    spacing: usize,
+    image: Option<PathBuf>,
+    imagewidth: usize,
+    imageheight: usize,
+    tiles: Vec<TmjTile>,
+}
+
+#[derive(Deserialize)]
+struct TmjTile {
+    id: u32,
+    #[serde(default)]
+    animation: Vec<TmjFrame>,
+    #[serde(default)]
+    properties: Vec<TmjProperty>,
+}
+
+#[derive(Deserialize)]
+struct TmjFrame {
+    tileid: u32,
+    duration: u32,
+}
+
+#[derive(Deserialize)]
+struct TmjProperty {
+    name: String,
+    #[serde(rename = "type", default)]
+    kind: Option<String>,
+    value: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum TmjLayer {
+    #[serde(rename = "tilelayer")]
+    Tiles {
+        width: usize,
+        height: usize,
+        data: serde_json::Value,
+        #[serde(default)]
+        encoding: Option<String>,
+    },
+    #[serde(rename = "group")]
+    Group { layers: Vec<TmjLayer> },
+    #[serde(other)]
+    Other,
+}
+
+impl From<TmjTileset> for TiledTileset {
+    fn from(value: TmjTileset) -> Self {
+        Self {
+            name: value.name,
+            tile_width: value.tilewidth,
+            tile_height: value.tileheight,
+            tile_count: value.tilecount,
+            columns: value.columns,
+            margin: value.margin,
+            spacing: value.spacing,
+            image: value.image.map(|source| TiledImage {
+                source,
+                width: value.imagewidth,
+                height: value.imageheight,
+            }),
+            tiles: value
+                .tiles
+                .into_iter()
+                .map(|tile| TiledTile {
+                    id: tile.id,
+                    animation: tile
+                        .animation
+                        .into_iter()
+                        .map(|frame| (frame.tileid, frame.duration))
+                        .collect(),
+                    properties: tile.properties.into_iter().fold(
+                        TileProperties::new(),
+                        |properties, property| {
+                            let value = match property.value {
+                                serde_json::Value::Bool(value) => TileProperty::Bool(value),
+                                serde_json::Value::Number(number) => {
+                                    match (property.kind.as_deref(), number.as_i64()) {
+                                        (Some("float"), _) | (_, None) => {
+                                            TileProperty::Float(number.as_f64().unwrap_or_default())
+                                        }
+                                        (_, Some(value)) => TileProperty::Int(value),
+                                    }
+                                }
+                                serde_json::Value::String(value) => TileProperty::String(value),
+                                value => TileProperty::String(value.to_string()),
+                            };
+
+                            properties.with(&property.name, value)
+                        },
+                    ),
+                })
+                .collect(),
+        }
+    }
+}
+
+fn parse_tmj(bytes: &[u8]) -> Result<TiledMap, Error> {
+    let map = serde_json::from_slice::<TmjMap>(bytes).map_err(Error::Json)?;
+    let mut layers = Vec::new();
+
+    parse_tmj_layers(map.layers, &mut layers)?;
+
+    Ok(TiledMap {
+        orientation: map.orientation,
+        infinite: map.infinite,
+        tile_width: map.tilewidth,
+        tile_height: map.tileheight,
+        tilesets: map
+            .tilesets
+            .into_iter()
+            .map(|tileset| match tileset.source {
+                Some(source) => TiledTilesetRef::External {
+                    first_gid: tileset.firstgid,
+                    source,
+                },
+                None => TiledTilesetRef::Embedded {
+                    first_gid: tileset.firstgid,
+                    tileset: tileset.tileset.into(),
+                },
+            })
+            .collect(),
+        layers,
+    })
+}
+
+fn parse_tmj_layers(source: Vec<TmjLayer>, layers: &mut Vec<TiledLayer>) -> Result<(), Error> {
+    for layer in source {
+        match layer {
+            TmjLayer::Tiles {
+                width,
+                height,
+                data,
+                encoding,
+            } => {
+                if let Some(encoding) = encoding.filter(|encoding| encoding != "csv") {
+                    return Err(Error::UnsupportedEncoding(encoding));
+                }
+
+                layers.push(TiledLayer {
+                    width,
+                    height,
+                    data: serde_json::from_value(data).map_err(Error::Json)?,
+                });
+            }
+            TmjLayer::Group { layers: children } => parse_tmj_layers(children, layers)?,
+            TmjLayer::Other => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::loading::{
+        tiled::{import_tiled_file, import_tiled_map, import_tiled_tileset, Error, TiledFormat},
+        tilemap::{TileIdentifier, TileReference},
+        tileset::TileDefinition,
+    };
+
+    const MAP_TMX: &str = include_str!("../../fixtures/tiled/island.tmx");
+    const MAP_TMJ: &str = include_str!("../../fixtures/tiled/island.tmj");
+    const TILESET_TSX: &str = include_str!("../../fixtures/tiled/blocks.tsx");
+
+    #[test]
+    fn test_import_tmx() {
+        let import = import_tiled_map(MAP_TMX.as_bytes(), TiledFormat::Xml, "island.tmx").unwrap();
+        let tilemap = import.tilemap;
+
+        assert_eq!("island", tilemap.name());
+        assert_eq!(64, tilemap.tile_size().height());
+        assert_eq!(2, tilemap.layers().len());
+        assert_eq!(1, tilemap.layers()[1].ordering_id());
+        assert_eq!(
+            Path::new("island.tmx#water"),
+            tilemap.tileset('a').unwrap().path()
+        );
+        assert_eq!(
+            Path::new("blocks.tsx"),
+            tilemap.tileset('b').unwrap().path()
+        );
+
+        // The map is 3x2 tiles, so the tile in the last column of the first row is at the bottom of the imported grid.
+        let ground = tilemap.layers()[0].tiles();
+        assert_eq!(3, ground.len());
+        assert_eq!(2, ground[0].len());
+        assert_eq!(
+            Some(TileReference::Tile { alias: 'b', id: 2 }),
+            ground[0][1].parse()
+        );
+        assert_eq!(
+            Some(TileReference::Tile { alias: 'a', id: 0 }),
+            ground[2][1].parse()
+        );
+        assert_eq!(TileIdentifier::new(1, 'a'), ground[2][0]);
+
+        let decoration = tilemap.layers()[1].tiles();
+        assert!(decoration[0][0].is_empty());
+        assert_eq!(TileIdentifier::new(0, 'b'), decoration[1][0]);
+    }
+
+    #[test]
+    fn test_import_embedded_tileset() {
+        let import = import_tiled_map(MAP_TMX.as_bytes(), TiledFormat::Xml, "island.tmx").unwrap();
+        let (path, water) = &import.tilesets[0];
+
+        assert_eq!(1, import.tilesets.len());
+        assert_eq!(&PathBuf::from("island.tmx#water"), path);
+        assert_eq!("water", water.name());
+        assert_eq!(Some(vec![0, 1, 2]), water.atlas_indices(0));
+        assert!(matches!(
+            water.tile(0),
+            Some(TileDefinition::Animated {
+                interval_per_sec,
+                ..
+            }) if *interval_per_sec == 0.25
+        ));
+        assert_eq!(
+            Some(true),
+            water.tile(0).unwrap().properties().get_bool("water")
+        );
+        assert_eq!(Some(2), water.tile(1).unwrap().properties().get_int("cost"));
+    }
+
+    #[test]
+    fn test_tmj_matches_tmx() {
+        let tmx = import_tiled_map(MAP_TMX.as_bytes(), TiledFormat::Xml, "island.tmx").unwrap();
+        let tmj = import_tiled_map(MAP_TMJ.as_bytes(), TiledFormat::Json, "island.tmx").unwrap();
+
+        assert_eq!(tmx, tmj);
+    }
+
+    #[test]
+    fn test_import_external_tileset() {
+        let tileset = import_tiled_tileset(TILESET_TSX.as_bytes(), TiledFormat::Xml).unwrap();
+
+        assert_eq!("blocks", tileset.name());
+        assert_eq!(4, tileset.tiles().len());
+        assert_eq!(
+            Some(&TileDefinition::new_standard(3, 1, 1)),
+            tileset.tile(3)
+        );
+    }
+
+    #[test]
+    fn test_import_file() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/tiled/island.tmx");
+        let import = import_tiled_file(&path).unwrap();
+
+        assert_eq!(2, import.tilesets.len());
+        assert_eq!(PathBuf::from("blocks.tsx"), import.tilesets[1].0);
+        assert!(import
+            .tilemap
+            .validate(|link| import
+                .tilesets
+                .iter()
+                .find(|(path, _)| path == link.path())
+                .map(|(_, tileset)| tileset))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_truncated_layer_data() {
+        let map = MAP_TMX.replace("0,5,0\n", "0,5\n");
+
+        assert!(matches!(
+            import_tiled_map(map.as_bytes(), TiledFormat::Xml, "island.tmx"),
+            Err(Error::InvalidLayerSize {
+                width: 3,
+                height: 2,
+                tiles: 5
+            })
+        ));
+
+        let map = MAP_TMX.replace("width=\"3\" height=\"2\">", "width=\"0\" height=\"2\">");
+        assert!(matches!(
+            import_tiled_map(map.as_bytes(), TiledFormat::Xml, "island.tmx"),
+            Err(Error::InvalidLayerSize { width: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_image_narrower_than_tile() {
+        let tileset = TILESET_TSX
+            .replace("columns=\"2\"", "columns=\"0\"")
+            .replace("width=\"128\"", "width=\"32\"");
+
+        assert!(matches!(
+            import_tiled_tileset(tileset.as_bytes(), TiledFormat::Xml),
+            Err(Error::UnsupportedTileset(_))
+        ));
+
+        let tileset = TILESET_TSX.replace("tileheight=\"64\"", "tileheight=\"0\"");
+        assert!(matches!(
+            import_tiled_tileset(tileset.as_bytes(), TiledFormat::Xml),
+            Err(Error::UnsupportedTileset(_))
+        ));
+    }
+
+    #[test]
+    fn test_unsupported_orientation() {
+        let map = MAP_TMX.replace("orientation=\"isometric\"", "orientation=\"orthogonal\"");
+
+        assert!(matches!(
+            import_tiled_map(map.as_bytes(), TiledFormat::Xml, "island.tmx"),
+            Err(Error::UnsupportedOrientation(_))
+        ));
+    }
+}
@@ -1,9 +1,13 @@
-use std::path::{Path, PathBuf};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
 use bevy::reflect::{Reflect, TypeUuid};
+use ron::ser::PrettyConfig;
 use serde::{Deserialize, Serialize};
 
-use super::tileset::TileSize;
+use super::tileset::{TileSize, TilesetDefinition};
 
 #[derive(TypeUuid, Reflect, Default, Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[uuid = "dd9b8ac0-170d-4ac5-a915-12fffd75df35"]
@@ -18,6 +22,9 @@ pub struct TilemapDefinition {
 pub struct LayerDefinition {
     ordering_id: u32,
     tiles: Vec<Vec<TileIdentifier>>,
+    /// Elevation of the tiles in tile heights, in the same rows as the tiles. Missing cells are not elevated.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    elevations: Vec<Vec<f32>>,
 }
 
 #[derive(Reflect, Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -37,14 +44,212 @@ pub struct TilemapDefinitionBuilder {
 #[derive(Serialize, Reflect, Deserialize, Debug, Clone, PartialEq)]
 pub struct TileIdentifier(String);
 
+/// Parsed form of a `TileIdentifier`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TileReference {
+    Empty,
+    Tile { alias: char, id: u32 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    InvalidIdentifier {
+        layer: u32,
+        x: usize,
+        y: usize,
+        identifier: String,
+    },
+    UnknownAlias {
+        layer: u32,
+        x: usize,
+        y: usize,
+        alias: char,
+    },
+    MissingTileset {
+        alias: char,
+        path: PathBuf,
+    },
+    MissingTileId {
+        layer: u32,
+        x: usize,
+        y: usize,
+        alias: char,
+        id: u32,
+    },
+    RaggedLayer {
+        layer: u32,
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// A row of elevations whose length differs from the row of tiles it belongs to.
+    /// Rows which only one of both has are reported with a length of 0 for the other one.
+    MismatchedElevations {
+        layer: u32,
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl TilemapDefinition {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn tilesets(&self) -> &[TilesetLink] {
+        &self.tilesets
+    }
+
+    pub fn tile_size(&self) -> TileSize {
+        self.tile_size
+    }
+
+    pub fn layers(&self) -> &[LayerDefinition] {
+        &self.layers
+    }
+
+    pub fn tileset(&self, alias: char) -> Option<&TilesetLink> {
+        self.tilesets.iter().find(|link| link.alias == alias)
+    }
+
+    /// Loads a tilemap definition from a RON file.
+    pub fn load(file_path: &Path) -> Result<Self, super::Error> {
+        let contents = fs::read_to_string(file_path).map_err(super::Error::IO)?;
+
+        ron::from_str(&contents).map_err(|error| super::Error::Ron(error.code))
+    }
+
+    /// Saves the tilemap definition as RON file.
+    pub fn save(&self, file_path: &Path) -> Result<(), super::Error> {
+        let serialized =
+            ron::ser::to_string_pretty(self, PrettyConfig::default()).map_err(super::Error::Ron)?;
+
+        fs::write(file_path, serialized).map_err(super::Error::IO)
+    }
+
+    /// Checks that every tile identifier can be resolved, that all rows of a layer have the same length
+    /// and that the elevations of a layer, if it has any, have the same shape as its tiles.
+    /// The tilesets are looked up through the given function, links it can not resolve are reported as missing.
+    pub fn validate<'a>(
+        &self,
+        tileset: impl Fn(&TilesetLink) -> Option<&'a TilesetDefinition>,
+    ) -> Result<(), Vec<Error>> {
+        let mut errors = Vec::new();
+
+        for link in self.tilesets.iter() {
+            if tileset(link).is_none() {
+                errors.push(Error::MissingTileset {
+                    alias: link.alias,
+                    path: link.path.clone(),
+                });
+            }
+        }
+
+        for layer in self.layers.iter() {
+            let expected = layer.tiles.first().map(|row| row.len()).unwrap_or_default();
+
+            if !layer.elevations.is_empty() {
+                for y in 0..layer.tiles.len().max(layer.elevations.len()) {
+                    let tiles = layer.tiles.get(y).map(|row| row.len()).unwrap_or_default();
+                    let elevations = layer
+                        .elevations
+                        .get(y)
+                        .map(|row| row.len())
+                        .unwrap_or_default();
+
+                    if tiles != elevations {
+                        errors.push(Error::MismatchedElevations {
+                            layer: layer.ordering_id,
+                            row: y,
+                            expected: tiles,
+                            found: elevations,
+                        });
+                    }
+                }
+            }
+
+            for (y, row) in layer.tiles.iter().enumerate() {
+                if row.len() != expected {
+                    errors.push(Error::RaggedLayer {
+                        layer: layer.ordering_id,
+                        row: y,
+                        expected,
+                        found: row.len(),
+                    });
+                }
+
+                for (x, identifier) in row.iter().enumerate() {
+                    let (alias, id) = match identifier.parse() {
+                        Some(TileReference::Tile { alias, id }) => (alias, id),
+                        Some(TileReference::Empty) => continue,
+                        None => {
+                            errors.push(Error::InvalidIdentifier {
+                                layer: layer.ordering_id,
+                                x,
+                                y,
+                                identifier: identifier.0.clone(),
+                            });
+                            continue;
+                        }
+                    };
+
+                    let Some(link) = self.tileset(alias) else {
+                        errors.push(Error::UnknownAlias {
+                            layer: layer.ordering_id,
+                            x,
+                            y,
+                            alias,
+                        });
+                        continue;
+                    };
+
+                    if let Some(definition) = tileset(link) {
+                        if definition.tile(id).is_none() {
+                            errors.push(Error::MissingTileId {
+                                layer: layer.ordering_id,
+                                x,
+                                y,
+                                alias,
+                                id,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            return Ok(());
+        }
+
+        Err(errors)
+    }
+}
+
 impl LayerDefinition {
     pub fn new(ordering_id: u32) -> Self {
         Self {
             ordering_id,
             tiles: Vec::new(),
+            elevations: Vec::new(),
         }
     }
 
+    pub fn with_tiles(mut self, tiles: Vec<Vec<TileIdentifier>>) -> Self {
+        self.tiles = tiles;
+        self
+    }
+
+    /// Sets the elevation of the tiles. Layers without elevated tiles store no elevations at all.
+    pub fn with_elevations(mut self, elevations: Vec<Vec<f32>>) -> Self {
+        self.elevations = match elevations.iter().flatten().any(|height| *height != 0.0) {
+            true => elevations,
+            false => Vec::new(),
+        };
+        self
+    }
+
     pub fn ordering_id(&self) -> u32 {
         self.ordering_id
     }
@@ -52,6 +257,19 @@ impl LayerDefinition {
     pub fn tiles(&self) -> &[Vec<TileIdentifier>] {
         &self.tiles
     }
+
+    pub fn elevations(&self) -> &[Vec<f32>] {
+        &self.elevations
+    }
+
+    /// The elevation of the tile in the given column and row, 0 if the layer has none for it.
+    pub fn elevation(&self, x: usize, y: usize) -> f32 {
+        self.elevations
+            .get(y)
+            .and_then(|row| row.get(x))
+            .copied()
+            .unwrap_or_default()
+    }
 }
 
 impl TilesetLink {
@@ -72,13 +290,59 @@ impl TilesetLink {
 }
 
 impl TileIdentifier {
+    /// Identifier of cells without a tile.
+    pub const EMPTY: &'static str = "-";
+
     pub fn new(id: u32, alias: char) -> Self {
         Self(format!("{id}_{alias}"))
     }
 
+    pub fn empty() -> Self {
+        Self(String::from(Self::EMPTY))
+    }
+
     pub fn value(&self) -> &str {
         &self.0
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == Self::EMPTY
+    }
+
+    /// Parses the identifier into a tile reference.
+    /// Accepts the `"{id}_{alias}"` format as well as the short `"{alias}{id}"` format, aliases have to be letters.
+    pub fn parse(&self) -> Option<TileReference> {
+        if self.is_empty() {
+            return Some(TileReference::Empty);
+        }
+
+        let (id, alias) = match self.0.split_once('_') {
+            Some((id, alias)) => (id, alias),
+            None => {
+                let alias_len = self.0.chars().next()?.len_utf8();
+                let (alias, id) = self.0.split_at(alias_len);
+                (id, alias)
+            }
+        };
+
+        let mut alias_chars = alias.chars();
+
+        match (id.parse::<u32>(), alias_chars.next(), alias_chars.next()) {
+            (Ok(id), Some(alias), None) if alias.is_alphabetic() => {
+                Some(TileReference::Tile { alias, id })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl From<TileReference> for TileIdentifier {
+    fn from(value: TileReference) -> Self {
+        match value {
+            TileReference::Empty => Self::empty(),
+            TileReference::Tile { alias, id } => Self::new(id, alias),
+        }
+    }
 }
 
 impl TilemapDefinitionBuilder {
@@ -188,10 +452,144 @@ impl TilemapDefinitionBuilder {
 
 #[cfg(test)]
 mod tests {
-    use crate::loading::tilemap::{TilemapDefinition, TilemapDefinitionBuilder, TilesetLink};
-    use crate::loading::tileset::TileSize;
+    use crate::loading::tilemap::{
+        Error, LayerDefinition, TileIdentifier, TileReference, TilemapDefinition,
+        TilemapDefinitionBuilder, TilesetLink,
+    };
+    use crate::loading::tileset::{
+        ImageDimensions, SourceDefinition, TileDefinition, TileSize, TilesetDefinition,
+        TilesetDefinitionBuilder,
+    };
     use std::path::Path;
 
+    fn tileset() -> TilesetDefinition {
+        TilesetDefinitionBuilder::new(SourceDefinition::new(
+            Path::new("./tiles.png"),
+            ImageDimensions::new(32, 16),
+        ))
+        .add_tile(TileDefinition::new_standard(0, 0, 0))
+        .build()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_identifier() {
+        assert_eq!(
+            Some(TileReference::Tile { alias: 't', id: 12 }),
+            TileIdentifier::new(12, 't').parse()
+        );
+        assert_eq!(
+            Some(TileReference::Tile { alias: 't', id: 0 }),
+            TileIdentifier(String::from("t0")).parse()
+        );
+        assert_eq!(Some(TileReference::Empty), TileIdentifier::empty().parse());
+        assert_eq!(None, TileIdentifier(String::from("-1")).parse());
+        assert_eq!(None, TileIdentifier(String::from("0_tt")).parse());
+        assert_eq!(None, TileIdentifier(String::new()).parse());
+    }
+
+    #[test]
+    fn test_validate() {
+        let tileset = tileset();
+        let definition = TilemapDefinitionBuilder::new("testmap")
+            .add_tileset(TilesetLink::new(Path::new("./tiles.its"), 't'))
+            .add_layer(LayerDefinition::new(0).with_tiles(vec![
+                vec![TileIdentifier::new(0, 't'), TileIdentifier::empty()],
+                vec![TileIdentifier::new(1, 't'), TileIdentifier::new(0, 'x')],
+                vec![TileIdentifier(String::from("?"))],
+            ]))
+            .build();
+
+        assert_eq!(
+            Err(vec![
+                Error::MissingTileId {
+                    layer: 0,
+                    x: 0,
+                    y: 1,
+                    alias: 't',
+                    id: 1
+                },
+                Error::UnknownAlias {
+                    layer: 0,
+                    x: 1,
+                    y: 1,
+                    alias: 'x'
+                },
+                Error::RaggedLayer {
+                    layer: 0,
+                    row: 2,
+                    expected: 2,
+                    found: 1
+                },
+                Error::InvalidIdentifier {
+                    layer: 0,
+                    x: 0,
+                    y: 2,
+                    identifier: String::from("?")
+                },
+            ]),
+            definition.validate(|_| Some(&tileset))
+        );
+    }
+
+    #[test]
+    fn test_validate_elevations() {
+        let tileset = tileset();
+        let row = vec![TileIdentifier::new(0, 't'), TileIdentifier::new(0, 't')];
+        let definition = TilemapDefinitionBuilder::new("testmap")
+            .add_tileset(TilesetLink::new(Path::new("./tiles.its"), 't'))
+            .add_layer(
+                LayerDefinition::new(0)
+                    .with_tiles(vec![row.clone(), row.clone()])
+                    .with_elevations(vec![vec![1.0, 0.0], vec![0.5], vec![0.0, 2.0]]),
+            )
+            .add_layer(
+                LayerDefinition::new(1)
+                    .with_tiles(vec![row.clone(), row])
+                    .with_elevations(vec![vec![1.0, 0.0]]),
+            )
+            .build();
+
+        assert_eq!(
+            Err(vec![
+                Error::MismatchedElevations {
+                    layer: 0,
+                    row: 1,
+                    expected: 2,
+                    found: 1
+                },
+                Error::MismatchedElevations {
+                    layer: 0,
+                    row: 2,
+                    expected: 0,
+                    found: 2
+                },
+                Error::MismatchedElevations {
+                    layer: 1,
+                    row: 1,
+                    expected: 2,
+                    found: 0
+                },
+            ]),
+            definition.validate(|_| Some(&tileset))
+        );
+    }
+
+    #[test]
+    fn test_validate_missing_tileset() {
+        let definition = TilemapDefinitionBuilder::new("testmap")
+            .add_tileset(TilesetLink::new(Path::new("./tiles.its"), 't'))
+            .build();
+
+        assert_eq!(
+            Err(vec![Error::MissingTileset {
+                alias: 't',
+                path: Path::new("./tiles.its").to_owned()
+            }]),
+            definition.validate(|_| None)
+        );
+    }
+
     #[test]
     fn test_add_layer() {
         let definition = TilemapDefinitionBuilder::new("testmap.json")
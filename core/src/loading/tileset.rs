@@ -1,14 +1,23 @@
-use bevy::reflect::Reflect;
+use bevy::{
+    prelude::*,
+    reflect::{Reflect, TypePath, TypeUuid},
+};
 use itertools::Itertools;
-use serde::{Serialize, Deserialize};
-use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+use crate::tile::{TileProperties, TileProperty};
+
+#[derive(TypeUuid, TypePath, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[uuid = "9e3f5945-d512-4f32-9c4f-920ebf421cf4"]
 pub struct TilesetDefinition {
-    name: String,
-    tile_size: TileSize,
-    source: SourceDefinition,
-    tiles: Vec<TileDefinition>,
+    pub(super) name: String,
+    pub(super) tile_size: TileSize,
+    pub(super) source: SourceDefinition,
+    pub(super) tiles: Vec<TileDefinition>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -16,6 +25,7 @@ pub struct AnimatedTileDefBuilder {
     id: u32,
     intervals: f32,
     positions: Vec<TilePosition>,
+    properties: TileProperties,
 }
 
 #[derive(Reflect, Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Default)]
@@ -42,11 +52,15 @@ pub enum TileDefinition {
         id: u32,
         x: usize,
         y: usize,
+        #[serde(default, skip_serializing_if = "TileProperties::is_empty")]
+        properties: TileProperties,
     },
     Animated {
         id: u32,
         positions: Vec<TilePosition>,
         interval_per_sec: f32,
+        #[serde(default, skip_serializing_if = "TileProperties::is_empty")]
+        properties: TileProperties,
     },
 }
 
@@ -70,12 +84,131 @@ pub enum Error {
     DublicatedTileIds(Vec<(u32, u32)>),
     DublicatedTilePositions(Vec<((usize, usize), Vec<u32>)>),
     TileOutOfBounds(u32),
+    /// Animated tiles whose interval is not a positive number of seconds.
+    InvalidIntervals(Vec<u32>),
+}
+
+impl TilesetDefinition {
+    /// Checks the tiles for dublicated ids and positions as well as invalid animation intervals.
+    /// Definitions read from files skip the builder and should be validated before they are used.
+    pub fn validate(&self) -> Result<(), Error> {
+        let dublicated_ids = TilesetDefinitionBuilder::get_dublicated_ids(&self.tiles);
+
+        if !dublicated_ids.is_empty() {
+            return Err(Error::DublicatedTileIds(dublicated_ids));
+        }
+
+        let dublicated_positions = TilesetDefinitionBuilder::get_dublicated_positions(&self.tiles);
+
+        if !dublicated_positions.is_empty() {
+            return Err(Error::DublicatedTilePositions(dublicated_positions));
+        }
+
+        let invalid_intervals = self
+            .tiles
+            .iter()
+            .filter_map(|tile| match tile {
+                TileDefinition::Animated {
+                    id,
+                    interval_per_sec,
+                    ..
+                } if !(interval_per_sec.is_finite() && *interval_per_sec > 0.0) => Some(*id),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        if !invalid_intervals.is_empty() {
+            return Err(Error::InvalidIntervals(invalid_intervals));
+        }
+
+        Ok(())
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn tile_size(&self) -> TileSize {
+        self.tile_size
+    }
+
+    pub fn source(&self) -> &SourceDefinition {
+        &self.source
+    }
+
+    pub fn tiles(&self) -> &[TileDefinition] {
+        &self.tiles
+    }
+
+    pub fn tile(&self, id: u32) -> Option<&TileDefinition> {
+        self.tiles.iter().find(|tile| tile.id() == id)
+    }
+
+    /// Creates a texture atlas with one rect per tile position, in the order the tiles are defined.
+    /// Animated tiles add one rect for each of their frames.
+    pub fn texture_atlas(&self, texture: Handle<Image>) -> TextureAtlas {
+        let dimensions = Vec2::new(
+            self.source.dimensions.width as f32,
+            self.source.dimensions.height as f32,
+        );
+        let mut texture_atlas = TextureAtlas::new_empty(texture, dimensions);
+
+        for tile in self.tiles.iter() {
+            for position in tile.positions() {
+                texture_atlas.add_texture(position.rect(self.tile_size));
+            }
+        }
+
+        texture_atlas
+    }
+
+    /// Gets the indices of all atlas rects belonging to the tile with the given id.
+    pub fn atlas_indices(&self, id: u32) -> Option<Vec<usize>> {
+        let first = self.atlas_index(id)?;
+        let count = self.tile(id)?.positions().len();
+
+        Some((first..first + count).collect())
+    }
+
+    /// Gets the index of the first atlas rect belonging to the tile with the given id.
+    pub fn atlas_index(&self, id: u32) -> Option<usize> {
+        let mut index = 0;
+
+        for tile in self.tiles.iter() {
+            if tile.id() == id {
+                return Some(index);
+            }
+
+            index += tile.positions().len();
+        }
+
+        None
+    }
 }
 
 impl TilePosition {
     pub fn new(x: usize, y: usize) -> Self {
         Self { x, y }
     }
+
+    pub fn x(&self) -> usize {
+        self.x
+    }
+
+    pub fn y(&self) -> usize {
+        self.y
+    }
+
+    /// The area of the tile inside of the source image.
+    pub fn rect(&self, tile_size: TileSize) -> Rect {
+        let min = Vec2::new(
+            (self.x * tile_size.width) as f32,
+            (self.y * tile_size.height) as f32,
+        );
+        let size = Vec2::new(tile_size.width as f32, tile_size.height as f32);
+
+        Rect::from_corners(min, min + size)
+    }
 }
 
 impl ImageDimensions {
@@ -125,27 +258,57 @@ impl TileSize {
 
 impl TileDefinition {
     pub fn new_standard(id: u32, x: usize, y: usize) -> Self {
-        Self::Standard { id, x, y }
+        Self::Standard {
+            id,
+            x,
+            y,
+            properties: TileProperties::default(),
+        }
     }
 
     pub fn new_animated(id: u32, interval: f32) -> Self {
         Self::Animated {
-            id: id,
+            id,
             interval_per_sec: interval,
             positions: Vec::new(),
+            properties: TileProperties::default(),
         }
     }
 
     pub fn id(&self) -> u32 {
         match self {
-            Self::Standard { id, x: _, y: _ } => *id,
+            Self::Standard {
+                id,
+                x: _,
+                y: _,
+                properties: _,
+            } => *id,
             Self::Animated {
                 id,
                 interval_per_sec: _,
                 positions: _,
+                properties: _,
             } => *id,
         }
     }
+
+    /// All positions of the tile inside of the source image.
+    pub fn positions(&self) -> Vec<TilePosition> {
+        match self {
+            Self::Standard {
+                id: _,
+                x,
+                y,
+                properties: _,
+            } => vec![TilePosition::new(*x, *y)],
+            Self::Animated {
+                id: _,
+                positions,
+                interval_per_sec: _,
+                properties: _,
+            } => positions.clone(),
+        }
+    }
 }
 
 impl TilesetDefinitionBuilder {
@@ -197,18 +360,6 @@ impl TilesetDefinitionBuilder {
     }
 
     pub fn build(self) -> Result<TilesetDefinition, Error> {
-        let dublicated_ids = Self::get_dublicated_ids(&self.tiles);
-
-        if dublicated_ids.len() != 0 {
-            return Err(Error::DublicatedTileIds(dublicated_ids));
-        }
-
-        let dublicated_positions = Self::get_dublicated_positions(&self.tiles);
-
-        if dublicated_positions.len() != 0 {
-            return Err(Error::DublicatedTilePositions(dublicated_positions));
-        }
-
         let name = match self.name {
             Some(n) => n,
             None => Path::new(&self.source.path)
@@ -224,17 +375,21 @@ impl TilesetDefinitionBuilder {
             None => TileSize::new(16, 16),
         };
 
-        Ok(TilesetDefinition {
+        let definition = TilesetDefinition {
             name,
             source: self.source,
             tile_size,
-            tiles: self.tiles.clone(),
-        })
+            tiles: self.tiles,
+        };
+        definition.validate()?;
+
+        Ok(definition)
     }
 
     fn get_dublicated_ids(tiles: &[TileDefinition]) -> Vec<(u32, u32)> {
         tiles
             .iter()
+            .sorted_by_key(|tile| tile.id())
             .group_by(|tile| tile.id())
             .into_iter()
             .map(|(key, group)| (key, group.count() as u32))
@@ -246,44 +401,75 @@ impl TilesetDefinitionBuilder {
         tiles
             .iter()
             .filter(|tile| tile.is_standard())
-            .map(|tile| match tile {
-                TileDefinition::Standard { id, x, y } => Some((*id, *x, *y)),
-                TileDefinition::Animated {
-                    id: _,
-                    positions: _,
-                    interval_per_sec: _,
-                } => None,
+            .filter_map(|tile| match tile {
+                TileDefinition::Standard {
+                    id,
+                    x,
+                    y,
+                    properties: _,
+                } => Some((*id, *x, *y)),
+                TileDefinition::Animated { .. } => None,
             })
-            .flatten()
+            .sorted_by_key(|(_, x, y)| (*x, *y))
             .group_by(|(_, x, y)| (*x, *y))
             .into_iter()
             .map(|((x, y), group)| ((x, y), group.map(|(id, _, _)| id).collect::<Vec<u32>>()))
+            .filter(|(_, ids)| ids.len() >= 2)
             .collect()
     }
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidName => write!(f, "invalid tileset name"),
+            Error::DublicatedTileIds(ids) => write!(f, "dublicated tile ids {:?}", ids),
+            Error::DublicatedTilePositions(positions) => {
+                write!(f, "dublicated tile positions {:?}", positions)
+            }
+            Error::TileOutOfBounds(id) => write!(f, "tile {} is outside of the image", id),
+            Error::InvalidIntervals(ids) => {
+                write!(f, "animated tiles {:?} need a positive interval", ids)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
 impl TileDefinition {
     pub fn is_standard(&self) -> bool {
+        matches!(self, TileDefinition::Standard { .. })
+    }
+
+    pub fn is_animated(&self) -> bool {
+        matches!(self, TileDefinition::Animated { .. })
+    }
+
+    /// The custom properties of the tile, empty if none are defined.
+    pub fn properties(&self) -> &TileProperties {
         match self {
-            TileDefinition::Standard { id: _, x: _, y: _ } => true,
-            TileDefinition::Animated {
-                id: _,
-                positions: _,
-                interval_per_sec: _,
-            } => false,
+            TileDefinition::Standard { properties, .. } => properties,
+            TileDefinition::Animated { properties, .. } => properties,
         }
     }
 
-    pub fn is_animated(&self) -> bool {
+    pub fn properties_mut(&mut self) -> &mut TileProperties {
         match self {
-            TileDefinition::Standard { id: _, x: _, y: _ } => false,
-            TileDefinition::Animated {
-                id: _,
-                positions: _,
-                interval_per_sec: _,
-            } => true,
+            TileDefinition::Standard { properties, .. } => properties,
+            TileDefinition::Animated { properties, .. } => properties,
         }
     }
+
+    pub fn with_property(mut self, key: &str, value: impl Into<TileProperty>) -> Self {
+        self.properties_mut().insert(key, value);
+        self
+    }
+
+    pub fn with_properties(mut self, properties: TileProperties) -> Self {
+        *self.properties_mut() = properties;
+        self
+    }
 }
 
 impl AnimatedTileDefBuilder {
@@ -292,6 +478,7 @@ impl AnimatedTileDefBuilder {
             id,
             intervals: 0.5,
             positions: Vec::new(),
+            properties: TileProperties::default(),
         }
     }
 
@@ -314,13 +501,18 @@ impl AnimatedTileDefBuilder {
         for i in 0..self.positions.len() {
             let index_pos = self.positions[i];
 
-            if index_pos.x == position.x && index_pos.y == index_pos.y {
+            if index_pos.x == position.x && index_pos.y == position.y {
                 self.positions.remove(i);
                 return self;
             }
         }
 
-        return self;
+        self
+    }
+
+    pub fn with_property(mut self, key: &str, value: impl Into<TileProperty>) -> Self {
+        self.properties.insert(key, value);
+        self
     }
 
     pub fn clear_positions(mut self) -> Self {
@@ -333,6 +525,128 @@ impl AnimatedTileDefBuilder {
             id: self.id,
             positions: self.positions,
             interval_per_sec: self.intervals,
+            properties: self.properties,
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+    use std::path::Path;
+
+    use crate::loading::tileset::{
+        AnimatedTileDefBuilder, Error, ImageDimensions, SourceDefinition, TileDefinition,
+        TilePosition, TilesetDefinition, TilesetDefinitionBuilder,
+    };
+
+    fn source() -> SourceDefinition {
+        SourceDefinition::new(Path::new("./tiles.png"), ImageDimensions::new(64, 64))
+    }
+
+    #[test]
+    fn test_dublicated_positions() {
+        let result = TilesetDefinitionBuilder::new(source())
+            .add_tile(TileDefinition::new_standard(0, 1, 0))
+            .add_tile(TileDefinition::new_standard(1, 0, 0))
+            .add_tile(TileDefinition::new_standard(2, 1, 0))
+            .build();
+
+        assert_eq!(
+            Err(Error::DublicatedTilePositions(vec![((1, 0), vec![0, 2])])),
+            result
+        );
+    }
+
+    #[test]
+    fn test_invalid_intervals() {
+        let animated = |id, interval| {
+            AnimatedTileDefBuilder::new(id)
+                .with_interval(interval)
+                .add_position(TilePosition::new(id as usize, 0))
+                .build()
+        };
+        let result = TilesetDefinitionBuilder::new(source())
+            .add_tile(animated(0, 0.0))
+            .add_tile(animated(1, 0.25))
+            .add_tile(animated(2, -1.0))
+            .add_tile(animated(3, f32::NAN))
+            .build();
+
+        assert_eq!(Err(Error::InvalidIntervals(vec![0, 2, 3])), result);
+    }
+
+    #[test]
+    fn test_texture_atlas() {
+        let tileset = TilesetDefinitionBuilder::new(source())
+            .with_tile_size(32, 32)
+            .add_tile(
+                AnimatedTileDefBuilder::new(0)
+                    .add_position(TilePosition::new(0, 0))
+                    .add_position(TilePosition::new(1, 0))
+                    .build(),
+            )
+            .add_tile(TileDefinition::new_standard(1, 1, 1))
+            .build()
+            .unwrap();
+
+        let atlas = tileset.texture_atlas(Handle::default());
+
+        assert_eq!(3, atlas.len());
+        assert_eq!(Some(0), tileset.atlas_index(0));
+        assert_eq!(Some(2), tileset.atlas_index(1));
+        assert_eq!(None, tileset.atlas_index(2));
+        assert_eq!(Some(vec![0, 1]), tileset.atlas_indices(0));
+        assert_eq!(Rect::new(32.0, 32.0, 64.0, 64.0), atlas.textures[2]);
+    }
+
+    #[test]
+    fn test_deserialize_ron() {
+        let tileset = ron::from_str::<TilesetDefinition>(
+            r#"(
+                name: "tiles",
+                tile_size: (width: 32, height: 32),
+                source: (path: "./tiles.png", dimensions: (width: 64, height: 64)),
+                tiles: [Standard(id: 0, x: 0, y: 0)],
+            )"#,
+        )
+        .unwrap();
+
+        assert_eq!("tiles", tileset.name());
+        assert_eq!(
+            Some(&TileDefinition::new_standard(0, 0, 0)),
+            tileset.tile(0)
+        );
+    }
+
+    #[test]
+    fn test_deserialize_properties() {
+        let tileset = ron::from_str::<TilesetDefinition>(
+            r#"(
+                name: "tiles",
+                tile_size: (width: 32, height: 32),
+                source: (path: "./tiles.png", dimensions: (width: 64, height: 64)),
+                tiles: [
+                    Standard(id: 0, x: 0, y: 0, properties: {
+                        "water": true,
+                        "cost": 3,
+                        "speed": 0.5,
+                        "sound": "splash",
+                    }),
+                    Animated(id: 1, positions: [(x: 1, y: 0)], interval_per_sec: 0.5),
+                ],
+            )"#,
+        )
+        .unwrap();
+
+        let properties = tileset.tile(0).unwrap().properties();
+        assert_eq!(Some(true), properties.get_bool("water"));
+        assert_eq!(Some(3), properties.get_int("cost"));
+        assert_eq!(Some(0.5), properties.get_float("speed"));
+        assert_eq!(Some("splash"), properties.get_str("sound"));
+        assert!(tileset.tile(1).unwrap().properties().is_empty());
+
+        let serialized = ron::to_string(&tileset).unwrap();
+        assert_eq!(tileset, ron::from_str(&serialized).unwrap());
+    }
+}
@@ -1,28 +1,23 @@
 use approx::ulps_eq;
 use bevy::prelude::*;
 
-pub fn grid_to_world(grid_pos: Vec3, tile_width: f32, tile_height: f32) -> Vec3 {
-    let tile_width_half = tile_width / 2.0;
-    let tile_height_half = tile_height / 2.0;
+use crate::projection::{GridLayout, GridProjection, LayerElevation};
 
-    let world_x = (grid_pos.x - grid_pos.y) * tile_width_half;
-    let world_y = (grid_pos.x + grid_pos.y) * tile_height_half;
+/// Places a grid position with the default `GridProjection`, a diamond grid with the default layer elevation.
+pub fn grid_to_world(grid_pos: Vec3, tile_width: f32, tile_height: f32) -> Vec3 {
+    GridProjection::default().grid_to_world(grid_pos, tile_width, tile_height)
+}
 
-    Vec3::new(
-        world_x,
-        world_y + (tile_height * (grid_pos.z - 1.0).clamp(0.0, f32::MAX)),
-        grid_pos.z,
-    )
+/// The vertical offset of a layer in world space. The first two layers share the same height.
+pub fn layer_height(layer: f32, tile_height: f32) -> f32 {
+    LayerElevation::default().elevation(layer) * tile_height
 }
 
+/// The position in a diamond grid of a world position on the ground, keeping its z coordinate.
 pub fn world_to_grid(world_pos: Vec3, tile_width: f32, tile_height: f32) -> Vec3 {
-    let tile_width_half = tile_width / 2.0;
-    let tile_height_half = tile_height / 2.0;
-
-    let grid_x = (world_pos.x / tile_width_half + world_pos.y / tile_height_half) / 2.0;
-    let grid_y = (world_pos.y / tile_height_half - (world_pos.x / tile_width_half)) / 2.0;
-
-    Vec3::new(grid_x, grid_y, world_pos.z)
+    GridLayout::Diamond
+        .to_grid(world_pos.truncate(), tile_width, tile_height)
+        .extend(world_pos.z)
 }
 
 pub fn rotate_vector(v: Vec3, rotation: f32) -> Vec3 {
@@ -0,0 +1,537 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    elevation::{surface_elevation, TileElevation},
+    grid::{Grid, GridMember, GridOffset, GridPosition, GridPositionF, GridSize, TileSize},
+    projection::GridProjection,
+    tile::TileMarker,
+    DynamicObject, WorldScale,
+};
+
+/// A step of a single tile along one of the grid axes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GridDirection {
+    /// Towards negative y.
+    North,
+    /// Towards positive x.
+    East,
+    /// Towards positive y.
+    South,
+    /// Towards negative x.
+    West,
+}
+
+/// Moves a dynamic object through the grid, tile by tile along a list of waypoints.
+#[derive(Component, Debug, Clone)]
+pub struct GridMover {
+    /// Speed in tiles per second.
+    pub speed: f32,
+    waypoints: VecDeque<GridPosition>,
+    position: Option<GridPositionF>,
+}
+
+/// Sent when a dynamic object starts moving.
+#[derive(Event, Debug, Copy, Clone, PartialEq)]
+pub struct MovementStarted {
+    pub entity: Entity,
+    pub from: GridPosition,
+    pub to: GridPosition,
+}
+
+/// Sent when a dynamic object reached its last waypoint.
+#[derive(Event, Debug, Copy, Clone, PartialEq)]
+pub struct MovementFinished {
+    pub entity: Entity,
+    pub position: GridPosition,
+}
+
+impl GridDirection {
+    /// The neighbour of the position in this direction, if its coordinates fit into a grid position.
+    pub fn step(&self, position: GridPosition) -> Option<GridPosition> {
+        let (x, y) = match self {
+            GridDirection::North => (Some(position.x), position.y.checked_sub(1)),
+            GridDirection::East => (position.x.checked_add(1), Some(position.y)),
+            GridDirection::South => (Some(position.x), position.y.checked_add(1)),
+            GridDirection::West => (position.x.checked_sub(1), Some(position.y)),
+        };
+
+        Some(GridPosition::new(x?, y?, position.layer))
+    }
+
+    /// The direction after rotating the grid clockwise, like `GridPosition::rotate_c_within`.
+    pub fn rotated_c(self) -> Self {
+        match self {
+            GridDirection::North => GridDirection::West,
+            GridDirection::East => GridDirection::North,
+            GridDirection::South => GridDirection::East,
+            GridDirection::West => GridDirection::South,
+        }
+    }
+
+    /// The direction after rotating the grid counterclockwise.
+    pub fn rotated_cc(self) -> Self {
+        match self {
+            GridDirection::North => GridDirection::East,
+            GridDirection::East => GridDirection::South,
+            GridDirection::South => GridDirection::West,
+            GridDirection::West => GridDirection::North,
+        }
+    }
+}
+
+impl GridMover {
+    pub fn new(speed: f32) -> Self {
+        Self {
+            speed,
+            waypoints: VecDeque::new(),
+            position: None,
+        }
+    }
+
+    /// Moves the object to the target, replacing all previous waypoints.
+    pub fn move_to(&mut self, target: GridPosition) {
+        self.waypoints.clear();
+        self.waypoints.push_back(target);
+    }
+
+    /// Moves the object along the positions of the path, replacing all previous waypoints.
+    pub fn follow(&mut self, path: impl IntoIterator<Item = GridPosition>) {
+        self.waypoints.clear();
+        self.waypoints.extend(path);
+    }
+
+    /// Moves the object one tile into the direction, starting from the last waypoint or the current position.
+    pub fn move_direction(&mut self, current: GridPosition, direction: GridDirection) {
+        let from = self.waypoints.back().copied().unwrap_or(current);
+
+        if let Some(target) = direction.step(from) {
+            self.waypoints.push_back(target);
+        }
+    }
+
+    /// Stops at the current position of the object.
+    pub fn stop(&mut self) {
+        self.waypoints.clear();
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.position.is_some()
+    }
+
+    /// The position of a moving object, which may be between two tiles.
+    pub fn position(&self) -> Option<GridPositionF> {
+        self.position
+    }
+
+    pub fn waypoints(&self) -> impl Iterator<Item = &GridPosition> {
+        self.waypoints.iter()
+    }
+
+    /// Rotates the waypoints and current position together with the grid of the object.
+    pub(crate) fn rotate(&mut self, clockwise: bool, size: GridSize) {
+        for waypoint in self.waypoints.iter_mut() {
+            *waypoint = match clockwise {
+                true => waypoint.rotate_c_within(size),
+                false => waypoint.rotate_cc_within(size),
+            };
+        }
+
+        if let Some(position) = self.position.as_mut() {
+            *position = match clockwise {
+                true => position.rotate_c_within(size),
+                false => position.rotate_cc_within(size),
+            };
+        }
+    }
+
+    /// Advances the position by the given distance in tiles.
+    /// Returns the new position and if the last waypoint got reached.
+    fn advance(&mut self, start: GridPositionF, mut distance: f32) -> (GridPositionF, bool) {
+        let mut position = start;
+
+        while let Some(waypoint) = self.waypoints.front() {
+            let target = GridPositionF::from(*waypoint);
+            let remaining = position.distance(target);
+
+            if remaining > distance {
+                position = position.lerp(target, distance / remaining);
+                return (position, false);
+            }
+
+            distance -= remaining;
+            position = target;
+            self.waypoints.pop_front();
+        }
+
+        (position, true)
+    }
+}
+
+type MoverQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut GridMover,
+        &'static mut GridPosition,
+        &'static mut Transform,
+        Option<&'static GridOffset>,
+        Option<&'static mut TileElevation>,
+    ),
+    With<DynamicObject>,
+>;
+
+type ElevatedTileQuery<'w, 's> = Query<
+    'w,
+    's,
+    (Entity, &'static GridPosition, &'static TileElevation),
+    (With<TileMarker>, Without<DynamicObject>),
+>;
+
+/// Moves dynamic objects towards their waypoints and updates their grid position whenever they enter a new tile.
+/// Objects use the grid they belong to, or the only grid if they belong to none.
+/// Moving objects stand on the surface of elevated tiles and ramps of their layer, their `TileElevation` follows it.
+#[allow(clippy::too_many_arguments)]
+pub fn move_grid_objects(
+    mut commands: Commands,
+    time: Res<Time>,
+    grids: Query<(Entity, &TileSize, &WorldScale, &GridProjection), With<Grid>>,
+    members: Query<&GridMember>,
+    parents: Query<&Parent>,
+    elevated_tiles: ElevatedTileQuery,
+    mut movers: MoverQuery,
+    mut started_events: EventWriter<MovementStarted>,
+    mut finished_events: EventWriter<MovementFinished>,
+) {
+    let mut grid_elevations: Option<HashMap<Entity, HashMap<GridPosition, TileElevation>>> = None;
+
+    for (entity, mut mover, mut grid_position, mut transform, offset, object_elevation) in
+        movers.iter_mut()
+    {
+        if mover.position.is_none() {
+            let Some(next) = mover.waypoints.front().copied() else {
+                continue;
+            };
+
+            mover.position = Some(GridPositionF::from(*grid_position));
+            started_events.send(MovementStarted {
+                entity,
+                from: *grid_position,
+                to: next,
+            });
+        }
+
+        let grid = GridMember::find_grid(entity, &members, &parents, |e| grids.contains(e))
+            .and_then(|grid| grids.get(grid).ok())
+            .or_else(|| grids.get_single().ok());
+        let Some((grid_entity, tilesize, scale, projection)) = grid else {
+            continue;
+        };
+
+        let start = mover
+            .position
+            .unwrap_or(GridPositionF::from(*grid_position));
+        let distance = mover.speed * time.delta_seconds();
+        let (position, finished) = mover.advance(start, distance);
+
+        if let Ok(cell) = position.round() {
+            if cell != *grid_position {
+                *grid_position = cell;
+            }
+        }
+
+        let elevations = grid_elevations.get_or_insert_with(|| {
+            let mut elevations = HashMap::<Entity, HashMap<GridPosition, TileElevation>>::new();
+            for (tile, tile_position, tile_elevation) in elevated_tiles.iter() {
+                if let Some(grid) =
+                    GridMember::find_grid(tile, &members, &parents, |e| grids.contains(e))
+                {
+                    elevations
+                        .entry(grid)
+                        .or_default()
+                        .insert(*tile_position, *tile_elevation);
+                }
+            }
+            elevations
+        });
+        let elevation = elevations
+            .get(&grid_entity)
+            .map(|tiles| surface_elevation(position, tiles))
+            .unwrap_or_default();
+
+        match object_elevation {
+            Some(mut object_elevation) if object_elevation.height != elevation => {
+                object_elevation.height = elevation;
+            }
+            Some(_) => {}
+            None if elevation != 0.0 => {
+                commands.entity(entity).insert(TileElevation::new(elevation));
+            }
+            None => {}
+        }
+
+        let world_pos = projection.elevated_to_world(
+            Vec3::from(position),
+            elevation,
+            tilesize.width() * scale.0,
+            tilesize.height() * scale.0,
+        );
+        let offset = offset.map(|o| o.0).unwrap_or_default();
+
+        transform.translation.x = world_pos.x + offset.x;
+        transform.translation.y = world_pos.y + offset.y;
+
+        if finished {
+            mover.position = None;
+            finished_events.send(MovementFinished {
+                entity,
+                position: *grid_position,
+            });
+        } else {
+            mover.position = Some(position);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{prelude::*, utils::Instant};
+
+    use crate::{
+        elevation::TileElevation,
+        grid::{Grid, GridBundle, GridMember, GridPosition, GridSize, TileSize},
+        math::grid_to_world,
+        movement::{
+            move_grid_objects, GridDirection, GridMover, MovementFinished, MovementStarted,
+        },
+        tile::TileMarker,
+        DynamicObject, WorldScale,
+    };
+
+    fn app() -> (App, Instant) {
+        let mut app = App::new();
+        app.add_event::<MovementStarted>()
+            .add_event::<MovementFinished>()
+            .init_resource::<Time>()
+            .add_systems(Update, move_grid_objects);
+
+        app.world.spawn((
+            GridBundle::new(Grid {
+                tilemap_handle: Handle::default(),
+                texture_atlas_handle: None,
+            }),
+            TileSize::new(32.0, 16.0),
+            WorldScale(2.0),
+        ));
+
+        let start = Instant::now();
+        app.world.resource_mut::<Time>().update_with_instant(start);
+        (app, start)
+    }
+
+    fn update_at(app: &mut App, start: Instant, seconds: f32) {
+        app.world
+            .resource_mut::<Time>()
+            .update_with_instant(start + Duration::from_secs_f32(seconds));
+        app.update();
+    }
+
+    fn event_count<E: Event>(app: &App) -> usize {
+        let events = app.world.resource::<Events<E>>();
+        events.get_reader().iter(events).count()
+    }
+
+    #[test]
+    fn test_direction_step() {
+        let position = GridPosition::new(0, 2, 1);
+
+        assert_eq!(
+            Some(GridPosition::new(0, 1, 1)),
+            GridDirection::North.step(position)
+        );
+        assert_eq!(
+            Some(GridPosition::new(1, 2, 1)),
+            GridDirection::East.step(position)
+        );
+        assert_eq!(
+            Some(GridPosition::new(-1, 2, 1)),
+            GridDirection::West.step(position)
+        );
+        assert_eq!(
+            None,
+            GridDirection::North.step(GridPosition::new(0, i32::MIN, 1))
+        );
+    }
+
+    #[test]
+    fn test_rotate_direction() {
+        let position = GridPosition::new(1, 2, 0);
+        let size = GridSize::new(4, 3);
+
+        for direction in [
+            GridDirection::North,
+            GridDirection::East,
+            GridDirection::South,
+            GridDirection::West,
+        ] {
+            assert_eq!(
+                direction
+                    .step(position)
+                    .map(|step| step.rotate_c_within(size)),
+                direction.rotated_c().step(position.rotate_c_within(size))
+            );
+            assert_eq!(direction, direction.rotated_c().rotated_cc());
+        }
+    }
+
+    #[test]
+    fn test_move_to_target() {
+        let (mut app, start) = app();
+        let mut mover = GridMover::new(2.0);
+        mover.move_to(GridPosition::new(3, 0, 0));
+
+        let object = app
+            .world
+            .spawn((
+                DynamicObject,
+                GridPosition::new(0, 0, 0),
+                Transform::default(),
+                mover,
+            ))
+            .id();
+
+        update_at(&mut app, start, 0.0);
+        assert_eq!(1, event_count::<MovementStarted>(&app));
+
+        update_at(&mut app, start, 0.5);
+        assert_eq!(
+            GridPosition::new(1, 0, 0),
+            *app.world.get::<GridPosition>(object).unwrap()
+        );
+
+        update_at(&mut app, start, 0.8);
+        assert_eq!(
+            GridPosition::new(2, 0, 0),
+            *app.world.get::<GridPosition>(object).unwrap()
+        );
+        assert_eq!(0, event_count::<MovementFinished>(&app));
+
+        update_at(&mut app, start, 2.0);
+        assert_eq!(
+            GridPosition::new(3, 0, 0),
+            *app.world.get::<GridPosition>(object).unwrap()
+        );
+        assert_eq!(
+            grid_to_world(Vec3::new(3.0, 0.0, 0.0), 64.0, 32.0).truncate(),
+            app.world
+                .get::<Transform>(object)
+                .unwrap()
+                .translation
+                .truncate()
+        );
+        assert_eq!(1, event_count::<MovementFinished>(&app));
+        assert!(!app.world.get::<GridMover>(object).unwrap().is_moving());
+    }
+
+    #[test]
+    fn test_follow_path() {
+        let (mut app, start) = app();
+        let mut mover = GridMover::new(1.0);
+        mover.follow([GridPosition::new(1, 0, 0), GridPosition::new(1, 1, 0)]);
+
+        let object = app
+            .world
+            .spawn((
+                DynamicObject,
+                GridPosition::new(0, 0, 0),
+                Transform::default(),
+                mover,
+            ))
+            .id();
+
+        update_at(&mut app, start, 0.0);
+        update_at(&mut app, start, 1.5);
+
+        assert_eq!(
+            grid_to_world(Vec3::new(1.0, 0.5, 0.0), 64.0, 32.0).truncate(),
+            app.world
+                .get::<Transform>(object)
+                .unwrap()
+                .translation
+                .truncate()
+        );
+    }
+
+    #[test]
+    fn test_walk_up_ramp() {
+        let (mut app, start) = app();
+        let grid = app
+            .world
+            .query_filtered::<Entity, With<Grid>>()
+            .single(&app.world);
+        app.world.spawn((
+            TileMarker,
+            GridPosition::new(1, 0, 0),
+            TileElevation::new(0.0).with_ramp(GridDirection::East, 1.0),
+            GridMember(grid),
+        ));
+        app.world.spawn((
+            TileMarker,
+            GridPosition::new(2, 0, 0),
+            TileElevation::new(1.0),
+            GridMember(grid),
+        ));
+
+        let mut mover = GridMover::new(1.0);
+        mover.move_to(GridPosition::new(2, 0, 0));
+        let object = app
+            .world
+            .spawn((
+                DynamicObject,
+                GridPosition::new(0, 0, 0),
+                Transform::default(),
+                mover,
+            ))
+            .id();
+        let y = |app: &App| app.world.get::<Transform>(object).unwrap().translation.y;
+
+        update_at(&mut app, start, 0.0);
+        assert_eq!(0.0, y(&app));
+
+        update_at(&mut app, start, 1.25);
+        assert_eq!(
+            grid_to_world(Vec3::new(1.25, 0.0, 0.0), 64.0, 32.0).y + 0.75 * 32.0,
+            y(&app)
+        );
+        assert_eq!(
+            Some(&TileElevation::new(0.75)),
+            app.world.get::<TileElevation>(object)
+        );
+
+        update_at(&mut app, start, 3.0);
+        assert_eq!(
+            grid_to_world(Vec3::new(2.0, 0.0, 0.0), 64.0, 32.0).y + 32.0,
+            y(&app)
+        );
+        assert_eq!(
+            Some(&TileElevation::new(1.0)),
+            app.world.get::<TileElevation>(object)
+        );
+    }
+
+    #[test]
+    fn test_rotate_waypoints() {
+        let mut mover = GridMover::new(1.0);
+        mover.move_to(GridPosition::new(3, 1, 0));
+        mover.rotate(true, GridSize::new(4, 2));
+
+        assert_eq!(
+            vec![&GridPosition::new(1, 0, 0)],
+            mover.waypoints().collect::<Vec<_>>()
+        );
+    }
+}
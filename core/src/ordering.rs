@@ -1,47 +1,567 @@
 use bevy::prelude::*;
 
-use crate::{rotate::GridRotationEvent, StaticObject, DynamicObject};
+use crate::{
+    elevation::TileElevation,
+    grid::{GridMember, GridPosition},
+    movement::GridMover,
+    projection::GridProjection,
+    rotate::GridRotationFinished,
+    DynamicObject, StaticObject,
+};
 
-/// Offset for layering dynamic objects and static tiles.
-#[derive(Component, Debug, Copy, Clone)]
+/// Offset added to the z order of an object, to move it in front of or behind objects at the same depth.
+#[derive(Component, Default, Debug, Copy, Clone)]
 pub struct ZOffset(pub f32);
 
-/// Sorts the static tiles basedon their z offeset and their y position substracted from their z.
+/// Cells covered by an object in the current view, starting at its grid position and extending
+/// towards the back of the grid. Multi tile sprites are drawn from their front cell, so objects
+/// with a footprint are ordered by the center of it.
+#[derive(Component, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Footprint {
+    pub width: usize,
+    pub height: usize,
+}
+
+/// The place of an object in the drawing order of a grid.
+#[derive(Default, Debug, Copy, Clone, PartialEq)]
+pub struct DepthKey {
+    /// The depth row of the grid projection, which is the sum of the x and y grid coordinates in a diamond grid.
+    /// Objects with a higher row are further away from the viewer.
+    pub row: f32,
+    /// The layer of the object, raised by the elevation of the object in tile heights.
+    pub layer: f32,
+}
+
+/// Decides the z translation of objects from their place in a grid.
+pub trait OrderingStrategy: Send + Sync + 'static {
+    /// The z translation of an object with the given depth key, before its `ZOffset` is added.
+    fn z_order(&self, key: DepthKey) -> f32;
+}
+
+/// Orders objects by their row first and by their layer inside of a row.
+/// Every row is `row_depth` behind the previous one. Layers start `layer_depth` apart and get closer the higher
+/// the layer key is, so any number of layers, including the elevation of objects, stays inside of half a row
+/// around the layer 0 tile and never overlaps the neighbouring rows.
+/// The z order also has to stay inside of the visible range of the camera, which ends at 0 for the default 2D camera,
+/// so the default ordering works for up to 9000 rows. Use `for_grid` to derive the depths for bigger grids.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GridDepthOrdering {
+    /// The z order of the layer 0 tile at the grid position (0, 0).
+    pub base: f32,
+    pub row_depth: f32,
+    pub layer_depth: f32,
+}
+
+/// The ordering strategy used for all grids. Defaults to `GridDepthOrdering`.
+#[derive(Resource)]
+pub struct DepthOrdering(Box<dyn OrderingStrategy>);
+
+type NewStaticTileQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Transform,
+        &'static GridPosition,
+        &'static ZOffset,
+        Option<&'static Footprint>,
+        Option<&'static TileElevation>,
+    ),
+    (Added<StaticObject>, With<StaticObject>),
+>;
+
+type StaticObjectQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Transform,
+        &'static GridPosition,
+        &'static ZOffset,
+        Option<&'static Footprint>,
+        Option<&'static TileElevation>,
+    ),
+    With<StaticObject>,
+>;
+
+type DynamicObjectQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Transform,
+        &'static GridPosition,
+        &'static ZOffset,
+        Option<&'static Footprint>,
+        Option<&'static TileElevation>,
+        Option<&'static GridMover>,
+    ),
+    With<DynamicObject>,
+>;
+
+/// Sorts new static objects by their grid position. Lifting a tile does not change its order.
 pub fn order_static_tile_z(
-    mut static_tiles: Query<(&mut Transform, &ZOffset), (Added<StaticObject>, With<StaticObject>)>,
+    ordering: Res<DepthOrdering>,
+    grids: Query<&GridProjection>,
+    members: Query<&GridMember>,
+    parents: Query<&Parent>,
+    mut static_tiles: NewStaticTileQuery,
 ) {
-    for (mut object_transform, object_offset) in static_tiles.iter_mut() {
-        object_transform.translation.z =
-            calculate_z_order(object_transform.translation, object_offset);
+    let default_projection = GridProjection::default();
+
+    for (entity, mut object_transform, position, object_offset, footprint, elevation) in
+        static_tiles.iter_mut()
+    {
+        let projection =
+            object_projection(entity, &grids, &members, &parents).unwrap_or(&default_projection);
+
+        object_transform.translation.z = ordering.object_z(
+            projection,
+            Vec3::from(*position),
+            footprint,
+            elevation,
+            object_offset,
+        );
     }
 }
 
 pub fn reorder_on_rotation(
-    mut rotation_event: EventReader<GridRotationEvent>,
-    mut static_tiles: Query<(&mut Transform, &ZOffset), With<StaticObject>>,
+    mut rotation_event: EventReader<GridRotationFinished>,
+    ordering: Res<DepthOrdering>,
+    grids: Query<&GridProjection>,
+    members: Query<&GridMember>,
+    parents: Query<&Parent>,
+    mut static_tiles: StaticObjectQuery,
 ) {
+    let default_projection = GridProjection::default();
+
     for _ in rotation_event.iter() {
-        for (mut object_transform, object_offset) in static_tiles.iter_mut() {
+        for (entity, mut object_transform, position, object_offset, footprint, elevation) in
+            static_tiles.iter_mut()
+        {
+            let projection = object_projection(entity, &grids, &members, &parents)
+                .unwrap_or(&default_projection);
+
             debug!("Old Z ordering: {}", object_transform.translation.z);
 
-            object_transform.translation.z =
-                calculate_z_order(object_transform.translation, object_offset);
+            object_transform.translation.z = ordering.object_z(
+                projection,
+                Vec3::from(*position),
+                footprint,
+                elevation,
+                object_offset,
+            );
 
             debug!("New Z ordering: {}", object_transform.translation.z);
         }
     }
 }
 
-/// Sorts the entities z position to change what on top via a zoffset to do the basic layer and then substracting y from z.
+/// Sorts dynamic objects by their grid position, or the position between two tiles while they are moving.
 pub fn update_dynamic_object_z(
-    mut dynamic_objects: Query<(&mut Transform, &ZOffset), With<DynamicObject>>,
+    ordering: Res<DepthOrdering>,
+    grids: Query<&GridProjection>,
+    members: Query<&GridMember>,
+    parents: Query<&Parent>,
+    mut dynamic_objects: DynamicObjectQuery,
 ) {
-    for (mut object_transform, object_offset) in dynamic_objects.iter_mut() {
+    let default_projection = GridProjection::default();
+
+    for (entity, mut object_transform, position, object_offset, footprint, elevation, mover) in
+        dynamic_objects.iter_mut()
+    {
+        let projection =
+            object_projection(entity, &grids, &members, &parents).unwrap_or(&default_projection);
+        let position = mover
+            .and_then(|mover| mover.position())
+            .map(Vec3::from)
+            .unwrap_or(Vec3::from(*position));
+
         object_transform.translation.z =
-            calculate_z_order(object_transform.translation, object_offset);
+            ordering.object_z(projection, position, footprint, elevation, object_offset);
+    }
+}
+
+/// The projection of the grid an object belongs to, or of the only grid if it belongs to none.
+fn object_projection<'a>(
+    entity: Entity,
+    grids: &'a Query<&GridProjection>,
+    members: &Query<&GridMember>,
+    parents: &Query<&Parent>,
+) -> Option<&'a GridProjection> {
+    GridMember::find_grid(entity, members, parents, |e| grids.contains(e))
+        .and_then(|grid| grids.get(grid).ok())
+        .or_else(|| grids.get_single().ok())
+}
+
+impl Footprint {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self { width, height }
+    }
+}
+
+impl Default for Footprint {
+    fn default() -> Self {
+        Self::new(1, 1)
+    }
+}
+
+impl DepthKey {
+    /// The depth key of an object at the given position in grid space of a diamond grid, where the z axis is the layer.
+    pub fn new(position: Vec3, footprint: Option<&Footprint>) -> Self {
+        Self::projected(&GridProjection::default(), position, footprint)
+    }
+
+    /// The depth key of an object at the given position in grid space of a grid with the given projection.
+    pub fn projected(
+        projection: &GridProjection,
+        position: Vec3,
+        footprint: Option<&Footprint>,
+    ) -> Self {
+        let footprint = footprint.copied().unwrap_or_default();
+        let center = Vec3::new(
+            (footprint.width.max(1) - 1) as f32 / 2.0,
+            (footprint.height.max(1) - 1) as f32 / 2.0,
+            0.0,
+        );
+
+        Self {
+            row: projection.depth_row(position + center),
+            layer: position.z,
+        }
+    }
+}
+
+impl GridDepthOrdering {
+    pub fn new(base: f32, row_depth: f32, layer_depth: f32) -> Self {
+        Self {
+            base,
+            row_depth,
+            layer_depth,
+        }
+    }
+
+    /// Spreads the given number of depth rows over the z range between the base and 0, and splits every row
+    /// into the given number of layer keys. Elevated objects need a layer key for every tile height.
+    pub fn for_grid(rows: usize, layers: usize) -> Self {
+        let base = 900.0;
+        let row_depth = base / rows.max(1) as f32;
+
+        Self::new(base, row_depth, row_depth / layers.max(1) as f32)
     }
 }
 
-fn calculate_z_order(orthogonal_position: Vec3, z_offset: &ZOffset) -> f32 {
-    return z_offset.0 - orthogonal_position.y / 100.0;
+impl Default for GridDepthOrdering {
+    fn default() -> Self {
+        Self::new(900.0, 0.1, 0.01)
+    }
+}
+
+impl OrderingStrategy for GridDepthOrdering {
+    fn z_order(&self, key: DepthKey) -> f32 {
+        // Maps the layer offset into half a row to either side, keeping the order of the layers.
+        let spread = self.row_depth / 2.0;
+        let layer = key.layer * self.layer_depth / spread;
+
+        self.base - key.row * self.row_depth + spread * layer / (1.0 + layer.abs())
+    }
+}
+
+impl DepthOrdering {
+    pub fn new(strategy: impl OrderingStrategy) -> Self {
+        Self(Box::new(strategy))
+    }
+
+    pub fn z_order(&self, key: DepthKey) -> f32 {
+        self.0.z_order(key)
+    }
+
+    /// The z translation of an object at the given position in grid space of a grid with the given projection.
+    /// Elevated objects are ordered as if they were on a higher layer, ramps by the elevation of their center.
+    pub fn object_z(
+        &self,
+        projection: &GridProjection,
+        position: Vec3,
+        footprint: Option<&Footprint>,
+        elevation: Option<&TileElevation>,
+        z_offset: &ZOffset,
+    ) -> f32 {
+        let mut key = DepthKey::projected(projection, position, footprint);
+        key.layer += elevation.map(TileElevation::center).unwrap_or_default();
+
+        self.z_order(key) + z_offset.0
+    }
+}
+
+impl Default for DepthOrdering {
+    fn default() -> Self {
+        Self::new(GridDepthOrdering::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{prelude::*, utils::Instant};
+
+    use crate::{
+        elevation::TileElevation,
+        grid::{Grid, GridBundle, GridPosition, GridPositionF, TileSize},
+        movement::{
+            move_grid_objects, GridDirection, GridMover, MovementFinished, MovementStarted,
+        },
+        ordering::{
+            order_static_tile_z, update_dynamic_object_z, DepthKey, DepthOrdering, Footprint,
+            GridDepthOrdering, OrderingStrategy, ZOffset,
+        },
+        DynamicObject, StaticObject, WorldScale,
+    };
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.init_resource::<DepthOrdering>()
+            .add_systems(Update, (order_static_tile_z, update_dynamic_object_z));
+        app
+    }
+
+    fn z(app: &App, entity: Entity) -> f32 {
+        app.world.get::<Transform>(entity).unwrap().translation.z
+    }
+
+    #[test]
+    fn test_large_map_order() {
+        let ordering = DepthOrdering::default();
+        let size = 512;
+
+        // Drawn from the back to the front, every tile has to be in front of the previous one.
+        let mut last = f32::MIN;
+        for row in (0..(size * 2 - 1)).rev() {
+            for layer in 0..4 {
+                let z = ordering.z_order(DepthKey {
+                    row: row as f32,
+                    layer: layer as f32,
+                });
+
+                assert!(z > last, "Row {} layer {} is misordered.", row, layer);
+                assert!(z > 0.0 && z < 1000.0);
+                last = z;
+            }
+        }
+    }
+
+    #[test]
+    fn test_derived_depths() {
+        let ordering = GridDepthOrdering::for_grid(20000, 16);
+
+        let mut last = f32::MIN;
+        for row in (0..20000).rev() {
+            for layer in 0..16 {
+                let z = ordering.z_order(DepthKey {
+                    row: row as f32,
+                    layer: layer as f32,
+                });
+
+                assert!(z > last, "Row {} layer {} is misordered.", row, layer);
+                assert!(z > 0.0 && z < 1000.0);
+                last = z;
+            }
+        }
+    }
+
+    #[test]
+    fn test_many_layers_order() {
+        let ordering = DepthOrdering::default();
+
+        // Twelve layers, with objects standing on tiles up to two tile heights high on the top layer.
+        let mut last = f32::MIN;
+        for row in (0..64).rev() {
+            for layer in 0..14 {
+                let z = ordering.z_order(DepthKey {
+                    row: row as f32,
+                    layer: layer as f32,
+                });
+
+                assert!(z > last, "Row {} layer {} is misordered.", row, layer);
+                last = z;
+            }
+        }
+    }
+
+    #[test]
+    fn test_footprint_center() {
+        assert_eq!(
+            DepthKey {
+                row: 11.0,
+                layer: 1.0
+            },
+            DepthKey::new(Vec3::new(4.0, 5.0, 1.0), Some(&Footprint::new(3, 3)))
+        );
+        assert_eq!(
+            DepthKey {
+                row: 9.5,
+                layer: 1.0
+            },
+            DepthKey::new(Vec3::new(4.0, 5.0, 1.0), Some(&Footprint::new(2, 1)))
+        );
+        assert_eq!(
+            DepthKey::new(Vec3::new(4.0, 5.0, 1.0), None),
+            DepthKey::new(Vec3::new(4.0, 5.0, 1.0), Some(&Footprint::default()))
+        );
+    }
+
+    #[test]
+    fn test_multi_tile_object_order() {
+        let mut app = app();
+
+        // A 3x3 building covering the cells (4..=6, 4..=6).
+        let building = app
+            .world
+            .spawn((
+                Transform::default(),
+                GridPosition::new(4, 4, 1),
+                ZOffset::default(),
+                Footprint::new(3, 3),
+                StaticObject,
+            ))
+            .id();
+        let mut spawn_object = |x, y| {
+            app.world
+                .spawn((
+                    Transform::default(),
+                    GridPosition::new(x, y, 1),
+                    ZOffset::default(),
+                    DynamicObject,
+                ))
+                .id()
+        };
+        let in_front = [spawn_object(3, 6), spawn_object(6, 3), spawn_object(4, 3)];
+        let behind = [spawn_object(7, 4), spawn_object(4, 7), spawn_object(7, 7)];
+
+        app.update();
+
+        for object in in_front {
+            assert!(z(&app, object) > z(&app, building));
+        }
+        for object in behind {
+            assert!(z(&app, object) < z(&app, building));
+        }
+    }
+
+    #[test]
+    fn test_elevated_tile_order() {
+        let mut app = app();
+        let mut spawn_tile = |x, y, layer, elevation| {
+            app.world
+                .spawn((
+                    Transform::default(),
+                    GridPosition::new(x, y, layer),
+                    ZOffset::default(),
+                    elevation,
+                    StaticObject,
+                ))
+                .id()
+        };
+        let ground = spawn_tile(2, 2, 0, TileElevation::default());
+        let elevated = spawn_tile(2, 2, 0, TileElevation::new(1.0));
+        let ramp = spawn_tile(
+            2,
+            2,
+            0,
+            TileElevation::new(0.0).with_ramp(GridDirection::East, 1.0),
+        );
+        let upper_layer = spawn_tile(2, 2, 1, TileElevation::default());
+        let next_row = spawn_tile(2, 1, 0, TileElevation::default());
+
+        app.update();
+
+        assert!(z(&app, ground) < z(&app, ramp));
+        assert!(z(&app, ramp) < z(&app, elevated));
+        assert_eq!(z(&app, elevated), z(&app, upper_layer));
+        assert!(z(&app, elevated) < z(&app, next_row));
+    }
+
+    #[test]
+    fn test_walking_behind_tall_tile() {
+        let mut app = app();
+        app.add_event::<MovementStarted>()
+            .add_event::<MovementFinished>()
+            .init_resource::<Time>()
+            .add_systems(Update, move_grid_objects.before(update_dynamic_object_z));
+        app.world.spawn((
+            GridBundle::new(Grid {
+                tilemap_handle: Handle::default(),
+                texture_atlas_handle: None,
+            }),
+            TileSize::new(32.0, 16.0),
+            WorldScale(1.0),
+        ));
+
+        // A wall on the ground layer whose sprite reaches up to the next layers.
+        let mut spawn_tile = |x, y| {
+            app.world
+                .spawn((
+                    Transform::default(),
+                    GridPosition::new(x, y, 0),
+                    ZOffset::default(),
+                    StaticObject,
+                ))
+                .id()
+        };
+        let wall = spawn_tile(5, 5);
+        let floor = spawn_tile(5, 6);
+
+        let mut mover = GridMover::new(2.0);
+        mover.move_to(GridPosition::new(7, 6, 1));
+        let object = app
+            .world
+            .spawn((
+                Transform::default(),
+                GridPosition::new(4, 6, 1),
+                ZOffset::default(),
+                DynamicObject,
+                mover,
+            ))
+            .id();
+
+        let start = Instant::now();
+        app.world.resource_mut::<Time>().update_with_instant(start);
+        app.update();
+
+        // Walks from (4, 6) to (7, 6) on the back side of the wall, passing the floor tile behind it.
+        for step in 1..=5 {
+            let x = 4.0 + step as f32 * 0.5;
+            app.world
+                .resource_mut::<Time>()
+                .update_with_instant(start + Duration::from_secs_f32(step as f32 * 0.25));
+            app.update();
+
+            assert_eq!(
+                Some(GridPositionF::new(x, 6.0, 1.0)),
+                app.world.get::<GridMover>(object).unwrap().position()
+            );
+            assert!(
+                z(&app, object) < z(&app, wall),
+                "In front of the wall at {}.",
+                x
+            );
+            assert_eq!(
+                x <= 5.0,
+                z(&app, object) > z(&app, floor),
+                "Wrong floor order at {}.",
+                x
+            );
+        }
+
+        // Standing in front of the wall.
+        app.world.get_mut::<GridMover>(object).unwrap().stop();
+        app.world
+            .resource_mut::<Time>()
+            .update_with_instant(start + Duration::from_secs_f32(2.0));
+        app.update();
+        *app.world.get_mut::<GridPosition>(object).unwrap() = GridPosition::new(5, 4, 1);
+        app.update();
+
+        assert!(z(&app, object) > z(&app, wall));
+    }
 }
@@ -0,0 +1,580 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeSet, BinaryHeap},
+};
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    grid::{GridOrientation, GridPosition, GridSize},
+    loading::tilemap::{TileReference, TilemapDefinition},
+    tile::TileMarker,
+};
+
+/// The tiles which are considered neighbours of a tile.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Neighbourhood {
+    /// Only tiles which share an edge.
+    #[default]
+    Four,
+    /// Tiles which share an edge or a corner. Diagonal steps can not cut corners of unwalkable tiles.
+    Eight,
+}
+
+/// The cost of walking onto a tile. Tiles without it cost 1.0.
+#[derive(Component, Debug, Copy, Clone, PartialEq)]
+pub struct MovementCost(pub f32);
+
+/// Marks a tile which can not be walked on.
+#[derive(Component, Debug, Copy, Clone)]
+pub struct Unwalkable;
+
+/// Connects a tile like stairs or a ramp with the neighbouring tiles on another layer.
+/// The connection works in both directions and does not depend on the direction of the grid,
+/// so it stays valid when the grid is rotated.
+#[derive(Component, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LayerTransition(pub i32);
+
+/// A tile which can be walked on.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct WalkableTile {
+    pub cost: f32,
+    pub transition: Option<i32>,
+}
+
+/// The walkable tiles of a grid, used to find paths between them.
+/// A tile is walkable if it is not covered by a tile on the layer above it.
+#[derive(Default, Debug, Clone)]
+pub struct WalkabilityGraph {
+    tiles: HashMap<GridPosition, WalkableTile>,
+    layers: BTreeSet<i32>,
+    neighbourhood: Neighbourhood,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct OpenTile {
+    estimate: f32,
+    position: GridPosition,
+}
+
+pub type PathTileQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static GridPosition,
+        Option<&'static MovementCost>,
+        Option<&'static Unwalkable>,
+        Option<&'static LayerTransition>,
+    ),
+    With<TileMarker>,
+>;
+
+impl Default for WalkableTile {
+    fn default() -> Self {
+        Self {
+            cost: 1.0,
+            transition: None,
+        }
+    }
+}
+
+impl Eq for OpenTile {}
+
+impl Ord for OpenTile {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed, so the binary heap returns the tile with the lowest estimate first.
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+impl PartialOrd for OpenTile {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl WalkabilityGraph {
+    pub fn new(neighbourhood: Neighbourhood) -> Self {
+        Self {
+            neighbourhood,
+            ..default()
+        }
+    }
+
+    /// Builds the graph from tiles given as position, movement cost, walkability and layer transition.
+    pub fn from_tiles(
+        neighbourhood: Neighbourhood,
+        tiles: impl IntoIterator<Item = (GridPosition, Option<f32>, bool, Option<i32>)>,
+    ) -> Self {
+        let tiles = tiles
+            .into_iter()
+            .map(|(position, cost, walkable, transition)| (position, (cost, walkable, transition)))
+            .collect::<HashMap<_, _>>();
+        let mut graph = Self::new(neighbourhood);
+
+        for (position, (cost, walkable, transition)) in tiles.iter() {
+            let above = GridPosition::new(position.x, position.y, position.layer + 1);
+
+            if !walkable || tiles.contains_key(&above) {
+                continue;
+            }
+
+            graph.insert(
+                *position,
+                WalkableTile {
+                    cost: cost.unwrap_or(1.0),
+                    transition: *transition,
+                },
+            );
+        }
+
+        graph
+    }
+
+    /// Builds the graph from the tiles of a tilemap definition, placed as seen with the given orientation.
+    /// Every tile of the definition is walkable with a cost of 1.0.
+    pub fn from_definition(
+        definition: &TilemapDefinition,
+        neighbourhood: Neighbourhood,
+        orientation: GridOrientation,
+    ) -> Self {
+        let canonical_size = GridSize::from(definition);
+        let tiles = definition.layers().iter().flat_map(|layer| {
+            layer.tiles().iter().enumerate().flat_map(move |(y, row)| {
+                row.iter()
+                    .enumerate()
+                    .filter(|(_, identifier)| {
+                        matches!(identifier.parse(), Some(TileReference::Tile { .. }))
+                    })
+                    .map(move |(x, _)| {
+                        let canonical =
+                            GridPosition::from_indices(x, y, layer.ordering_id() as usize);
+                        (
+                            orientation.to_view(canonical, canonical_size),
+                            None,
+                            true,
+                            None,
+                        )
+                    })
+            })
+        });
+
+        Self::from_tiles(neighbourhood, tiles)
+    }
+
+    /// Builds the graph from the tiles in the hierarchy of a grid entity.
+    pub fn from_grid(
+        grid: Entity,
+        neighbourhood: Neighbourhood,
+        children: &Query<&Children>,
+        tiles: &PathTileQuery,
+    ) -> Self {
+        let tiles = children
+            .iter_descendants(grid)
+            .filter_map(|entity| tiles.get(entity).ok())
+            .map(|(position, cost, unwalkable, transition)| {
+                (
+                    *position,
+                    cost.map(|c| c.0),
+                    unwalkable.is_none(),
+                    transition.map(|t| t.0),
+                )
+            });
+
+        Self::from_tiles(neighbourhood, tiles)
+    }
+
+    pub fn neighbourhood(&self) -> Neighbourhood {
+        self.neighbourhood
+    }
+
+    pub fn insert(&mut self, position: GridPosition, tile: WalkableTile) {
+        self.tiles.insert(position, tile);
+        self.layers.insert(position.layer);
+    }
+
+    pub fn remove(&mut self, position: GridPosition) -> Option<WalkableTile> {
+        self.tiles.remove(&position)
+    }
+
+    pub fn tile(&self, position: GridPosition) -> Option<&WalkableTile> {
+        self.tiles.get(&position)
+    }
+
+    pub fn is_walkable(&self, position: GridPosition) -> bool {
+        self.tiles.contains_key(&position)
+    }
+
+    /// The tiles which can be reached from the position in a single step, together with the cost of the step.
+    pub fn neighbours(&self, position: GridPosition) -> Vec<(GridPosition, f32)> {
+        let Some(tile) = self.tiles.get(&position) else {
+            return Vec::new();
+        };
+
+        let offsets: &[(i32, i32)] = match self.neighbourhood {
+            Neighbourhood::Four => &[(0, -1), (1, 0), (0, 1), (-1, 0)],
+            Neighbourhood::Eight => &[
+                (0, -1),
+                (1, -1),
+                (1, 0),
+                (1, 1),
+                (0, 1),
+                (-1, 1),
+                (-1, 0),
+                (-1, -1),
+            ],
+        };
+
+        let mut neighbours = Vec::new();
+
+        for (dx, dy) in offsets.iter().copied() {
+            let (Some(x), Some(y)) = (position.x.checked_add(dx), position.y.checked_add(dy))
+            else {
+                continue;
+            };
+
+            if dx != 0 && dy != 0 {
+                let target = GridPosition::new(x, y, position.layer);
+                let Some(target_tile) = self.tiles.get(&target) else {
+                    continue;
+                };
+
+                if self.is_walkable(GridPosition::new(x, position.y, position.layer))
+                    && self.is_walkable(GridPosition::new(position.x, y, position.layer))
+                {
+                    neighbours.push((target, target_tile.cost * std::f32::consts::SQRT_2));
+                }
+
+                continue;
+            }
+
+            for layer in self.layers.iter().copied() {
+                let target = GridPosition::new(x, y, layer);
+                let Some(target_tile) = self.tiles.get(&target) else {
+                    continue;
+                };
+
+                if layer == position.layer
+                    || tile.transition == Some(layer)
+                    || target_tile.transition == Some(position.layer)
+                {
+                    neighbours.push((target, target_tile.cost));
+                }
+            }
+        }
+
+        neighbours
+    }
+
+    /// Finds the cheapest path between two walkable tiles with A*.
+    /// The path contains every tile after the start up to and including the goal,
+    /// so it can be passed directly to `GridMover::follow`.
+    pub fn find_path(&self, start: GridPosition, goal: GridPosition) -> Option<Vec<GridPosition>> {
+        if !self.is_walkable(start) || !self.is_walkable(goal) {
+            return None;
+        }
+
+        if start == goal {
+            return Some(Vec::new());
+        }
+
+        let min_cost = self
+            .tiles
+            .values()
+            .map(|tile| tile.cost)
+            .fold(f32::MAX, f32::min)
+            .max(0.0);
+
+        let mut open = BinaryHeap::new();
+        let mut came_from = HashMap::<GridPosition, GridPosition>::default();
+        let mut costs = HashMap::<GridPosition, f32>::default();
+
+        costs.insert(start, 0.0);
+        open.push(OpenTile {
+            estimate: self.heuristic(start, goal) * min_cost,
+            position: start,
+        });
+
+        while let Some(OpenTile { position, .. }) = open.pop() {
+            if position == goal {
+                let mut path = vec![goal];
+                let mut current = goal;
+
+                while let Some(previous) = came_from.get(&current) {
+                    if *previous == start {
+                        break;
+                    }
+
+                    path.push(*previous);
+                    current = *previous;
+                }
+
+                path.reverse();
+                return Some(path);
+            }
+
+            let cost = costs[&position];
+
+            for (neighbour, step_cost) in self.neighbours(position) {
+                let new_cost = cost + step_cost;
+
+                if costs
+                    .get(&neighbour)
+                    .is_some_and(|old_cost| *old_cost <= new_cost)
+                {
+                    continue;
+                }
+
+                costs.insert(neighbour, new_cost);
+                came_from.insert(neighbour, position);
+                open.push(OpenTile {
+                    estimate: new_cost + self.heuristic(neighbour, goal) * min_cost,
+                    position: neighbour,
+                });
+            }
+        }
+
+        None
+    }
+
+    /// Number of steps between two positions if every tile would be walkable.
+    fn heuristic(&self, from: GridPosition, to: GridPosition) -> f32 {
+        let dx = from.x.abs_diff(to.x) as f32;
+        let dy = from.y.abs_diff(to.y) as f32;
+
+        match self.neighbourhood {
+            Neighbourhood::Four => dx + dy,
+            Neighbourhood::Eight => dx.max(dy) + (std::f32::consts::SQRT_2 - 1.0) * dx.min(dy),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::SystemState, prelude::*};
+
+    use crate::{
+        grid::{GridOrientation, GridPosition, GridSize},
+        loading::tilemap::{LayerDefinition, TileIdentifier, TilemapDefinitionBuilder},
+        pathfinding::{
+            LayerTransition, MovementCost, Neighbourhood, PathTileQuery, Unwalkable,
+            WalkabilityGraph,
+        },
+        tile::TileMarker,
+    };
+
+    /// Creates the tiles of a layer from rows of characters, '#' is a tile and every other character is empty.
+    fn layer(ordering_id: u32, rows: &[&str]) -> LayerDefinition {
+        LayerDefinition::new(ordering_id).with_tiles(
+            rows.iter()
+                .map(|row| {
+                    row.chars()
+                        .map(|c| match c {
+                            '#' => TileIdentifier::new(0, 't'),
+                            _ => TileIdentifier::empty(),
+                        })
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+
+    fn tiles(layer: i32, rows: &[&str]) -> Vec<(GridPosition, Option<f32>, bool, Option<i32>)> {
+        rows.iter()
+            .enumerate()
+            .flat_map(|(y, row)| {
+                row.chars().enumerate().filter_map(move |(x, c)| match c {
+                    '#' => Some((
+                        GridPosition::new(x as i32, y as i32, layer),
+                        None,
+                        true,
+                        None,
+                    )),
+                    'x' => Some((
+                        GridPosition::new(x as i32, y as i32, layer),
+                        None,
+                        false,
+                        None,
+                    )),
+                    '~' => Some((
+                        GridPosition::new(x as i32, y as i32, layer),
+                        Some(5.0),
+                        true,
+                        None,
+                    )),
+                    _ => None,
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_path_around_obstacle() {
+        let graph =
+            WalkabilityGraph::from_tiles(Neighbourhood::Four, tiles(0, &["###", "#x#", "###"]));
+
+        let path = graph
+            .find_path(GridPosition::new(0, 1, 0), GridPosition::new(2, 1, 0))
+            .unwrap();
+
+        assert_eq!(4, path.len());
+        assert_eq!(GridPosition::new(2, 1, 0), *path.last().unwrap());
+        assert!(!path.contains(&GridPosition::new(1, 1, 0)));
+        assert!(!path.contains(&GridPosition::new(0, 1, 0)));
+    }
+
+    #[test]
+    fn test_no_path() {
+        let graph = WalkabilityGraph::from_tiles(Neighbourhood::Four, tiles(0, &["#x#"]));
+
+        assert_eq!(
+            None,
+            graph.find_path(GridPosition::new(0, 0, 0), GridPosition::new(2, 0, 0))
+        );
+        assert_eq!(
+            Some(vec![]),
+            graph.find_path(GridPosition::new(0, 0, 0), GridPosition::new(0, 0, 0))
+        );
+    }
+
+    #[test]
+    fn test_eight_neighbours() {
+        let graph =
+            WalkabilityGraph::from_tiles(Neighbourhood::Eight, tiles(0, &["###", "###", "###"]));
+
+        assert_eq!(
+            Some(vec![GridPosition::new(1, 1, 0), GridPosition::new(2, 2, 0)]),
+            graph.find_path(GridPosition::new(0, 0, 0), GridPosition::new(2, 2, 0))
+        );
+    }
+
+    #[test]
+    fn test_eight_neighbours_do_not_cut_corners() {
+        let graph = WalkabilityGraph::from_tiles(Neighbourhood::Eight, tiles(0, &["#x", "##"]));
+
+        assert_eq!(
+            Some(vec![GridPosition::new(0, 1, 0), GridPosition::new(1, 1, 0)]),
+            graph.find_path(GridPosition::new(0, 0, 0), GridPosition::new(1, 1, 0))
+        );
+    }
+
+    #[test]
+    fn test_prefers_cheaper_tiles() {
+        let graph = WalkabilityGraph::from_tiles(Neighbourhood::Four, tiles(0, &["#~#", "###"]));
+
+        assert_eq!(
+            Some(vec![
+                GridPosition::new(0, 1, 0),
+                GridPosition::new(1, 1, 0),
+                GridPosition::new(2, 1, 0),
+                GridPosition::new(2, 0, 0),
+            ]),
+            graph.find_path(GridPosition::new(0, 0, 0), GridPosition::new(2, 0, 0))
+        );
+    }
+
+    #[test]
+    fn test_layer_transition() {
+        let mut tiles = tiles(0, &["#  ", "#  "]);
+        tiles.extend([
+            (GridPosition::new(1, 1, 1), None, true, Some(0)),
+            (GridPosition::new(2, 1, 1), None, true, None),
+        ]);
+        let graph = WalkabilityGraph::from_tiles(Neighbourhood::Four, tiles);
+
+        assert_eq!(
+            Some(vec![
+                GridPosition::new(0, 1, 0),
+                GridPosition::new(1, 1, 1),
+                GridPosition::new(2, 1, 1),
+            ]),
+            graph.find_path(GridPosition::new(0, 0, 0), GridPosition::new(2, 1, 1))
+        );
+
+        let mut without_ramp = graph.clone();
+        without_ramp.remove(GridPosition::new(1, 1, 1));
+        assert_eq!(
+            None,
+            without_ramp.find_path(GridPosition::new(0, 0, 0), GridPosition::new(2, 1, 1))
+        );
+    }
+
+    #[test]
+    fn test_covered_tiles_are_not_walkable() {
+        let mut tiles = tiles(0, &["###"]);
+        tiles.push((GridPosition::new(1, 0, 1), None, false, None));
+        let graph = WalkabilityGraph::from_tiles(Neighbourhood::Four, tiles);
+
+        assert!(!graph.is_walkable(GridPosition::new(1, 0, 0)));
+        assert!(!graph.is_walkable(GridPosition::new(1, 0, 1)));
+    }
+
+    #[test]
+    fn test_path_after_rotation() {
+        let definition = TilemapDefinitionBuilder::new("testmap")
+            .add_layer(layer(0, &["####", "#  #", "## #"]))
+            .build();
+        let canonical_size = GridSize::from(&definition);
+        let start = GridPosition::new(0, 2, 0);
+        let goal = GridPosition::new(3, 2, 0);
+
+        let canonical_path = WalkabilityGraph::from_definition(
+            &definition,
+            Neighbourhood::Four,
+            GridOrientation::Deg0,
+        )
+        .find_path(start, goal)
+        .unwrap();
+
+        for orientation in [
+            GridOrientation::Deg90,
+            GridOrientation::Deg180,
+            GridOrientation::Deg270,
+        ] {
+            let view_path =
+                WalkabilityGraph::from_definition(&definition, Neighbourhood::Four, orientation)
+                    .find_path(
+                        orientation.to_view(start, canonical_size),
+                        orientation.to_view(goal, canonical_size),
+                    )
+                    .unwrap();
+
+            assert_eq!(
+                canonical_path
+                    .iter()
+                    .map(|position| orientation.to_view(*position, canonical_size))
+                    .collect::<Vec<_>>(),
+                view_path
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_grid() {
+        let mut world = World::new();
+        let tiles = [
+            world.spawn((TileMarker, GridPosition::new(0, 0, 0))).id(),
+            world
+                .spawn((TileMarker, GridPosition::new(1, 0, 0), Unwalkable))
+                .id(),
+            world
+                .spawn((TileMarker, GridPosition::new(0, 1, 0), MovementCost(2.0)))
+                .id(),
+            world
+                .spawn((TileMarker, GridPosition::new(1, 1, 1), LayerTransition(0)))
+                .id(),
+        ];
+        let grid = world.spawn_empty().push_children(&tiles).id();
+
+        let mut state = SystemState::<(Query<&Children>, PathTileQuery)>::new(&mut world);
+        let (children, tile_query) = state.get(&world);
+        let graph = WalkabilityGraph::from_grid(grid, Neighbourhood::Four, &children, &tile_query);
+
+        assert!(!graph.is_walkable(GridPosition::new(1, 0, 0)));
+        assert_eq!(2.0, graph.tile(GridPosition::new(0, 1, 0)).unwrap().cost);
+        assert_eq!(
+            Some(vec![GridPosition::new(0, 1, 0), GridPosition::new(1, 1, 1)]),
+            graph.find_path(GridPosition::new(0, 0, 0), GridPosition::new(1, 1, 1))
+        );
+    }
+}
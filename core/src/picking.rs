@@ -0,0 +1,433 @@
+use bevy::{prelude::*, utils::HashMap, window::PrimaryWindow};
+
+use crate::{
+    elevation::TileElevation,
+    grid::{Grid, GridMember, GridOffset, GridPosition, TileSize},
+    projection::GridProjection,
+    tile::TileMarker,
+    WorldScale,
+};
+
+/// A tile under the cursor.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PickedTile {
+    pub entity: Entity,
+    pub grid: Entity,
+    pub grid_position: GridPosition,
+}
+
+/// The tile which is currently hovered by the cursor.
+#[derive(Resource, Default, Debug, Copy, Clone, PartialEq)]
+pub struct HoveredTile(pub Option<PickedTile>);
+
+/// Sent when the cursor starts hovering a tile.
+#[derive(Event, Debug, Copy, Clone, PartialEq)]
+pub struct TileHovered {
+    pub entity: Entity,
+    pub grid: Entity,
+    pub grid_position: GridPosition,
+}
+
+/// Sent when the cursor stops hovering a tile.
+#[derive(Event, Debug, Copy, Clone, PartialEq)]
+pub struct TileHoverEnded {
+    pub entity: Entity,
+    pub grid: Entity,
+    pub grid_position: GridPosition,
+}
+
+/// Sent when a mouse button is pressed while a tile is hovered.
+#[derive(Event, Debug, Copy, Clone, PartialEq)]
+pub struct TileClicked {
+    pub entity: Entity,
+    pub grid: Entity,
+    pub grid_position: GridPosition,
+    pub button: MouseButton,
+}
+
+type GridQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static TileSize,
+        &'static WorldScale,
+        &'static GridProjection,
+        Option<&'static GridOffset>,
+        &'static GlobalTransform,
+    ),
+    With<Grid>,
+>;
+
+/// Finds the tile under the cursor of the primary window and sends the hover and click events for it.
+/// Every grid is picked with its own offset, scale, projection and transform, the topmost tile of all grids wins.
+#[allow(clippy::too_many_arguments)]
+pub fn pick_tiles(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    grids: GridQuery,
+    members: Query<&GridMember>,
+    parents: Query<&Parent>,
+    tiles: Query<
+        (
+            Entity,
+            &GridPosition,
+            &GlobalTransform,
+            Option<&TileElevation>,
+        ),
+        With<TileMarker>,
+    >,
+    buttons: Res<Input<MouseButton>>,
+    mut hovered: ResMut<HoveredTile>,
+    mut hovered_events: EventWriter<TileHovered>,
+    mut hover_ended_events: EventWriter<TileHoverEnded>,
+    mut clicked_events: EventWriter<TileClicked>,
+) {
+    let picked = cursor_world_position(&windows, &cameras).and_then(|cursor| {
+        let mut grid_tiles = HashMap::<Entity, HashMap<GridPosition, Entity>>::new();
+        let mut grid_elevations = HashMap::<Entity, HashMap<GridPosition, f32>>::new();
+        let no_elevations = HashMap::default();
+        for (entity, grid_position, _, elevation) in tiles.iter() {
+            if let Some(grid) =
+                GridMember::find_grid(entity, &members, &parents, |e| grids.contains(e))
+            {
+                grid_tiles
+                    .entry(grid)
+                    .or_default()
+                    .insert(*grid_position, entity);
+
+                if let Some(elevation) = elevation {
+                    grid_elevations
+                        .entry(grid)
+                        .or_default()
+                        .insert(*grid_position, elevation.center());
+                }
+            }
+        }
+
+        grids
+            .iter()
+            .filter_map(
+                |(grid_entity, tilesize, scale, projection, offset, grid_transform)| {
+                    let offset = offset.map(|o| o.0).unwrap_or_default();
+                    let position = grid_transform
+                        .affine()
+                        .inverse()
+                        .transform_point3(cursor.extend(0.0))
+                        .truncate()
+                        - offset;
+
+                    find_tile(
+                        position,
+                        grid_tiles.get(&grid_entity)?,
+                        grid_elevations.get(&grid_entity).unwrap_or(&no_elevations),
+                        projection,
+                        tilesize.width() * scale.0,
+                        tilesize.height() * scale.0,
+                    )
+                    .map(|(entity, grid_position)| PickedTile {
+                        entity,
+                        grid: grid_entity,
+                        grid_position,
+                    })
+                },
+            )
+            .max_by(|a, b| {
+                let z = |tile: &PickedTile| {
+                    tiles
+                        .get(tile.entity)
+                        .map(|(_, _, transform, _)| transform.translation().z)
+                        .unwrap_or(f32::MIN)
+                };
+
+                z(a).total_cmp(&z(b))
+            })
+    });
+
+    if hovered.0.map(|tile| tile.entity) != picked.map(|tile| tile.entity) {
+        if let Some(old) = hovered.0 {
+            hover_ended_events.send(TileHoverEnded {
+                entity: old.entity,
+                grid: old.grid,
+                grid_position: old.grid_position,
+            });
+        }
+
+        if let Some(new) = picked {
+            hovered_events.send(TileHovered {
+                entity: new.entity,
+                grid: new.grid,
+                grid_position: new.grid_position,
+            });
+        }
+    }
+
+    hovered.0 = picked;
+
+    if let Some(tile) = picked {
+        for button in buttons.get_just_pressed() {
+            clicked_events.send(TileClicked {
+                entity: tile.entity,
+                grid: tile.grid,
+                grid_position: tile.grid_position,
+                button: *button,
+            });
+        }
+    }
+}
+
+/// The position of the cursor in the world, as seen by the active camera with the highest order.
+pub fn cursor_world_position(
+    windows: &Query<&Window, With<PrimaryWindow>>,
+    cameras: &Query<(&Camera, &GlobalTransform)>,
+) -> Option<Vec2> {
+    let cursor = windows.get_single().ok()?.cursor_position()?;
+    let (camera, camera_transform) = cameras
+        .iter()
+        .filter(|(camera, _)| camera.is_active)
+        .max_by_key(|(camera, _)| camera.order)?;
+
+    camera.viewport_to_world_2d(camera_transform, cursor)
+}
+
+/// Finds the topmost tile at a position relative to the grid.
+/// Layers are checked from top to bottom, so higher tiles cover the ones below them.
+/// Inside of a layer the surfaces of elevated tiles are checked from the highest elevation down,
+/// tiles missing in `elevations` are on the ground of their layer.
+pub fn find_tile(
+    position: Vec2,
+    tiles: &HashMap<GridPosition, Entity>,
+    elevations: &HashMap<GridPosition, f32>,
+    projection: &GridProjection,
+    tile_width: f32,
+    tile_height: f32,
+) -> Option<(Entity, GridPosition)> {
+    let elevation_of =
+        |grid_position: &GridPosition| elevations.get(grid_position).copied().unwrap_or_default();
+    let mut surfaces = tiles
+        .keys()
+        .map(|grid_position| (grid_position.layer, elevation_of(grid_position)))
+        .collect::<Vec<_>>();
+    surfaces.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.total_cmp(&a.1)));
+    surfaces.dedup();
+
+    surfaces.into_iter().find_map(|(layer, elevation)| {
+        let grid_position = grid_position_at(
+            position - Vec2::Y * elevation * tile_height,
+            layer,
+            projection,
+            tile_width,
+            tile_height,
+        )?;
+
+        tiles
+            .get(&grid_position)
+            .filter(|_| elevation_of(&grid_position) == elevation)
+            .map(|entity| (*entity, grid_position))
+    })
+}
+
+/// Gets the grid position of the tile surface at a position relative to the grid on the given layer.
+/// Positions outside of the grid give cells outside of it, even at negative coordinates.
+pub fn grid_position_at(
+    position: Vec2,
+    layer: i32,
+    projection: &GridProjection,
+    tile_width: f32,
+    tile_height: f32,
+) -> Option<GridPosition> {
+    let grid_position = projection.cell_at(position.extend(layer as f32), tile_width, tile_height);
+
+    GridPosition::try_from(grid_position).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{prelude::*, utils::HashMap};
+
+    use crate::{
+        grid::GridPosition,
+        math::grid_to_world,
+        picking::{find_tile, grid_position_at},
+        projection::{GridLayout, GridProjection},
+    };
+
+    /// Center of the top surface of a tile.
+    fn surface_center(grid_position: GridPosition, tile_width: f32, tile_height: f32) -> Vec2 {
+        grid_to_world(Vec3::from(grid_position), tile_width, tile_height).truncate()
+            + Vec2::new(0.0, tile_height / 2.0)
+    }
+
+    #[test]
+    fn test_grid_position_at() {
+        let target = GridPosition::new(12, 3, 0);
+        let position = surface_center(target, 64.0, 32.0);
+
+        assert_eq!(
+            Some(target),
+            grid_position_at(position, 0, &GridProjection::default(), 64.0, 32.0)
+        );
+    }
+
+    #[test]
+    fn test_grid_position_at_layer() {
+        let target = GridPosition::new(2, 1, 3);
+        let position = surface_center(target, 64.0, 32.0);
+
+        assert_eq!(
+            Some(target),
+            grid_position_at(position, 3, &GridProjection::default(), 64.0, 32.0)
+        );
+    }
+
+    #[test]
+    fn test_grid_position_outside() {
+        assert_eq!(
+            Some(GridPosition::new(-2, -2, 0)),
+            grid_position_at(
+                Vec2::new(0.0, -40.0),
+                0,
+                &GridProjection::default(),
+                64.0,
+                32.0
+            )
+        );
+    }
+
+    #[test]
+    fn test_find_tile_prefers_top_layer() {
+        let lower = Entity::from_raw(0);
+        let upper = Entity::from_raw(1);
+        let mut tiles = HashMap::default();
+        tiles.insert(GridPosition::new(2, 1, 0), lower);
+
+        let position = surface_center(GridPosition::new(1, 0, 2), 64.0, 32.0);
+        assert_eq!(
+            Some((lower, GridPosition::new(2, 1, 0))),
+            find_tile(
+                position,
+                &tiles,
+                &HashMap::default(),
+                &GridProjection::default(),
+                64.0,
+                32.0
+            )
+        );
+
+        tiles.insert(GridPosition::new(1, 0, 2), upper);
+        assert_eq!(
+            Some((upper, GridPosition::new(1, 0, 2))),
+            find_tile(
+                position,
+                &tiles,
+                &HashMap::default(),
+                &GridProjection::default(),
+                64.0,
+                32.0
+            )
+        );
+    }
+
+    #[test]
+    fn test_find_elevated_tile() {
+        let ground = Entity::from_raw(0);
+        let elevated = Entity::from_raw(1);
+        let mut tiles = HashMap::default();
+        let mut elevations = HashMap::default();
+        tiles.insert(GridPosition::new(1, 1, 0), ground);
+        tiles.insert(GridPosition::new(2, 2, 0), elevated);
+        elevations.insert(GridPosition::new(2, 2, 0), 1.0);
+
+        // The raised surface of (2, 2) is drawn where the ground surface of (3, 3) would be.
+        let position = surface_center(GridPosition::new(2, 2, 0), 64.0, 32.0) + Vec2::Y * 32.0;
+        assert_eq!(
+            Some((elevated, GridPosition::new(2, 2, 0))),
+            find_tile(
+                position,
+                &tiles,
+                &elevations,
+                &GridProjection::default(),
+                64.0,
+                32.0
+            )
+        );
+
+        // The ground surface of an elevated tile is not part of it.
+        let position = surface_center(GridPosition::new(2, 2, 0), 64.0, 32.0);
+        assert_eq!(
+            None,
+            find_tile(
+                position,
+                &tiles,
+                &elevations,
+                &GridProjection::default(),
+                64.0,
+                32.0
+            )
+        );
+
+        let position = surface_center(GridPosition::new(1, 1, 0), 64.0, 32.0);
+        assert_eq!(
+            Some((ground, GridPosition::new(1, 1, 0))),
+            find_tile(
+                position,
+                &tiles,
+                &elevations,
+                &GridProjection::default(),
+                64.0,
+                32.0
+            )
+        );
+    }
+
+    #[test]
+    fn test_find_tile_with_scale() {
+        let entity = Entity::from_raw(0);
+        let mut tiles = HashMap::default();
+        tiles.insert(GridPosition::new(4, 2, 1), entity);
+
+        let position = surface_center(GridPosition::new(4, 2, 1), 32.0 * 3.0, 16.0 * 3.0);
+        assert_eq!(
+            Some((entity, GridPosition::new(4, 2, 1))),
+            find_tile(
+                position,
+                &tiles,
+                &HashMap::default(),
+                &GridProjection::default(),
+                32.0 * 3.0,
+                16.0 * 3.0
+            )
+        );
+    }
+
+    #[test]
+    fn test_find_tile_staggered() {
+        let projection = GridProjection::new(GridLayout::StaggeredY);
+        let mut tiles = HashMap::default();
+        for x in 0..4 {
+            for y in 0..4 {
+                tiles.insert(
+                    GridPosition::new(x, y, 1),
+                    Entity::from_raw((x * 4 + y) as u32),
+                );
+            }
+        }
+
+        let target = GridPosition::new(2, 3, 1);
+        let position = projection
+            .grid_to_world(Vec3::from(target), 64.0, 32.0)
+            .truncate()
+            + Vec2::new(0.0, 16.0);
+        assert_eq!(
+            Some((Entity::from_raw(11), target)),
+            find_tile(
+                position,
+                &tiles,
+                &HashMap::default(),
+                &projection,
+                64.0,
+                32.0
+            )
+        );
+    }
+}
@@ -1,8 +1,23 @@
 use bevy::prelude::*;
 
 use crate::{
-    ordering::{order_static_tile_z, reorder_on_rotation, update_dynamic_object_z},
-    rotate::{rotate_grid, GridRotationEvent}, loading::{loader::TilemapAssetLoader, tilemap::TilemapDefinition},
+    animation::{animate_tiles, TileAnimationSettings},
+    batching::update_tile_batches,
+    camera::{update_iso_cameras, update_map_bounds},
+    chunking::update_chunks,
+    interaction::{
+        animate_hover_lift, apply_hover_effects, prepare_hover_lift, TileInteractionSettings,
+    },
+    movement::{move_grid_objects, MovementFinished, MovementStarted},
+    ordering::{
+        order_static_tile_z, reorder_on_rotation, update_dynamic_object_z, DepthOrdering,
+    },
+    picking::{pick_tiles, HoveredTile, TileClicked, TileHoverEnded, TileHovered},
+    rotate::{rotate_grid, GridRotationEvent, GridRotationFinished}, loading::{ldtk::SpawnPoints, loader::{JsonTilemapAssetLoader, JsonTilesetAssetLoader, LdtkProjectAssetLoader, TiledMapAssetLoader, TiledTilesetAssetLoader, TilemapAssetLoader, TilesetAssetLoader}, tilemap::TilemapDefinition, tileset::TilesetDefinition},
+    reloading::reload_tilemaps,
+    saving::{save_tilemaps, SaveTilemapEvent},
+    spawning::{spawn_tilemap, update_registered_tilesets, TilemapSpawner},
+    transition::animate_rotation_transitions,
 };
 
 pub struct IsometricTilemapPlugin;
@@ -10,13 +25,111 @@ pub struct IsometricTilemapPlugin;
 impl Plugin for IsometricTilemapPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<GridRotationEvent>()
+            .add_event::<GridRotationFinished>()
+            .add_event::<MovementStarted>()
+            .add_event::<MovementFinished>()
+            .add_event::<SaveTilemapEvent>()
             .add_asset::<TilemapDefinition>()
+            .add_asset::<TilesetDefinition>()
+            .add_asset::<SpawnPoints>()
             .init_asset_loader::<TilemapAssetLoader>()
+            .init_asset_loader::<TilesetAssetLoader>()
+            .init_asset_loader::<JsonTilemapAssetLoader>()
+            .init_asset_loader::<JsonTilesetAssetLoader>()
+            .init_asset_loader::<TiledMapAssetLoader>()
+            .init_asset_loader::<TiledTilesetAssetLoader>()
+            .init_asset_loader::<LdtkProjectAssetLoader>()
+            .init_resource::<TilemapSpawner>()
+            .init_resource::<TileAnimationSettings>()
+            .init_resource::<DepthOrdering>()
             .add_systems(Update,(
+                update_registered_tilesets.before(spawn_tilemap),
+                spawn_tilemap.before(order_static_tile_z),
                 order_static_tile_z.before(reorder_on_rotation),
                 update_dynamic_object_z,
+                move_grid_objects
+                    .after(rotate_grid)
+                    .before(update_dynamic_object_z),
                 rotate_grid.before(reorder_on_rotation),
+                animate_rotation_transitions
+                    .after(rotate_grid)
+                    .before(reorder_on_rotation),
                 reorder_on_rotation.after(rotate_grid),
+                animate_tiles,
+                save_tilemaps.after(rotate_grid),
+                reload_tilemaps.after(rotate_grid).before(order_static_tile_z),
+                update_chunks
+                    .after(spawn_tilemap)
+                    .after(rotate_grid)
+                    .before(reload_tilemaps),
+                update_map_bounds.after(spawn_tilemap).after(rotate_grid),
             ));
     }
-}
\ No newline at end of file
+}
+
+/// Sends hover and click events for the tiles under the cursor.
+pub struct TilePickingPlugin;
+
+impl Plugin for TilePickingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TileHovered>()
+            .add_event::<TileHoverEnded>()
+            .add_event::<TileClicked>()
+            .init_resource::<HoveredTile>()
+            .add_systems(Update, pick_tiles.after(rotate_grid));
+    }
+}
+
+/// Applies the built in hover effects like `HoverTint`, `HoverSprite` and `HoverLift` to tiles.
+pub struct TileInteractionPlugin;
+
+impl Plugin for TileInteractionPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<TilePickingPlugin>() {
+            app.add_plugins(TilePickingPlugin);
+        }
+
+        app.init_resource::<TileInteractionSettings>()
+            .add_systems(
+                Update,
+                (
+                    apply_hover_effects.after(pick_tiles),
+                    prepare_hover_lift,
+                    animate_hover_lift
+                        .after(apply_hover_effects)
+                        .after(rotate_grid)
+                        .before(order_static_tile_z),
+                ),
+            );
+    }
+}
+
+/// Moves cameras with an `IsoCamera` by keyboard, mouse and their follow target.
+pub struct IsoCameraPlugin;
+
+impl Plugin for IsoCameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            update_iso_cameras
+                .after(update_map_bounds)
+                .after(move_grid_objects),
+        );
+    }
+}
+
+/// Draws grids marked with `BatchedRendering` as batched meshes instead of one sprite per tile.
+pub struct BatchedRenderingPlugin;
+
+impl Plugin for BatchedRenderingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            update_tile_batches
+                .after(order_static_tile_z)
+                .after(reorder_on_rotation)
+                .after(animate_tiles)
+                .after(update_chunks),
+        );
+    }
+}
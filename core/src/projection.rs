@@ -0,0 +1,454 @@
+use bevy::prelude::*;
+
+/// How the cells and layers of a grid are placed in the world. Part of every `GridBundle`.
+/// Positions in grid space use the z axis for the layer, positions in the world are relative to the grid.
+/// A cell is placed at the bottom corner of its tile surface, which is the center of a tile sprite.
+#[derive(Component, Default, Debug, Clone, PartialEq)]
+pub struct GridProjection {
+    pub layout: GridLayout,
+    pub elevation: LayerElevation,
+}
+
+/// The arrangement of the cells of a grid.
+#[derive(Default, Debug, Copy, Clone, PartialEq)]
+pub enum GridLayout {
+    /// The x axis runs to the upper right and the y axis to the upper left, so the grid forms a diamond.
+    #[default]
+    Diamond,
+    /// Columns of tiles stacked on top of each other, every odd column is shifted up by half a tile.
+    StaggeredX,
+    /// Rows of tiles next to each other, every odd row is shifted right by half a tile.
+    StaggeredY,
+    /// Pointy topped hexagons in rows like `StaggeredY`.
+    /// The vertical sides of the hexagons are `side_height` times the tile height high.
+    Hexagonal { side_height: f32 },
+}
+
+/// The height at which the layers of a grid are drawn.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayerElevation {
+    /// The first `ground_layers` layers are drawn at the ground, every further layer `height` tile heights
+    /// above the previous one.
+    Uniform { height: f32, ground_layers: usize },
+    /// The elevation of every layer in tile heights, layers above the last one are drawn at its elevation.
+    PerLayer(Vec<f32>),
+}
+
+impl GridProjection {
+    pub fn new(layout: GridLayout) -> Self {
+        Self {
+            layout,
+            ..default()
+        }
+    }
+
+    pub fn with_elevation(mut self, elevation: LayerElevation) -> Self {
+        self.elevation = elevation;
+        self
+    }
+
+    /// The world position of a position in grid space.
+    /// Positions between cells are placed between the surrounding cells, so objects can move smoothly.
+    pub fn grid_to_world(&self, grid_pos: Vec3, tile_width: f32, tile_height: f32) -> Vec3 {
+        let ground = self
+            .layout
+            .to_world(grid_pos.truncate(), tile_width, tile_height);
+
+        Vec3::new(
+            ground.x,
+            ground.y + self.layer_height(grid_pos.z, tile_height),
+            grid_pos.z,
+        )
+    }
+
+    /// The world position of a position in grid space, raised by an elevation in tile heights.
+    /// Used for tiles and objects with a `TileElevation`.
+    pub fn elevated_to_world(
+        &self,
+        grid_pos: Vec3,
+        elevation: f32,
+        tile_width: f32,
+        tile_height: f32,
+    ) -> Vec3 {
+        self.grid_to_world(grid_pos, tile_width, tile_height) + Vec3::Y * elevation * tile_height
+    }
+
+    /// The position in grid space of a world position on the layer given by its z coordinate.
+    /// Inverse of `grid_to_world`.
+    pub fn world_to_grid(&self, world_pos: Vec3, tile_width: f32, tile_height: f32) -> Vec3 {
+        let ground = Vec2::new(
+            world_pos.x,
+            world_pos.y - self.layer_height(world_pos.z, tile_height),
+        );
+
+        self.layout
+            .to_grid(ground, tile_width, tile_height)
+            .extend(world_pos.z)
+    }
+
+    /// The cell whose tile surface on the layer given by the z coordinate covers the world position.
+    /// The cell can be outside of the grid, even at negative coordinates.
+    pub fn cell_at(&self, world_pos: Vec3, tile_width: f32, tile_height: f32) -> Vec3 {
+        let ground = Vec2::new(
+            world_pos.x,
+            world_pos.y - self.layer_height(world_pos.z, tile_height),
+        );
+
+        self.layout
+            .cell_at(ground, tile_width, tile_height)
+            .extend(world_pos.z)
+    }
+
+    /// The cell whose tile surface, raised by an elevation in tile heights, covers the world position.
+    pub fn elevated_cell_at(
+        &self,
+        world_pos: Vec3,
+        elevation: f32,
+        tile_width: f32,
+        tile_height: f32,
+    ) -> Vec3 {
+        self.cell_at(
+            world_pos - Vec3::Y * elevation * tile_height,
+            tile_width,
+            tile_height,
+        )
+    }
+
+    /// The vertical offset of a layer in the world.
+    pub fn layer_height(&self, layer: f32, tile_height: f32) -> f32 {
+        self.elevation.elevation(layer) * tile_height
+    }
+
+    /// How far back a position in grid space is drawn, ignoring its layer.
+    /// Grows by one for every half tile height, which is the sum of x and y in a diamond grid.
+    pub fn depth_row(&self, grid_pos: Vec3) -> f32 {
+        self.layout.to_world(grid_pos.truncate(), 2.0, 2.0).y
+    }
+}
+
+impl GridLayout {
+    /// The world position of a position in grid space on the ground.
+    pub fn to_world(self, grid_pos: Vec2, tile_width: f32, tile_height: f32) -> Vec2 {
+        let tile_width_half = tile_width / 2.0;
+        let tile_height_half = tile_height / 2.0;
+
+        match self {
+            GridLayout::Diamond => Vec2::new(
+                (grid_pos.x - grid_pos.y) * tile_width_half,
+                (grid_pos.x + grid_pos.y) * tile_height_half,
+            ),
+            GridLayout::StaggeredX => Vec2::new(
+                grid_pos.x * tile_width_half,
+                grid_pos.y * tile_height + stagger(grid_pos.x) * tile_height_half,
+            ),
+            GridLayout::StaggeredY | GridLayout::Hexagonal { .. } => Vec2::new(
+                grid_pos.x * tile_width + stagger(grid_pos.y) * tile_width_half,
+                grid_pos.y * self.row_height(tile_height),
+            ),
+        }
+    }
+
+    /// The position in grid space of a world position on the ground. Inverse of `to_world`.
+    pub fn to_grid(self, world_pos: Vec2, tile_width: f32, tile_height: f32) -> Vec2 {
+        let tile_width_half = tile_width / 2.0;
+        let tile_height_half = tile_height / 2.0;
+
+        match self {
+            GridLayout::Diamond => Vec2::new(
+                (world_pos.x / tile_width_half + world_pos.y / tile_height_half) / 2.0,
+                (world_pos.y / tile_height_half - (world_pos.x / tile_width_half)) / 2.0,
+            ),
+            GridLayout::StaggeredX => {
+                let x = world_pos.x / tile_width_half;
+                Vec2::new(
+                    x,
+                    (world_pos.y - stagger(x) * tile_height_half) / tile_height,
+                )
+            }
+            GridLayout::StaggeredY | GridLayout::Hexagonal { .. } => {
+                let y = world_pos.y / self.row_height(tile_height);
+                Vec2::new((world_pos.x - stagger(y) * tile_width_half) / tile_width, y)
+            }
+        }
+    }
+
+    /// The cell whose tile surface on the ground covers the world position.
+    pub fn cell_at(self, world_pos: Vec2, tile_width: f32, tile_height: f32) -> Vec2 {
+        let approximate = self.to_grid(world_pos, tile_width, tile_height).floor();
+        if matches!(self, GridLayout::Diamond) {
+            return approximate;
+        }
+
+        // The surfaces of the cells around the approximate one cover the position.
+        // Only the cell which covers it has a distance of at most one to the center of its surface.
+        (-2..=1)
+            .flat_map(|x| (-2..=1).map(move |y| approximate + Vec2::new(x as f32, y as f32)))
+            .min_by(|a, b| {
+                let distance = |cell: &Vec2| {
+                    let center = self.to_world(*cell, tile_width, tile_height)
+                        + Vec2::new(0.0, tile_height / 2.0);
+                    self.surface_distance(world_pos - center, tile_width, tile_height)
+                };
+
+                distance(a).total_cmp(&distance(b))
+            })
+            .unwrap_or(approximate)
+    }
+
+    /// Vertical distance between two rows of a staggered or hexagonal grid.
+    fn row_height(self, tile_height: f32) -> f32 {
+        match self {
+            GridLayout::Hexagonal { side_height } => tile_height * (1.0 + side_height) / 2.0,
+            _ => tile_height / 2.0,
+        }
+    }
+
+    /// Distance of an offset to the center of a tile surface, measured so the border of the surface is at one.
+    fn surface_distance(self, offset: Vec2, tile_width: f32, tile_height: f32) -> f32 {
+        let side_height = match self {
+            GridLayout::Hexagonal { side_height } => side_height,
+            _ => 0.0,
+        };
+        let x = offset.x.abs() / (tile_width / 2.0);
+        let y = offset.y.abs() / (tile_height / 2.0);
+
+        x.max(y + x * (1.0 - side_height))
+    }
+}
+
+impl LayerElevation {
+    /// The elevation of a layer in tile heights.
+    /// The elevation of layers between two others lies between theirs.
+    pub fn elevation(&self, layer: f32) -> f32 {
+        match self {
+            LayerElevation::Uniform {
+                height,
+                ground_layers,
+            } => height * (layer - ground_layers.saturating_sub(1) as f32).max(0.0),
+            LayerElevation::PerLayer(elevations) => {
+                let at = |index: f32| {
+                    elevations
+                        .get(index as usize)
+                        .or(elevations.last())
+                        .copied()
+                        .unwrap_or_default()
+                };
+                let layer = layer.max(0.0);
+
+                at(layer.floor()) + (at(layer.ceil()) - at(layer.floor())) * layer.fract()
+            }
+        }
+    }
+}
+
+impl Default for LayerElevation {
+    /// The first two layers share the ground, every further layer is one tile height above the previous one.
+    fn default() -> Self {
+        Self::Uniform {
+            height: 1.0,
+            ground_layers: 2,
+        }
+    }
+}
+
+/// How far a staggered row or column at the given position is shifted.
+/// Odd rows are shifted by one, positions between two rows by the part of the way between them.
+fn stagger(position: f32) -> f32 {
+    let row = position.floor();
+    let odd = row.rem_euclid(2.0);
+
+    odd + (1.0 - 2.0 * odd) * (position - row)
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use crate::{
+        math::{approx_eq_vec3, grid_to_world},
+        projection::{GridLayout, GridProjection, LayerElevation},
+    };
+
+    const LAYOUTS: [GridLayout; 4] = [
+        GridLayout::Diamond,
+        GridLayout::StaggeredX,
+        GridLayout::StaggeredY,
+        GridLayout::Hexagonal { side_height: 0.5 },
+    ];
+
+    fn assert_round_trip(projection: &GridProjection, grid_pos: Vec3) {
+        let world_pos = projection.grid_to_world(grid_pos, 64.0, 32.0);
+        let back = projection.world_to_grid(world_pos, 64.0, 32.0);
+
+        assert!(
+            (back - grid_pos).abs().max_element() < 1e-4,
+            "{:?} became {:?} in {:?}",
+            grid_pos,
+            back,
+            projection.layout
+        );
+    }
+
+    #[test]
+    fn test_default_matches_diamond() {
+        let projection = GridProjection::default();
+
+        for grid_pos in [
+            Vec3::new(1.0, 0.0, 2.0),
+            Vec3::new(3.5, 2.0, 0.0),
+            Vec3::new(4.0, 7.0, 1.0),
+        ] {
+            assert!(approx_eq_vec3(
+                grid_to_world(grid_pos, 128.0, 64.0),
+                projection.grid_to_world(grid_pos, 128.0, 64.0)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_round_trip_cells() {
+        for layout in LAYOUTS {
+            let projection = GridProjection::new(layout);
+
+            for x in 0..6 {
+                for y in 0..6 {
+                    for layer in 0..4 {
+                        assert_round_trip(&projection, Vec3::new(x as f32, y as f32, layer as f32));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip_between_cells() {
+        for layout in LAYOUTS {
+            let projection = GridProjection::new(layout);
+
+            for grid_pos in [
+                Vec3::new(0.5, 0.0, 0.0),
+                Vec3::new(2.0, 3.25, 1.0),
+                Vec3::new(3.75, 4.5, 2.0),
+                Vec3::new(-1.5, 2.5, 3.0),
+            ] {
+                assert_round_trip(&projection, grid_pos);
+            }
+        }
+    }
+
+    #[test]
+    fn test_staggered_neighbours() {
+        let staggered_x = GridProjection::new(GridLayout::StaggeredX);
+        assert_eq!(
+            Vec3::new(32.0, 16.0, 0.0),
+            staggered_x.grid_to_world(Vec3::new(1.0, 0.0, 0.0), 64.0, 32.0)
+        );
+        assert_eq!(
+            Vec3::new(64.0, 32.0, 0.0),
+            staggered_x.grid_to_world(Vec3::new(2.0, 1.0, 0.0), 64.0, 32.0)
+        );
+
+        let staggered_y = GridProjection::new(GridLayout::StaggeredY);
+        assert_eq!(
+            Vec3::new(32.0, 16.0, 0.0),
+            staggered_y.grid_to_world(Vec3::new(0.0, 1.0, 0.0), 64.0, 32.0)
+        );
+        assert_eq!(
+            Vec3::new(64.0, 32.0, 0.0),
+            staggered_y.grid_to_world(Vec3::new(1.0, 2.0, 0.0), 64.0, 32.0)
+        );
+
+        let hexagonal = GridProjection::new(GridLayout::Hexagonal { side_height: 0.5 });
+        assert_eq!(
+            Vec3::new(32.0, 24.0, 0.0),
+            hexagonal.grid_to_world(Vec3::new(0.0, 1.0, 0.0), 64.0, 32.0)
+        );
+    }
+
+    #[test]
+    fn test_cell_at_surface() {
+        for layout in LAYOUTS {
+            let projection = GridProjection::new(layout);
+
+            for x in 0..5 {
+                for y in 0..5 {
+                    let cell = Vec3::new(x as f32, y as f32, 2.0);
+                    let center = projection.grid_to_world(cell, 64.0, 32.0) + Vec3::Y * 16.0;
+
+                    // The center of the surface and points close to its four corners.
+                    for offset in [
+                        Vec2::ZERO,
+                        Vec2::new(-30.0, 0.0),
+                        Vec2::new(30.0, 0.0),
+                        Vec2::new(0.0, -14.0),
+                        Vec2::new(0.0, 14.0),
+                    ] {
+                        assert_eq!(
+                            cell,
+                            projection.cell_at(center + offset.extend(0.0), 64.0, 32.0),
+                            "Wrong cell at {:?} of {:?} in {:?}",
+                            offset,
+                            cell,
+                            layout
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_layer_elevation() {
+        let uniform = GridProjection::default().with_elevation(LayerElevation::Uniform {
+            height: 0.5,
+            ground_layers: 1,
+        });
+        assert_eq!(0.0, uniform.layer_height(0.0, 32.0));
+        assert_eq!(16.0, uniform.layer_height(1.0, 32.0));
+        assert_eq!(48.0, uniform.layer_height(3.0, 32.0));
+
+        let per_layer = GridProjection::default()
+            .with_elevation(LayerElevation::PerLayer(vec![0.0, 0.25, 1.0]));
+        assert_eq!(8.0, per_layer.layer_height(1.0, 32.0));
+        assert_eq!(20.0, per_layer.layer_height(1.5, 32.0));
+        assert_eq!(32.0, per_layer.layer_height(5.0, 32.0));
+
+        let world_pos = per_layer.grid_to_world(Vec3::new(2.0, 1.0, 2.0), 64.0, 32.0);
+        assert_eq!(Vec3::new(32.0, 80.0, 2.0), world_pos);
+        assert_eq!(
+            Vec3::new(2.0, 1.0, 2.0),
+            per_layer.world_to_grid(world_pos, 64.0, 32.0)
+        );
+    }
+
+    #[test]
+    fn test_elevated_cell() {
+        let projection = GridProjection::default();
+        let cell = Vec3::new(3.0, 1.0, 0.0);
+        let surface = projection.elevated_to_world(cell, 1.5, 64.0, 32.0) + Vec3::Y * 16.0;
+
+        assert_eq!(
+            projection.grid_to_world(cell, 64.0, 32.0) + Vec3::Y * 48.0,
+            projection.elevated_to_world(cell, 1.5, 64.0, 32.0)
+        );
+        assert_eq!(cell, projection.elevated_cell_at(surface, 1.5, 64.0, 32.0));
+        assert_ne!(cell, projection.cell_at(surface, 64.0, 32.0));
+    }
+
+    #[test]
+    fn test_depth_row() {
+        let diamond = GridProjection::default();
+        assert_eq!(7.0, diamond.depth_row(Vec3::new(3.0, 4.0, 1.0)));
+
+        // Cells of the same staggered row are drawn at the same depth.
+        let staggered_y = GridProjection::new(GridLayout::StaggeredY);
+        assert_eq!(
+            staggered_y.depth_row(Vec3::new(0.0, 3.0, 0.0)),
+            staggered_y.depth_row(Vec3::new(5.0, 3.0, 0.0))
+        );
+        assert!(
+            staggered_y.depth_row(Vec3::new(5.0, 2.0, 0.0))
+                < staggered_y.depth_row(Vec3::new(0.0, 3.0, 0.0))
+        );
+    }
+}
@@ -0,0 +1,572 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    chunking::LoadedChunks,
+    elevation::TileElevation,
+    grid::{Grid, GridOffset, GridOrientation, GridPosition, GridSize, TileSize},
+    loading::{
+        tilemap::{TileReference, TilemapDefinition},
+        tileset::TilesetDefinition,
+    },
+    ordering::{DepthOrdering, Footprint, ZOffset},
+    projection::GridProjection,
+    spawning::{register_loaded_tilesets, TilemapSpawner, TilesetLoadState},
+    tile::{TileId, TileLift, TileMarker, TilesetAlias},
+    tilemap::{TilemapBundle, TilemapOrderId},
+    transition::GridRotationTransition,
+    DynamicObject, WorldScale,
+};
+
+/// Marks a grid whose tilemap asset was modified but could not be reloaded yet,
+/// because its tilesets are still loading or the grid is rotating.
+#[derive(Component, Debug, Copy, Clone)]
+pub struct PendingTilemapReload;
+
+type ReloadGridQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Grid,
+        &'static GridOrientation,
+        &'static GridSize,
+        &'static TileSize,
+        &'static WorldScale,
+        &'static GridProjection,
+        Option<&'static GridOffset>,
+        Option<&'static GridRotationTransition>,
+        Option<&'static PendingTilemapReload>,
+        Option<&'static mut LoadedChunks>,
+    ),
+>;
+
+pub type ReloadTileQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static TileId,
+        &'static TilesetAlias,
+        &'static mut GridPosition,
+        &'static mut Transform,
+        &'static ZOffset,
+        Option<&'static Footprint>,
+        Option<&'static TileLift>,
+        Option<&'static TileElevation>,
+    ),
+    With<TileMarker>,
+>;
+
+/// The spawned grid a modified tilemap definition is applied to.
+pub struct ReloadTarget {
+    pub grid: Entity,
+    pub orientation: GridOrientation,
+    /// Size of the grid in the current view, before the reload.
+    pub size: GridSize,
+    pub tile_size: TileSize,
+    pub scale: WorldScale,
+    pub offset: GridOffset,
+    pub projection: GridProjection,
+    /// Chunked grids only get their layers changed, their chunks are spawned again afterwards.
+    pub chunked: bool,
+}
+
+/// Applies the modified tilemap assets of spawned grids.
+/// Grids which are rotating or whose new tilesets are still loading are reloaded as soon as possible.
+#[allow(clippy::too_many_arguments)]
+pub fn reload_tilemaps(
+    mut commands: Commands,
+    mut asset_events: EventReader<AssetEvent<TilemapDefinition>>,
+    mut spawner: ResMut<TilemapSpawner>,
+    ordering: Res<DepthOrdering>,
+    asset_server: Res<AssetServer>,
+    tilemaps: Res<Assets<TilemapDefinition>>,
+    tilesets: Res<Assets<TilesetDefinition>>,
+    mut grids: ReloadGridQuery,
+    children: Query<&Children>,
+    layers: Query<&TilemapOrderId>,
+    mut tiles: ReloadTileQuery,
+    dynamic_objects: Query<(), With<DynamicObject>>,
+) {
+    let modified = asset_events
+        .iter()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { handle } => Some(handle.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    for (
+        grid_entity,
+        grid,
+        orientation,
+        size,
+        tile_size,
+        scale,
+        projection,
+        offset,
+        transition,
+        pending,
+        loaded_chunks,
+    ) in grids.iter_mut()
+    {
+        if pending.is_none() && !modified.contains(&grid.tilemap_handle) {
+            continue;
+        }
+
+        let Some(definition) = tilemaps.get(&grid.tilemap_handle) else {
+            continue;
+        };
+
+        if transition.is_some_and(|t| t.is_running())
+            || register_loaded_tilesets(
+                &mut spawner,
+                grid_entity,
+                grid,
+                definition,
+                &asset_server,
+                &tilesets,
+            ) == TilesetLoadState::Loading
+        {
+            commands.entity(grid_entity).insert(PendingTilemapReload);
+            continue;
+        }
+
+        if let Err(errors) = spawner.validate_grid(Some(grid_entity), definition) {
+            for error in errors {
+                warn!("Invalid tilemap '{}': {:?}", definition.name(), error);
+            }
+        }
+
+        let chunked = loaded_chunks.is_some();
+
+        if let Some(mut loaded_chunks) = loaded_chunks {
+            for entity in loaded_chunks.drain() {
+                despawn_keeping_objects(
+                    &mut commands,
+                    entity,
+                    grid_entity,
+                    &children,
+                    &dynamic_objects,
+                );
+            }
+        }
+
+        apply_tilemap_changes(
+            &mut commands,
+            &spawner,
+            &ordering,
+            &ReloadTarget {
+                grid: grid_entity,
+                orientation: *orientation,
+                size: *size,
+                tile_size: *tile_size,
+                scale: *scale,
+                offset: offset.copied().unwrap_or(GridOffset(Vec2::default())),
+                projection: projection.clone(),
+                chunked,
+            },
+            definition,
+            &children,
+            &layers,
+            &mut tiles,
+            &dynamic_objects,
+        );
+        commands
+            .entity(grid_entity)
+            .remove::<PendingTilemapReload>();
+    }
+}
+
+/// Changes an already spawned grid into the given definition.
+///
+/// Only tiles whose identifier changed are despawned and spawned again, added and removed layers are
+/// spawned and despawned. The grid keeps its rotation, so the new tiles are placed in the current view.
+/// Unchanged tiles are only moved if the size of the grid or its tiles changed.
+/// Dynamic objects attached to removed layers or tiles are moved to the grid instead of being despawned.
+/// Chunked grids only get their layers changed, their tiles are left to the chunks.
+#[allow(clippy::too_many_arguments)]
+pub fn apply_tilemap_changes(
+    commands: &mut Commands,
+    spawner: &TilemapSpawner,
+    ordering: &DepthOrdering,
+    target: &ReloadTarget,
+    definition: &TilemapDefinition,
+    children: &Query<&Children>,
+    layers: &Query<&TilemapOrderId>,
+    tiles: &mut ReloadTileQuery,
+    dynamic_objects: &Query<(), With<DynamicObject>>,
+) {
+    let grid = target.grid;
+    let old_canonical_size = target.orientation.canonical_size(target.size);
+    let canonical_size = GridSize::from(definition);
+    let tile_size = TileSize::new(
+        definition.tile_size().width() as f32,
+        definition.tile_size().height() as f32 / 2.0,
+    );
+    let resized = canonical_size != old_canonical_size
+        || tile_size.width() != target.tile_size.width()
+        || tile_size.height() != target.tile_size.height();
+
+    commands.entity(grid).insert((
+        tile_size,
+        target.orientation.view_size(canonical_size),
+        Name::new(format!("Grid - {}", definition.name())),
+    ));
+
+    let mut old_layers = HashMap::new();
+    let mut old_tiles = HashMap::new();
+
+    for entity in children.iter_descendants(grid) {
+        if let Ok(order_id) = layers.get(entity) {
+            old_layers.insert(order_id.id(), entity);
+        } else if target.chunked {
+            continue;
+        } else if let Ok((_, _, position, ..)) = tiles.get(entity) {
+            let canonical = target
+                .orientation
+                .to_canonical(*position, old_canonical_size);
+            old_tiles.insert(canonical, entity);
+        }
+    }
+
+    for layer in definition.layers() {
+        let layer_id = layer.ordering_id() as usize;
+        let layer_entity = match old_layers.remove(&layer_id) {
+            Some(entity) => entity,
+            None => {
+                let layer_name = format!("{} - {}", definition.name(), layer_id);
+                let entity = commands
+                    .spawn((
+                        TilemapBundle::new(&layer_name, layer_id),
+                        Name::new(format!("Tilemap - {}", layer_name)),
+                    ))
+                    .id();
+                commands.entity(grid).add_child(entity);
+                entity
+            }
+        };
+
+        if target.chunked {
+            continue;
+        }
+
+        for (y, row) in layer.tiles().iter().enumerate() {
+            for (x, identifier) in row.iter().enumerate() {
+                let canonical = GridPosition::from_indices(x, y, layer_id);
+                let position = target.orientation.to_view(canonical, canonical_size);
+                let old_tile = old_tiles.remove(&canonical);
+
+                if let (Some(entity), Some(TileReference::Tile { alias, id })) =
+                    (old_tile, identifier.parse())
+                {
+                    let Ok((
+                        tile_id,
+                        tile_alias,
+                        mut grid_position,
+                        mut transform,
+                        z_offset,
+                        footprint,
+                        lift,
+                        elevation,
+                    )) = tiles.get_mut(entity)
+                    else {
+                        continue;
+                    };
+                    let elevation = elevation.copied().unwrap_or_default();
+
+                    if tile_id.id() == id
+                        && tile_alias.0 == alias
+                        && elevation.height == layer.elevation(x, y)
+                    {
+                        if resized {
+                            let mut translation = target.projection.elevated_to_world(
+                                Vec3::from(position),
+                                elevation.height,
+                                tile_size.width() * target.scale.0,
+                                tile_size.height() * target.scale.0,
+                            );
+                            translation.x += target.offset.0.x;
+                            translation.y +=
+                                target.offset.0.y + lift.map(|l| l.0).unwrap_or_default();
+                            translation.z = ordering.object_z(
+                                &target.projection,
+                                Vec3::from(position),
+                                footprint,
+                                Some(&elevation),
+                                z_offset,
+                            );
+
+                            *grid_position = position;
+                            transform.translation = translation;
+                        }
+
+                        continue;
+                    }
+                }
+
+                if let Some(entity) = old_tile {
+                    despawn_keeping_objects(commands, entity, grid, children, dynamic_objects);
+                }
+
+                if identifier.is_empty() {
+                    continue;
+                }
+
+                commands.entity(layer_entity).with_children(|tilemap| {
+                    spawner.spawn_tile(
+                        tilemap,
+                        grid,
+                        definition,
+                        identifier,
+                        position,
+                        layer.elevation(x, y),
+                        target.orientation,
+                        tile_size,
+                        target.scale,
+                        target.offset,
+                        &target.projection,
+                    );
+                });
+            }
+        }
+    }
+
+    for entity in old_tiles.into_values().chain(old_layers.into_values()) {
+        despawn_keeping_objects(commands, entity, grid, children, dynamic_objects);
+    }
+}
+
+/// Despawns an entity with its descendants, but moves dynamic objects among its children to the grid.
+pub(crate) fn despawn_keeping_objects(
+    commands: &mut Commands,
+    entity: Entity,
+    grid: Entity,
+    children: &Query<&Children>,
+    dynamic_objects: &Query<(), With<DynamicObject>>,
+) {
+    if let Ok(entity_children) = children.get(entity) {
+        for child in entity_children.iter() {
+            if dynamic_objects.contains(*child) {
+                commands.entity(grid).add_child(*child);
+            }
+        }
+    }
+
+    commands.entity(entity).despawn_recursive();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use bevy::prelude::*;
+
+    use crate::{
+        grid::{Grid, GridBundle, GridOrientation, GridPosition, GridSize},
+        loading::{
+            tilemap::{
+                LayerDefinition, TileIdentifier, TilemapDefinition, TilemapDefinitionBuilder,
+                TilesetLink,
+            },
+            tileset::{
+                ImageDimensions, SourceDefinition, TileDefinition, TilesetDefinition,
+                TilesetDefinitionBuilder,
+            },
+        },
+        ordering::DepthOrdering,
+        reloading::reload_tilemaps,
+        rotate::{rotate_grid, GridRotationEvent, GridRotationFinished},
+        spawning::{spawn_tilemap, TilemapSpawner},
+        tile::{TileId, TileMarker},
+        tilemap::TilemapOrderId,
+        DynamicObject,
+    };
+
+    fn definition() -> TilemapDefinition {
+        TilemapDefinitionBuilder::new("testmap")
+            .with_tile_size(32, 32)
+            .add_tileset(TilesetLink::new(Path::new("tiles.its"), 't'))
+            .add_layer(LayerDefinition::new(0).with_tiles(vec![
+                vec![TileIdentifier::new(0, 't'), TileIdentifier::new(1, 't')],
+                vec![TileIdentifier::new(1, 't'), TileIdentifier::new(0, 't')],
+            ]))
+            .add_layer(LayerDefinition::new(1).with_tiles(vec![vec![
+                TileIdentifier::empty(),
+                TileIdentifier::new(1, 't'),
+            ]]))
+            .build()
+    }
+
+    fn app() -> (App, Handle<TilemapDefinition>, Entity) {
+        let tileset = TilesetDefinitionBuilder::new(SourceDefinition::new(
+            Path::new("tiles.png"),
+            ImageDimensions::new(64, 32),
+        ))
+        .with_tile_size(32, 32)
+        .add_tile(TileDefinition::new_standard(0, 0, 0))
+        .add_tile(TileDefinition::new_standard(1, 1, 0))
+        .build()
+        .unwrap();
+
+        let mut spawner = TilemapSpawner::new();
+        spawner.add_tileset(Path::new("tiles.its"), tileset, Handle::default());
+
+        let mut app = App::new();
+        app.add_plugins((TaskPoolPlugin::default(), AssetPlugin::default()))
+            .add_asset::<TilemapDefinition>()
+            .add_asset::<TilesetDefinition>()
+            .add_event::<GridRotationEvent>()
+            .add_event::<GridRotationFinished>()
+            .insert_resource(spawner)
+            .init_resource::<DepthOrdering>()
+            .add_systems(Update, (spawn_tilemap, rotate_grid, reload_tilemaps));
+
+        let handle = app
+            .world
+            .resource_mut::<Assets<TilemapDefinition>>()
+            .add(definition());
+        let grid = app
+            .world
+            .spawn(GridBundle::new(Grid {
+                tilemap_handle: handle.clone(),
+                texture_atlas_handle: None,
+            }))
+            .id();
+
+        app.update();
+        app.update();
+
+        (app, handle, grid)
+    }
+
+    fn modify(app: &mut App, handle: &Handle<TilemapDefinition>, definition: TilemapDefinition) {
+        app.world
+            .resource_mut::<Assets<TilemapDefinition>>()
+            .set_untracked(handle, definition);
+        app.update();
+        app.update();
+    }
+
+    fn tiles(app: &mut App) -> Vec<(Entity, GridPosition, u32)> {
+        let mut tiles = app
+            .world
+            .query_filtered::<(Entity, &GridPosition, &TileId), With<TileMarker>>()
+            .iter(&app.world)
+            .map(|(entity, position, id)| (entity, *position, id.id()))
+            .collect::<Vec<_>>();
+        tiles.sort_by_key(|(_, p, _)| (p.layer, p.y, p.x));
+        tiles
+    }
+
+    fn tile_at(app: &mut App, position: GridPosition) -> Option<(Entity, u32)> {
+        tiles(app)
+            .into_iter()
+            .find(|(_, p, _)| *p == position)
+            .map(|(entity, _, id)| (entity, id))
+    }
+
+    #[test]
+    fn test_reload_changed_tile() {
+        let (mut app, handle, _) = app();
+        let unchanged = tile_at(&mut app, GridPosition::new(1, 0, 0)).unwrap();
+        let changed = tile_at(&mut app, GridPosition::new(0, 0, 0)).unwrap();
+
+        let modified = TilemapDefinitionBuilder::new("testmap")
+            .with_tile_size(32, 32)
+            .add_tileset(TilesetLink::new(Path::new("tiles.its"), 't'))
+            .add_layer(LayerDefinition::new(0).with_tiles(vec![
+                vec![TileIdentifier::new(1, 't'), TileIdentifier::new(1, 't')],
+                vec![TileIdentifier::new(1, 't'), TileIdentifier::empty()],
+            ]))
+            .add_layer(definition().layers()[1].clone())
+            .build();
+        modify(&mut app, &handle, modified);
+
+        assert_eq!(4, tiles(&mut app).len());
+        assert_eq!(
+            Some(unchanged),
+            tile_at(&mut app, GridPosition::new(1, 0, 0))
+        );
+
+        let (entity, id) = tile_at(&mut app, GridPosition::new(0, 0, 0)).unwrap();
+        assert_ne!(changed.0, entity);
+        assert_eq!(1, id);
+        assert!(app.world.get_entity(changed.0).is_none());
+        assert_eq!(None, tile_at(&mut app, GridPosition::new(1, 1, 0)));
+    }
+
+    #[test]
+    fn test_reload_layers() {
+        let (mut app, handle, grid) = app();
+        let object = app.world.spawn(DynamicObject).id();
+        let layer = app
+            .world
+            .query::<(Entity, &TilemapOrderId)>()
+            .iter(&app.world)
+            .find(|(_, order_id)| order_id.id() == 1)
+            .map(|(entity, _)| entity)
+            .unwrap();
+        app.world.entity_mut(layer).add_child(object);
+
+        let modified = TilemapDefinitionBuilder::new("testmap")
+            .with_tile_size(32, 32)
+            .add_tileset(TilesetLink::new(Path::new("tiles.its"), 't'))
+            .add_layer(definition().layers()[0].clone())
+            .add_layer(LayerDefinition::new(2).with_tiles(vec![vec![TileIdentifier::new(0, 't')]]))
+            .build();
+        modify(&mut app, &handle, modified);
+
+        let mut layer_ids = app
+            .world
+            .query::<&TilemapOrderId>()
+            .iter(&app.world)
+            .map(|order_id| order_id.id())
+            .collect::<Vec<_>>();
+        layer_ids.sort();
+
+        assert_eq!(vec![0, 2], layer_ids);
+        assert!(app.world.get_entity(layer).is_none());
+        assert_eq!(Some(grid), app.world.get::<Parent>(object).map(|p| p.get()));
+        assert_eq!(
+            Some(0),
+            tile_at(&mut app, GridPosition::new(0, 0, 2)).map(|(_, id)| id)
+        );
+    }
+
+    #[test]
+    fn test_reload_keeps_rotation() {
+        let (mut app, handle, grid) = app();
+
+        app.world.send_event(GridRotationEvent::clockwise());
+        app.update();
+
+        // The tile at canonical (1, 0) is at (0, 0) after a clockwise rotation of the 2x2 grid.
+        let rotated = tile_at(&mut app, GridPosition::new(0, 0, 0)).unwrap();
+
+        let modified = TilemapDefinitionBuilder::new("testmap")
+            .with_tile_size(32, 32)
+            .add_tileset(TilesetLink::new(Path::new("tiles.its"), 't'))
+            .add_layer(LayerDefinition::new(0).with_tiles(vec![
+                vec![TileIdentifier::new(1, 't'), TileIdentifier::new(1, 't')],
+                vec![TileIdentifier::new(1, 't'), TileIdentifier::new(0, 't')],
+            ]))
+            .add_layer(definition().layers()[1].clone())
+            .build();
+        modify(&mut app, &handle, modified);
+
+        assert_eq!(
+            GridOrientation::Deg90,
+            *app.world.get::<GridOrientation>(grid).unwrap()
+        );
+        assert_eq!(
+            GridSize::new(2, 2),
+            *app.world.get::<GridSize>(grid).unwrap()
+        );
+        assert_eq!(Some(rotated), tile_at(&mut app, GridPosition::new(0, 0, 0)));
+        // Canonical (0, 0) changed and is spawned at its rotated position (0, 1).
+        assert_eq!(
+            Some(1),
+            tile_at(&mut app, GridPosition::new(0, 1, 0)).map(|(_, id)| id)
+        );
+    }
+}
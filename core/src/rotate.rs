@@ -1,82 +1,502 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, utils::HashSet};
 
-use crate::{WorldScale, grid::{Grid, GridPosition, GridOffset, TileSize}, StaticObject, DynamicObject, math::grid_to_world};
+use crate::{
+    elevation::TileElevation,
+    grid::{Grid, GridMember, GridOffset, GridOrientation, GridPosition, GridSize, TileSize},
+    movement::GridMover,
+    projection::GridProjection,
+    tile::TileLift,
+    transition::{GridRotationTransition, TransitionObject, TransitionPath},
+    DynamicObject, StaticObject, WorldScale,
+};
 
+/// Rotates a grid by 90 degrees, or every grid if no grid is given.
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct GridRotationEvent {
+    pub direction: RotationDirection,
+    pub grid: Option<Entity>,
+}
 
-#[derive(Event, Debug, Clone)]
-pub enum GridRotationEvent {
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RotationDirection {
     Clockwise,
     CounterClockwise,
 }
 
+/// Sent once the objects of a grid reached their positions after a rotation.
+#[derive(Event, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct GridRotationFinished {
+    pub grid: Entity,
+}
+
+type GridQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static TileSize,
+        &'static WorldScale,
+        &'static GridProjection,
+        &'static mut GridSize,
+        &'static mut GridOrientation,
+        Option<&'static GridOffset>,
+        Option<&'static mut GridRotationTransition>,
+    ),
+    With<Grid>,
+>;
+
+type TileQuery<'w, 's> =
+    Query<'w, 's, &'static mut GridPosition, (With<StaticObject>, Without<DynamicObject>)>;
+
+type DynamicObjectQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut GridPosition,
+        Option<&'static GridOffset>,
+        Option<&'static mut GridMover>,
+    ),
+    (With<DynamicObject>, Without<StaticObject>),
+>;
+
+/// Rotates the tiles and dynamic objects of the targeted grids within the bounds of that grid.
+/// Objects belong to the grid of their `GridMember`, or to the grid they are a descendant of.
+/// Tiles are placed with the offset of their grid, dynamic objects with their own offset.
+/// Dynamic objects which belong to no grid are rotated with the grid if there is only one.
+/// The waypoints of moving objects and the ramps of elevated tiles are rotated with them.
+/// Grids with a running rotation transition ignore further rotation events until it is finished.
+#[allow(clippy::too_many_arguments)]
 pub fn rotate_grid(
     mut rotation_event: EventReader<GridRotationEvent>,
-    tilesize: Query<(&TileSize, &WorldScale), With<Grid>>,
-    mut tiles: Query<
-        (&mut GridPosition, &mut Transform),
-        (With<StaticObject>, Without<DynamicObject>),
-    >,
-    mut dynamic_objects: Query<
-        (&mut GridPosition, &mut Transform, Option<&GridOffset>),
-        (With<DynamicObject>, Without<StaticObject>),
-    >,
+    mut grids: GridQuery,
+    children: Query<&Children>,
+    members: Query<(Entity, &GridMember)>,
+    mut tiles: TileQuery,
+    mut dynamic_objects: DynamicObjectQuery,
+    mut transforms: Query<(
+        &mut Transform,
+        Option<&TileLift>,
+        Option<&mut TileElevation>,
+    )>,
+    mut finished_events: EventWriter<GridRotationFinished>,
 ) {
-    if let Ok((tilesize, scale)) = tilesize.get_single() {
-        for rotation_event in rotation_event.iter() {
-            for (old_grid_position, old_transform) in tiles.iter_mut() {
-                rotate(
-                    rotation_event,
-                    old_grid_position,
-                    old_transform,
-                    *tilesize,
-                    *scale,
-                    GridOffset(Vec2::default()),
-                );
+    let single_grid = grids.iter().count() == 1;
+
+    for rotation_event in rotation_event.iter() {
+        let mut rotated_objects = HashSet::new();
+        let is_clockwise = rotation_event.is_clockwise();
+
+        for (
+            grid_entity,
+            tilesize,
+            scale,
+            projection,
+            mut size,
+            mut orientation,
+            grid_offset,
+            transition,
+        ) in grids.iter_mut()
+        {
+            if rotation_event.grid.is_some_and(|grid| grid != grid_entity)
+                || transition.as_ref().is_some_and(|t| t.is_running())
+            {
+                continue;
             }
 
-            for (old_grid_position, old_transform, offset) in dynamic_objects.iter_mut() {
-                let offset = match offset {
-                    Some(o) => *o,
-                    None => GridOffset(Vec2::default()),
-                };
-
-                rotate(
-                    rotation_event,
-                    old_grid_position,
-                    old_transform,
-                    *tilesize,
-                    *scale,
-                    offset,
-                );
+            let mut objects = Vec::new();
+            let grid_members = members
+                .iter()
+                .filter(|(_, member)| member.0 == grid_entity)
+                .map(|(entity, _)| entity);
+
+            for entity in children.iter_descendants(grid_entity).chain(grid_members) {
+                let belongs_to_grid = members
+                    .get(entity)
+                    .map_or(true, |(_, member)| member.0 == grid_entity);
+                if !belongs_to_grid || !rotated_objects.insert(entity) {
+                    continue;
+                }
+
+                if let Ok(old_grid_position) = tiles.get_mut(entity) {
+                    objects.push(rotate(
+                        rotation_event,
+                        entity,
+                        old_grid_position,
+                        *size,
+                        grid_offset.copied().unwrap_or(GridOffset(Vec2::default())),
+                    ));
+                } else if let Ok((_, old_grid_position, offset, mover)) =
+                    dynamic_objects.get_mut(entity)
+                {
+                    if let Some(mut mover) = mover {
+                        mover.rotate(is_clockwise, *size);
+                    }
+
+                    objects.push(rotate(
+                        rotation_event,
+                        entity,
+                        old_grid_position,
+                        *size,
+                        offset.copied().unwrap_or(GridOffset(Vec2::default())),
+                    ));
+                }
+            }
+
+            if single_grid {
+                for (entity, old_grid_position, offset, mover) in dynamic_objects.iter_mut() {
+                    if rotated_objects.contains(&entity) || members.contains(entity) {
+                        continue;
+                    }
+
+                    if let Some(mut mover) = mover {
+                        mover.rotate(is_clockwise, *size);
+                    }
+
+                    objects.push(rotate(
+                        rotation_event,
+                        entity,
+                        old_grid_position,
+                        *size,
+                        offset.copied().unwrap_or(GridOffset(Vec2::default())),
+                    ));
+                }
             }
+
+            for object in objects.iter() {
+                if let Ok((_, _, Some(mut elevation))) = transforms.get_mut(object.entity) {
+                    *elevation = elevation.rotated(is_clockwise);
+                }
+            }
+
+            *size = size.rotated();
+            *orientation = match is_clockwise {
+                true => orientation.rotated_c(),
+                false => orientation.rotated_cc(),
+            };
+
+            match transition {
+                Some(mut transition) if transition.duration() > 0.0 => transition.start(objects),
+                _ => {
+                    for object in objects {
+                        if let Ok((mut transform, lift, elevation)) =
+                            transforms.get_mut(object.entity)
+                        {
+                            let mut world_pos = projection.elevated_to_world(
+                                object.path.target,
+                                elevation.map(|e| e.height).unwrap_or_default(),
+                                tilesize.width() * scale.0,
+                                tilesize.height() * scale.0,
+                            );
+                            world_pos.x += object.offset.x;
+                            world_pos.y += object.offset.y + lift.map(|l| l.0).unwrap_or_default();
+
+                            transform.translation = world_pos;
+                        }
+                    }
+
+                    finished_events.send(GridRotationFinished { grid: grid_entity });
+                }
+            }
+        }
+    }
+}
+
+impl GridRotationEvent {
+    /// Rotates every grid clockwise.
+    pub fn clockwise() -> Self {
+        Self {
+            direction: RotationDirection::Clockwise,
+            grid: None,
+        }
+    }
+
+    /// Rotates every grid counterclockwise.
+    pub fn counter_clockwise() -> Self {
+        Self {
+            direction: RotationDirection::CounterClockwise,
+            grid: None,
         }
     }
+
+    /// Only rotates the given grid.
+    pub fn with_grid(mut self, grid: Entity) -> Self {
+        self.grid = Some(grid);
+        self
+    }
+
+    pub fn is_clockwise(&self) -> bool {
+        self.direction == RotationDirection::Clockwise
+    }
 }
 
 fn rotate(
     rotation_event: &GridRotationEvent,
+    entity: Entity,
     mut old_grid_position: Mut<GridPosition>,
-    mut old_transform: Mut<Transform>,
-    tilesize: TileSize,
-    scale: WorldScale,
+    size: GridSize,
     offset: GridOffset,
-) {
-    let new_grid_position = match rotation_event {
-        GridRotationEvent::Clockwise => old_grid_position.clone().rotate_c(13), // TODO: Remove hardcoded numbers
-        GridRotationEvent::CounterClockwise => old_grid_position.clone().rotate_cc(13),
+) -> TransitionObject {
+    let new_grid_position = match rotation_event.is_clockwise() {
+        true => old_grid_position.rotate_c_within(size),
+        false => old_grid_position.rotate_cc_within(size),
     };
 
-    old_grid_position.x = new_grid_position.x;
-    old_grid_position.y = new_grid_position.y;
-    old_grid_position.layer = new_grid_position.layer;
-
-    let mut world_pos: Vec3 = grid_to_world(
+    let path = TransitionPath::new(
+        Vec3::from(*old_grid_position),
         Vec3::from(new_grid_position),
-        tilesize.width() * scale.0,
-        tilesize.height() * scale.0,
+        size,
+        rotation_event.is_clockwise(),
     );
-    world_pos.x += offset.0.x;
-    world_pos.y += offset.0.y;
 
-    old_transform.translation = world_pos;
+    *old_grid_position = new_grid_position;
+
+    TransitionObject {
+        entity,
+        path,
+        offset: offset.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{prelude::*, utils::Instant};
+
+    use crate::{
+        grid::{Grid, GridBundle, GridMember, GridOrientation, GridPosition, GridSize, TileSize},
+        math::grid_to_world,
+        rotate::{rotate_grid, GridRotationEvent, GridRotationFinished},
+        transition::{animate_rotation_transitions, GridRotationTransition, LerpTransition},
+        DynamicObject, StaticObject, WorldScale,
+    };
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_event::<GridRotationEvent>()
+            .add_event::<GridRotationFinished>()
+            .init_resource::<Time>()
+            .add_systems(
+                Update,
+                (rotate_grid, animate_rotation_transitions.after(rotate_grid)),
+            );
+        app
+    }
+
+    fn finished_count(app: &App) -> usize {
+        let events = app.world.resource::<Events<GridRotationFinished>>();
+        events.get_reader().iter(events).count()
+    }
+
+    fn spawn_grid(app: &mut App, size: GridSize, tiles: &[GridPosition]) -> (Entity, Vec<Entity>) {
+        let tiles = tiles
+            .iter()
+            .map(|position| {
+                app.world
+                    .spawn((*position, Transform::default(), StaticObject))
+                    .id()
+            })
+            .collect::<Vec<Entity>>();
+
+        let grid = app
+            .world
+            .spawn((
+                GridBundle::new(Grid {
+                    tilemap_handle: Handle::default(),
+                    texture_atlas_handle: None,
+                }),
+                size,
+                TileSize::new(32.0, 16.0),
+                WorldScale(1.0),
+            ))
+            .push_children(&tiles)
+            .id();
+
+        (grid, tiles)
+    }
+
+    #[test]
+    fn test_rotate_rectangular_grid() {
+        let mut app = app();
+        let (grid, tiles) = spawn_grid(
+            &mut app,
+            GridSize::new(3, 2),
+            &[GridPosition::new(0, 0, 0), GridPosition::new(2, 1, 1)],
+        );
+
+        app.world.send_event(GridRotationEvent::clockwise());
+        app.update();
+
+        assert_eq!(
+            GridSize::new(2, 3),
+            *app.world.get::<GridSize>(grid).unwrap()
+        );
+        assert_eq!(
+            GridOrientation::Deg90,
+            *app.world.get::<GridOrientation>(grid).unwrap()
+        );
+        assert_eq!(
+            GridPosition::new(0, 2, 0),
+            *app.world.get::<GridPosition>(tiles[0]).unwrap()
+        );
+        assert_eq!(
+            GridPosition::new(1, 0, 1),
+            *app.world.get::<GridPosition>(tiles[1]).unwrap()
+        );
+        assert_eq!(
+            grid_to_world(Vec3::new(1.0, 0.0, 1.0), 32.0, 16.0),
+            app.world.get::<Transform>(tiles[1]).unwrap().translation
+        );
+        assert_eq!(1, finished_count(&app));
+
+        app.world.send_event(GridRotationEvent::counter_clockwise());
+        app.update();
+
+        assert_eq!(
+            GridSize::new(3, 2),
+            *app.world.get::<GridSize>(grid).unwrap()
+        );
+        assert_eq!(
+            GridOrientation::Deg0,
+            *app.world.get::<GridOrientation>(grid).unwrap()
+        );
+        assert_eq!(
+            GridPosition::new(2, 1, 1),
+            *app.world.get::<GridPosition>(tiles[1]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_rotate_multiple_grids() {
+        let mut app = app();
+        let (_, small_tiles) =
+            spawn_grid(&mut app, GridSize::new(2, 1), &[GridPosition::new(1, 0, 0)]);
+        let (_, large_tiles) =
+            spawn_grid(&mut app, GridSize::new(5, 4), &[GridPosition::new(1, 0, 0)]);
+        let object = app
+            .world
+            .spawn((
+                GridPosition::new(1, 0, 0),
+                Transform::default(),
+                DynamicObject,
+            ))
+            .id();
+
+        app.world.send_event(GridRotationEvent::clockwise());
+        app.update();
+
+        assert_eq!(
+            GridPosition::new(0, 0, 0),
+            *app.world.get::<GridPosition>(small_tiles[0]).unwrap()
+        );
+        assert_eq!(
+            GridPosition::new(0, 3, 0),
+            *app.world.get::<GridPosition>(large_tiles[0]).unwrap()
+        );
+        assert_eq!(
+            GridPosition::new(1, 0, 0),
+            *app.world.get::<GridPosition>(object).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_rotate_target_grid_with_members() {
+        let mut app = app();
+        let (small, small_tiles) =
+            spawn_grid(&mut app, GridSize::new(2, 1), &[GridPosition::new(1, 0, 0)]);
+        let (large, large_tiles) =
+            spawn_grid(&mut app, GridSize::new(5, 4), &[GridPosition::new(1, 0, 0)]);
+        let object = app
+            .world
+            .spawn((
+                GridPosition::new(1, 0, 0),
+                Transform::default(),
+                DynamicObject,
+                GridMember(large),
+            ))
+            .id();
+
+        app.world
+            .send_event(GridRotationEvent::clockwise().with_grid(large));
+        app.update();
+
+        assert_eq!(
+            GridOrientation::Deg0,
+            *app.world.get::<GridOrientation>(small).unwrap()
+        );
+        assert_eq!(
+            GridPosition::new(1, 0, 0),
+            *app.world.get::<GridPosition>(small_tiles[0]).unwrap()
+        );
+        assert_eq!(
+            GridOrientation::Deg90,
+            *app.world.get::<GridOrientation>(large).unwrap()
+        );
+        assert_eq!(
+            GridPosition::new(0, 3, 0),
+            *app.world.get::<GridPosition>(large_tiles[0]).unwrap()
+        );
+        assert_eq!(
+            GridPosition::new(0, 3, 0),
+            *app.world.get::<GridPosition>(object).unwrap()
+        );
+        assert_eq!(1, finished_count(&app));
+    }
+
+    #[test]
+    fn test_rotation_transition() {
+        let mut app = app();
+        let (grid, tiles) =
+            spawn_grid(&mut app, GridSize::new(3, 2), &[GridPosition::new(2, 1, 1)]);
+        app.world
+            .entity_mut(grid)
+            .insert(GridRotationTransition::new(LerpTransition {
+                duration: 1.0,
+            }));
+
+        let start = Instant::now();
+        app.world.resource_mut::<Time>().update_with_instant(start);
+        app.world.send_event(GridRotationEvent::clockwise());
+        app.update();
+
+        let start_pos = grid_to_world(Vec3::new(2.0, 1.0, 1.0), 32.0, 16.0);
+        let target_pos = grid_to_world(Vec3::new(1.0, 0.0, 1.0), 32.0, 16.0);
+
+        assert_eq!(
+            GridPosition::new(1, 0, 1),
+            *app.world.get::<GridPosition>(tiles[0]).unwrap()
+        );
+        assert_eq!(0, finished_count(&app));
+
+        app.world
+            .resource_mut::<Time>()
+            .update_with_instant(start + Duration::from_secs_f32(0.5));
+        app.world.send_event(GridRotationEvent::counter_clockwise());
+        app.update();
+
+        let translation = app.world.get::<Transform>(tiles[0]).unwrap().translation;
+        let halfway = start_pos.lerp(target_pos, 0.5);
+        assert!(translation.truncate().abs_diff_eq(halfway.truncate(), 1e-3));
+        assert_eq!(
+            GridOrientation::Deg90,
+            *app.world.get::<GridOrientation>(grid).unwrap()
+        );
+
+        app.world
+            .resource_mut::<Time>()
+            .update_with_instant(start + Duration::from_secs_f32(1.1));
+        app.update();
+
+        assert_eq!(
+            target_pos,
+            app.world.get::<Transform>(tiles[0]).unwrap().translation
+        );
+        assert_eq!(1, finished_count(&app));
+        assert!(!app
+            .world
+            .get::<GridRotationTransition>(grid)
+            .unwrap()
+            .is_running());
+    }
 }
@@ -0,0 +1,312 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    chunking::{with_unloaded_chunks, LoadedChunks, TilemapChunking},
+    elevation::TileElevation,
+    grid::{Grid, GridOrientation, GridPosition, GridSize},
+    loading::tilemap::{
+        LayerDefinition, TileIdentifier, TilemapDefinition, TilemapDefinitionBuilder,
+    },
+    tile::{TileId, TileMarker, TilesetAlias},
+    tilemap::TilemapOrderId,
+};
+
+/// Requests saving the tiles of a grid as tilemap definition to the given path.
+/// The name, tile size and tilesets of the definition are taken from the tilemap asset of the grid.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct SaveTilemapEvent {
+    pub grid: Entity,
+    pub path: PathBuf,
+}
+
+pub type SavedTileQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static TileId,
+        &'static GridPosition,
+        &'static TilesetAlias,
+        Option<&'static TileElevation>,
+    ),
+    With<TileMarker>,
+>;
+
+type SavedGridQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static Grid,
+        &'static GridOrientation,
+        &'static GridSize,
+        Option<(&'static TilemapChunking, &'static LoadedChunks)>,
+    ),
+>;
+
+/// Reconstructs a tilemap definition from the tiles in the hierarchy of a grid entity.
+/// The rotation of the grid is undone, so the tiles are placed as they were authored.
+/// Name, tile size and tilesets are taken from the base definition, its layers are replaced.
+/// Every layer is as large as needed for its tiles, missing tiles are saved as empty.
+/// The height of elevated tiles is saved, their ramps are part of the tileset.
+pub fn extract_tilemap(
+    grid: Entity,
+    base: &TilemapDefinition,
+    orientation: GridOrientation,
+    view_size: GridSize,
+    children: &Query<&Children>,
+    tiles: &SavedTileQuery,
+    layers: &Query<&TilemapOrderId>,
+) -> TilemapDefinition {
+    let canonical_size = orientation.canonical_size(view_size);
+    let mut layer_tiles = BTreeMap::<usize, Vec<(usize, usize, TileIdentifier, f32)>>::new();
+    let mut layer_ids = BTreeSet::new();
+
+    for entity in children.iter_descendants(grid) {
+        if let Ok(order_id) = layers.get(entity) {
+            layer_ids.insert(order_id.id());
+        }
+
+        if let Ok((tile_id, position, alias, elevation)) = tiles.get(entity) {
+            let canonical = orientation.to_canonical(*position, canonical_size);
+            let Some((x, y, layer)) = canonical.indices() else {
+                warn!(
+                    "Tile at {:?} is outside of the grid and not saved.",
+                    canonical
+                );
+                continue;
+            };
+
+            layer_ids.insert(layer);
+            layer_tiles.entry(layer).or_default().push((
+                x,
+                y,
+                TileIdentifier::new(tile_id.id(), alias.0),
+                elevation.map(|e| e.height).unwrap_or_default(),
+            ));
+        }
+    }
+
+    let tile_size = base.tile_size();
+    let mut builder = TilemapDefinitionBuilder::new(base.name())
+        .with_tile_size(tile_size.width(), tile_size.height());
+
+    for link in base.tilesets() {
+        builder = builder.add_tileset(link.clone());
+    }
+
+    for layer_id in layer_ids {
+        let tiles = layer_tiles.remove(&layer_id).unwrap_or_default();
+        let width = tiles.iter().map(|(x, ..)| x + 1).max().unwrap_or_default();
+        let height = tiles
+            .iter()
+            .map(|(_, y, ..)| y + 1)
+            .max()
+            .unwrap_or_default();
+        let mut rows = vec![vec![TileIdentifier::empty(); width]; height];
+        let mut elevations = vec![vec![0.0; width]; height];
+
+        for (x, y, identifier, elevation) in tiles {
+            rows[y][x] = identifier;
+            elevations[y][x] = elevation;
+        }
+
+        builder = builder.add_layer(
+            LayerDefinition::new(layer_id as u32)
+                .with_tiles(rows)
+                .with_elevations(elevations),
+        );
+    }
+
+    builder.build()
+}
+
+/// Saves grids as tilemap definitions when a `SaveTilemapEvent` is received.
+/// Tiles of chunks which are not spawned are saved as they are in the tilemap asset of the grid.
+pub fn save_tilemaps(
+    mut save_events: EventReader<SaveTilemapEvent>,
+    definitions: Res<Assets<TilemapDefinition>>,
+    grids: SavedGridQuery,
+    children: Query<&Children>,
+    tiles: SavedTileQuery,
+    layers: Query<&TilemapOrderId>,
+) {
+    for event in save_events.iter() {
+        let Ok((grid, orientation, size, chunks)) = grids.get(event.grid) else {
+            warn!("Can not save {:?}, it is not a spawned grid.", event.grid);
+            continue;
+        };
+        let Some(base) = definitions.get(&grid.tilemap_handle) else {
+            warn!(
+                "Can not save {:?}, its tilemap asset is not loaded.",
+                event.grid
+            );
+            continue;
+        };
+
+        let mut definition = extract_tilemap(
+            event.grid,
+            base,
+            *orientation,
+            *size,
+            &children,
+            &tiles,
+            &layers,
+        );
+
+        if let Some((chunking, loaded_chunks)) = chunks {
+            definition = with_unloaded_chunks(definition, base, chunking, loaded_chunks);
+        }
+
+        if let Err(error) = definition.save(&event.path) {
+            error!("Failed to save tilemap to {:?}: {:?}", event.path, error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use bevy::{
+        ecs::system::{CommandQueue, SystemState},
+        prelude::*,
+    };
+
+    use crate::{
+        grid::{GridOrientation, GridSize},
+        loading::{
+            tilemap::{
+                LayerDefinition, TileIdentifier, TilemapDefinition, TilemapDefinitionBuilder,
+                TilesetLink,
+            },
+            tileset::{
+                ImageDimensions, SourceDefinition, TileDefinition, TilesetDefinitionBuilder,
+            },
+        },
+        rotate::{rotate_grid, GridRotationEvent, GridRotationFinished},
+        saving::{extract_tilemap, SavedTileQuery},
+        spawning::{Spawner, TilemapSpawner},
+        tilemap::TilemapOrderId,
+    };
+
+    fn definition() -> TilemapDefinition {
+        TilemapDefinitionBuilder::new("testmap")
+            .with_tile_size(32, 32)
+            .add_tileset(TilesetLink::new(Path::new("tiles.its"), 't'))
+            .add_layer(
+                LayerDefinition::new(0)
+                    .with_tiles(vec![
+                        vec![
+                            TileIdentifier::new(0, 't'),
+                            TileIdentifier::new(1, 't'),
+                            TileIdentifier::empty(),
+                        ],
+                        vec![
+                            TileIdentifier::new(1, 't'),
+                            TileIdentifier::new(0, 't'),
+                            TileIdentifier::new(1, 't'),
+                        ],
+                    ])
+                    .with_elevations(vec![vec![0.0, 1.5, 0.0], vec![0.0, 0.0, 0.5]]),
+            )
+            .add_layer(LayerDefinition::new(1).with_tiles(vec![vec![
+                TileIdentifier::empty(),
+                TileIdentifier::new(1, 't'),
+            ]]))
+            .build()
+    }
+
+    fn app() -> App {
+        let tileset = TilesetDefinitionBuilder::new(SourceDefinition::new(
+            Path::new("tiles.png"),
+            ImageDimensions::new(64, 32),
+        ))
+        .with_tile_size(32, 32)
+        .add_tile(TileDefinition::new_standard(0, 0, 0))
+        .add_tile(TileDefinition::new_standard(1, 1, 0))
+        .build()
+        .unwrap();
+
+        let mut spawner = TilemapSpawner::new();
+        spawner.add_tileset(Path::new("tiles.its"), tileset, Handle::default());
+
+        let mut app = App::new();
+        app.add_event::<GridRotationEvent>()
+            .add_event::<GridRotationFinished>()
+            .insert_resource(spawner)
+            .add_systems(Update, rotate_grid);
+        app
+    }
+
+    fn spawn(app: &mut App, definition: TilemapDefinition) -> Entity {
+        let mut queue = CommandQueue::default();
+        let grid = app
+            .world
+            .resource::<TilemapSpawner>()
+            .spawn(&mut Commands::new(&mut queue, &app.world), definition);
+        queue.apply(&mut app.world);
+        grid
+    }
+
+    fn extract(app: &mut App, grid: Entity, base: &TilemapDefinition) -> TilemapDefinition {
+        let mut state =
+            SystemState::<(Query<&Children>, SavedTileQuery, Query<&TilemapOrderId>)>::new(
+                &mut app.world,
+            );
+        let (children, tiles, layers) = state.get(&app.world);
+
+        extract_tilemap(
+            grid,
+            base,
+            *app.world.get::<GridOrientation>(grid).unwrap(),
+            *app.world.get::<GridSize>(grid).unwrap(),
+            &children,
+            &tiles,
+            &layers,
+        )
+    }
+
+    #[test]
+    fn test_extract_spawned_tilemap() {
+        let mut app = app();
+        let grid = spawn(&mut app, definition());
+
+        assert_eq!(definition(), extract(&mut app, grid, &definition()));
+    }
+
+    #[test]
+    fn test_extract_undoes_rotation() {
+        let mut app = app();
+        let grid = spawn(&mut app, definition());
+
+        for event in [
+            GridRotationEvent::clockwise(),
+            GridRotationEvent::clockwise(),
+            GridRotationEvent::counter_clockwise(),
+        ] {
+            app.world.send_event(event);
+            app.update();
+        }
+
+        assert_eq!(
+            GridOrientation::Deg90,
+            *app.world.get::<GridOrientation>(grid).unwrap()
+        );
+        assert_eq!(definition(), extract(&mut app, grid, &definition()));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join("bevy_iso_test_save_and_load.itm");
+
+        definition().save(&path).unwrap();
+        let loaded = TilemapDefinition::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(definition(), loaded);
+    }
+}
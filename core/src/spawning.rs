@@ -1,6 +1,29 @@
-use bevy::prelude::*;
+use std::path::Path;
 
-use crate::{WorldScale, grid::{Grid, TileSize, GridPosition}, tile::{TileBundle, TileId}, ordering::ZOffset, tilemap::TilemapBundle, math::grid_to_world, loading::tilemap::TilemapDefinition};
+use bevy::{
+    asset::{AssetPath, LoadState},
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use crate::{
+    animation::AnimatedTile,
+    chunking::{LoadedChunks, TilemapChunking},
+    elevation::TileElevation,
+    grid::{
+        Grid, GridBundle, GridMember, GridOffset, GridOrientation, GridPosition, GridSize, TileSize,
+    },
+    loading::{
+        loader::tileset_asset_paths,
+        tilemap::{self, TileIdentifier, TileReference, TilemapDefinition, TilesetLink},
+        tileset::{TileDefinition, TilesetDefinition},
+    },
+    ordering::ZOffset,
+    projection::GridProjection,
+    tile::{TileBundle, TileId, TilesetAlias},
+    tilemap::TilemapBundle,
+    WorldScale,
+};
 
 pub trait Spawner {
     type Definition;
@@ -8,93 +31,808 @@ pub trait Spawner {
     fn spawn(&self, commands: &mut Commands, defintion: Self::Definition) -> Entity;
 }
 
-#[derive(Resource)]
-pub struct TilemapSpawner;
+/// A tileset definition together with the texture atlas created from it.
+#[derive(Debug, Clone)]
+pub struct TilesetAtlas {
+    definition: TilesetDefinition,
+    texture_atlas: Handle<TextureAtlas>,
+}
+
+/// Spawns tilemap definitions as a grid entity with one child per layer and the tiles as children of the layers.
+/// Tilesets of tilemap assets are registered by their asset path, resolved relative to the tilemap file,
+/// so tilemaps in different directories can link different tilesets with the same relative path.
+#[derive(Resource, Debug, Clone)]
+pub struct TilemapSpawner {
+    tilesets: HashMap<AssetPath<'static>, TilesetAtlas>,
+    /// The resolved tileset paths of grids spawned from tilemap assets, by the alias of their link.
+    grid_tilesets: HashMap<Entity, HashMap<char, AssetPath<'static>>>,
+    failed_tilesets: HashSet<AssetPath<'static>>,
+    world_scale: f32,
+}
+
+/// Whether the tilesets linked by a tilemap asset are ready to spawn it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TilesetLoadState {
+    Loading,
+    Loaded,
+    /// Some tilesets failed to load, tiles using them can not be spawned.
+    Failed,
+}
+
+impl TilesetAtlas {
+    pub fn new(definition: TilesetDefinition, texture_atlas: Handle<TextureAtlas>) -> Self {
+        Self {
+            definition,
+            texture_atlas,
+        }
+    }
+
+    pub fn definition(&self) -> &TilesetDefinition {
+        &self.definition
+    }
+
+    pub fn texture_atlas(&self) -> &Handle<TextureAtlas> {
+        &self.texture_atlas
+    }
+}
+
+impl Default for TilemapSpawner {
+    fn default() -> Self {
+        Self {
+            tilesets: HashMap::default(),
+            grid_tilesets: HashMap::default(),
+            failed_tilesets: HashSet::default(),
+            world_scale: 1.0,
+        }
+    }
+}
+
+impl TilemapSpawner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_world_scale(mut self, world_scale: f32) -> Self {
+        self.world_scale = world_scale;
+        self
+    }
+
+    /// Registers a tileset under the path used by the `TilesetLink`s of tilemap definitions
+    /// which are spawned directly instead of from a tilemap asset.
+    pub fn add_tileset(
+        &mut self,
+        path: &Path,
+        definition: TilesetDefinition,
+        texture_atlas: Handle<TextureAtlas>,
+    ) {
+        self.tilesets.insert(
+            AssetPath::new(path.to_owned(), None),
+            TilesetAtlas::new(definition, texture_atlas),
+        );
+    }
+
+    pub fn remove_tileset(&mut self, path: &Path) {
+        self.tilesets.remove(&AssetPath::new(path.to_owned(), None));
+    }
+
+    pub fn tileset(&self, path: &Path) -> Option<&TilesetAtlas> {
+        self.tilesets.get(&AssetPath::new(path.to_owned(), None))
+    }
+
+    /// The tileset of a link, as registered for the grid or under the path of the link.
+    fn linked_tileset(&self, grid: Option<Entity>, link: &TilesetLink) -> Option<&TilesetAtlas> {
+        match grid
+            .and_then(|grid| self.grid_tilesets.get(&grid))
+            .and_then(|tilesets| tilesets.get(&link.alias()))
+        {
+            Some(path) => self.tilesets.get(path),
+            None => self.tileset(link.path()),
+        }
+    }
+
+    /// Spawns the layers and tiles of the definition as children of an already existing grid entity.
+    /// The tiles are placed with the given scale, offset and projection of the grid.
+    pub fn spawn_into(
+        &self,
+        commands: &mut Commands,
+        grid_entity: Entity,
+        definition: &TilemapDefinition,
+        scale: WorldScale,
+        offset: GridOffset,
+        projection: &GridProjection,
+    ) {
+        self.spawn_grid(
+            commands,
+            grid_entity,
+            definition,
+            scale,
+            offset,
+            projection,
+            true,
+        );
+    }
+
+    /// Spawns only the layers of the definition into an already existing grid entity.
+    /// The tiles are spawned in chunks near the camera, see `TilemapChunking`.
+    pub fn spawn_chunked_into(
+        &self,
+        commands: &mut Commands,
+        grid_entity: Entity,
+        definition: &TilemapDefinition,
+        scale: WorldScale,
+        offset: GridOffset,
+        projection: &GridProjection,
+    ) {
+        self.spawn_grid(
+            commands,
+            grid_entity,
+            definition,
+            scale,
+            offset,
+            projection,
+            false,
+        );
+        commands.entity(grid_entity).insert(LoadedChunks::default());
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn spawn_grid(
+        &self,
+        commands: &mut Commands,
+        grid_entity: Entity,
+        definition: &TilemapDefinition,
+        scale: WorldScale,
+        offset: GridOffset,
+        projection: &GridProjection,
+        spawn_tiles: bool,
+    ) {
+        // The tiles are drawn as blocks, so only the upper half of the image is the actual tile surface.
+        let tilesize = TileSize::new(
+            definition.tile_size().width() as f32,
+            definition.tile_size().height() as f32 / 2.0,
+        );
+
+        commands.entity(grid_entity).insert((
+            tilesize,
+            GridSize::from(definition),
+            scale,
+            projection.clone(),
+            Name::new(format!("Grid - {}", definition.name())),
+        ));
+
+        let mut layers = definition.layers().to_vec();
+        layers.sort_by_key(|layer| layer.ordering_id());
+
+        commands.entity(grid_entity).with_children(|grid| {
+            for layer in layers.iter() {
+                let layer_id = layer.ordering_id() as usize;
+                let layer_name = format!("{} - {}", definition.name(), layer_id);
+
+                grid.spawn((
+                    TilemapBundle::new(&layer_name, layer_id),
+                    Name::new(format!("Tilemap - {}", layer_name)),
+                ))
+                .with_children(|tilemap| {
+                    if !spawn_tiles {
+                        return;
+                    }
+
+                    for (y, row) in layer.tiles().iter().enumerate() {
+                        for (x, identifier) in row.iter().enumerate() {
+                            self.spawn_tile(
+                                tilemap,
+                                grid_entity,
+                                definition,
+                                identifier,
+                                GridPosition::from_indices(x, y, layer_id),
+                                layer.elevation(x, y),
+                                GridOrientation::default(),
+                                tilesize,
+                                scale,
+                                offset,
+                                projection,
+                            );
+                        }
+                    }
+                });
+            }
+        });
+    }
+
+    /// Spawns a single tile of the definition at the given position of the current view.
+    /// The tile is raised by its elevation from the definition, its ramp is turned to the orientation of the view.
+    /// Empty and invalid identifiers as well as tiles of unknown tilesets are skipped.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn spawn_tile(
+        &self,
+        tilemap: &mut ChildBuilder,
+        grid: Entity,
+        definition: &TilemapDefinition,
+        identifier: &TileIdentifier,
+        position: GridPosition,
+        height: f32,
+        orientation: GridOrientation,
+        tilesize: TileSize,
+        scale: WorldScale,
+        offset: GridOffset,
+        projection: &GridProjection,
+    ) -> Option<Entity> {
+        let (alias, tile_id) = match identifier.parse() {
+            Some(TileReference::Tile { alias, id }) => (alias, id),
+            Some(TileReference::Empty) => return None,
+            None => {
+                warn!("Invalid tile identifier '{}'.", identifier.value());
+                return None;
+            }
+        };
+        let Some(tileset) = definition
+            .tileset(alias)
+            .and_then(|link| self.linked_tileset(Some(grid), link))
+        else {
+            warn!("No tileset registered for alias '{}'.", alias);
+            return None;
+        };
+        let Some(index) = tileset.definition.atlas_index(tile_id) else {
+            warn!("Tile {} does not exist in tileset '{}'.", tile_id, alias);
+            return None;
+        };
+
+        let properties = tileset
+            .definition
+            .tile(tile_id)
+            .map(|tile| tile.properties().clone())
+            .unwrap_or_default();
+        let elevation = TileElevation::from_properties(height, &properties).to_view(orientation);
+
+        let mut transform = Transform::from_translation(projection.elevated_to_world(
+            Vec3::from(position),
+            elevation.height,
+            tilesize.width() * scale.0,
+            tilesize.height() * scale.0,
+        ));
+        transform.translation += offset.0.extend(0.0);
+        transform.scale = Vec3::new(scale.0, scale.0, 1.0);
+
+        let mut tile = tilemap.spawn((
+            TileBundle::new(
+                TileId::new(tile_id),
+                position,
+                ZOffset::default(),
+                SpriteSheetBundle {
+                    texture_atlas: tileset.texture_atlas.clone(),
+                    transform,
+                    sprite: TextureAtlasSprite::new(index),
+                    ..default()
+                },
+            ),
+            properties,
+            TilesetAlias(alias),
+            GridMember(grid),
+            Name::new(format!(
+                "Tile ({},{},{})",
+                position.x, position.y, position.layer
+            )),
+        ));
+
+        if !elevation.is_flat_ground() {
+            tile.insert(elevation);
+        }
+
+        if let Some(TileDefinition::Animated {
+            id: _,
+            positions: _,
+            interval_per_sec,
+            properties: _,
+        }) = tileset.definition.tile(tile_id)
+        {
+            if let Some(frames) = tileset.definition.atlas_indices(tile_id) {
+                tile.insert(AnimatedTile::new(frames, *interval_per_sec));
+            }
+        }
+
+        Some(tile.id())
+    }
+
+    /// Validates the definition against the registered tilesets.
+    pub fn validate(&self, definition: &TilemapDefinition) -> Result<(), Vec<tilemap::Error>> {
+        self.validate_grid(None, definition)
+    }
+
+    /// Validates the definition against the tilesets registered for the grid it is spawned into.
+    pub(crate) fn validate_grid(
+        &self,
+        grid: Option<Entity>,
+        definition: &TilemapDefinition,
+    ) -> Result<(), Vec<tilemap::Error>> {
+        definition.validate(|link| {
+            self.linked_tileset(grid, link)
+                .map(|tileset| &tileset.definition)
+        })
+    }
+
+    /// The atlas of the first tileset linked in the definition.
+    fn primary_texture_atlas(
+        &self,
+        grid: Option<Entity>,
+        definition: &TilemapDefinition,
+    ) -> Option<Handle<TextureAtlas>> {
+        definition
+            .tilesets()
+            .iter()
+            .find_map(|link| self.linked_tileset(grid, link))
+            .map(|tileset| tileset.texture_atlas.clone())
+    }
+}
 
 impl Spawner for TilemapSpawner {
     type Definition = TilemapDefinition;
 
     fn spawn(&self, commands: &mut Commands, defintion: Self::Definition) -> Entity {
-        todo!()
+        let grid_entity = commands
+            .spawn(GridBundle::new(Grid {
+                tilemap_handle: Handle::default(),
+                texture_atlas_handle: self.primary_texture_atlas(None, &defintion),
+            }))
+            .id();
+
+        self.spawn_into(
+            commands,
+            grid_entity,
+            &defintion,
+            WorldScale(self.world_scale),
+            GridOffset(Vec2::default()),
+            &GridProjection::default(),
+        );
+
+        grid_entity
     }
 }
 
+type NewGridQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        Option<&'static WorldScale>,
+        Option<&'static GridOffset>,
+        Option<&'static TilemapChunking>,
+        Option<&'static GridProjection>,
+        &'static mut Grid,
+    ),
+    Without<TileSize>,
+>;
 
+/// Spawns the tiles of grids whose tilemap asset and linked tilesets finished loading.
+/// Grids with `TilemapChunking` only get their layers, their tiles are spawned by `update_chunks`.
 pub fn spawn_tilemap(
     mut commands: Commands,
-    mut new_grids: Query<(Entity, &WorldScale, &mut Grid), Added<Grid>>,
+    mut new_grids: NewGridQuery,
+    mut spawner: ResMut<TilemapSpawner>,
     asset_server: Res<AssetServer>,
     tilemaps: Res<Assets<TilemapDefinition>>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    tilesets: Res<Assets<TilesetDefinition>>,
 ) {
-    /*
-    for (grid_entity, scale, mut grid) in new_grids.iter_mut() {
-        if let Some(tilemap_file) = tilemaps.get(&grid.tilemap_handle) {
-            let tilesize =
-                TileSize::new(tilemap_file.tile_width(), tilemap_file.tile_height() / 2.0);
-            commands.entity(grid_entity).insert((
-                tilesize,
-                Name::new(format!("Grid - {}", tilemap_file.name())),
-            ));
-
-            let texture_handle = asset_server.load(tilemap_file.source_image());
-            let texture_atlas = TextureAtlas::from_grid(
-                texture_handle,
-                Vec2::new(tilemap_file.tile_width(), tilemap_file.tile_height()),
-                tilemap_file.columns(),
-                tilemap_file.rows(),
-                None,
-                None,
-            );
+    for (grid_entity, scale, offset, chunking, projection, mut grid) in new_grids.iter_mut() {
+        let Some(definition) = tilemaps.get(&grid.tilemap_handle) else {
+            continue;
+        };
+
+        match register_loaded_tilesets(
+            &mut spawner,
+            grid_entity,
+            &grid,
+            definition,
+            &asset_server,
+            &tilesets,
+        ) {
+            TilesetLoadState::Loading => continue,
+            TilesetLoadState::Failed => error!(
+                "Tilemap '{}' is spawned without the tiles of its failed tilesets.",
+                definition.name()
+            ),
+            TilesetLoadState::Loaded => {}
+        }
 
-            let texture_atlas_handle = texture_atlases.add(texture_atlas);
-            grid.texture_atlas_handle = Some(texture_atlas_handle.clone());
+        let scale = match scale {
+            Some(s) => *s,
+            None => WorldScale(spawner.world_scale),
+        };
 
-            let mut layers = tilemap_file.layers();
-            layers.sort_by_key(|x| x.order_id());
+        if let Err(errors) = spawner.validate_grid(Some(grid_entity), definition) {
+            for error in errors {
+                warn!("Invalid tilemap '{}': {:?}", definition.name(), error);
+            }
+        }
 
-            for layer in layers {
-                let tiles = layer.tiles();
-                let tilemap_height = tiles.len();
-                let tilemap_width = tiles[0].len();
+        if grid.texture_atlas_handle.is_none() {
+            grid.texture_atlas_handle =
+                spawner.primary_texture_atlas(Some(grid_entity), definition);
+        }
 
-                for y in 0..tilemap_height {
-                    for x in 0..tilemap_width {
-                        if tiles[y][x] != -1 {
-                            let pos: Vec3 = Vec3::new(x as f32, y as f32, layer.order_id() as f32);
+        let offset = offset.copied().unwrap_or(GridOffset(Vec2::default()));
+        let projection = projection.cloned().unwrap_or_default();
 
-                            let mut transform = Transform::default();
-                            transform.translation = grid_to_world(
-                                pos,
-                                tilesize.width() * scale.0,
-                                tilesize.height() * scale.0,
-                            );
-                            transform.scale = Vec3::new(scale.0, scale.0, 0.0);
-
-                            commands.spawn((
-                                TileBundle::new(
-                                    TileId::new(tiles[y][x] as u32),
-                                    GridPosition::new(x, y, layer.order_id()),
-                                    ZOffset(layer.order_id() as f32 * 100.0),
-                                    SpriteSheetBundle {
-                                        texture_atlas: texture_atlas_handle.clone(),
-                                        transform: transform,
-                                        sprite: TextureAtlasSprite::new(tiles[y][x] as usize),
-                                        ..default()
-                                    },
-                                ),
-                                Name::new(format!("Tile ({},{},{})", x, y, layer.order_id())),
-                            ));
-                        }
-                    }
-                }
+        match chunking {
+            Some(_) => spawner.spawn_chunked_into(
+                &mut commands,
+                grid_entity,
+                definition,
+                scale,
+                offset,
+                &projection,
+            ),
+            None => spawner.spawn_into(
+                &mut commands,
+                grid_entity,
+                definition,
+                scale,
+                offset,
+                &projection,
+            ),
+        }
+    }
+}
+
+/// Adds the tilesets linked by the loaded tilemap of a grid to the spawner, resolved relative to the tilemap file.
+/// Failed tilesets are only reported once.
+pub(crate) fn register_loaded_tilesets(
+    spawner: &mut TilemapSpawner,
+    grid_entity: Entity,
+    grid: &Grid,
+    definition: &TilemapDefinition,
+    asset_server: &AssetServer,
+    tilesets: &Assets<TilesetDefinition>,
+) -> TilesetLoadState {
+    let Some(tilemap_path) = asset_server.get_handle_path(&grid.tilemap_handle) else {
+        return TilesetLoadState::Loaded;
+    };
+    let mut state = TilesetLoadState::Loaded;
+    let mut grid_tilesets = HashMap::new();
+
+    for link in definition.tilesets() {
+        let (tileset_path, atlas_path) = tileset_asset_paths(tilemap_path.path(), link.path());
+        grid_tilesets.insert(link.alias(), tileset_path.clone());
+
+        if spawner.tilesets.contains_key(&tileset_path) {
+            continue;
+        }
+
+        let tileset_handle: Handle<TilesetDefinition> = asset_server.load(tileset_path.clone());
+
+        if let Some(tileset) = tilesets.get(&tileset_handle) {
+            let texture_atlas = asset_server.load(atlas_path);
+            spawner.failed_tilesets.remove(&tileset_path);
+            spawner.tilesets.insert(
+                tileset_path,
+                TilesetAtlas::new(tileset.clone(), texture_atlas),
+            );
+        } else if asset_server.get_load_state(&tileset_handle) == LoadState::Failed {
+            if spawner.failed_tilesets.insert(tileset_path) {
+                warn!("Failed to load tileset '{}'.", link.path().display());
+            }
+
+            state = TilesetLoadState::Failed;
+        } else if state != TilesetLoadState::Failed {
+            state = TilesetLoadState::Loading;
+        }
+    }
 
-                commands.spawn((
-                    TilemapBundle::new(&layer.name(), layer.order_id()),
-                    Name::new(format!("Tilemap - {}", layer.name())),
-                ));
+    spawner.grid_tilesets.insert(grid_entity, grid_tilesets);
+    state
+}
+
+/// Updates the registered tilesets whose asset was modified and forgets the tilesets of despawned grids.
+/// Tiles spawned afterwards use the modified tileset.
+pub fn update_registered_tilesets(
+    mut asset_events: EventReader<AssetEvent<TilesetDefinition>>,
+    mut removed_grids: RemovedComponents<Grid>,
+    mut spawner: ResMut<TilemapSpawner>,
+    asset_server: Res<AssetServer>,
+    tilesets: Res<Assets<TilesetDefinition>>,
+) {
+    for event in asset_events.iter() {
+        let handle = match event {
+            AssetEvent::Modified { handle } | AssetEvent::Removed { handle } => handle,
+            AssetEvent::Created { .. } => continue,
+        };
+        let Some(path) = asset_server
+            .get_handle_path(handle)
+            .map(|path| path.to_owned())
+        else {
+            continue;
+        };
+
+        match (tilesets.get(handle), spawner.tilesets.get_mut(&path)) {
+            (Some(tileset), Some(registered)) => registered.definition = tileset.clone(),
+            (None, Some(_)) => {
+                spawner.tilesets.remove(&path);
             }
+            _ => {}
         }
     }
-    */
+
+    for grid in removed_grids.iter() {
+        spawner.grid_tilesets.remove(&grid);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use bevy::{ecs::system::CommandQueue, prelude::*};
+
+    use crate::{
+        animation::AnimatedTile,
+        elevation::TileElevation,
+        grid::{Grid, GridBundle, GridPosition, GridSize, TileSize},
+        loading::{
+            tilemap::{
+                LayerDefinition, TileIdentifier, TilemapDefinition, TilemapDefinitionBuilder,
+                TilesetLink,
+            },
+            tileset::{
+                AnimatedTileDefBuilder, ImageDimensions, SourceDefinition, TileDefinition,
+                TilePosition, TilesetDefinition, TilesetDefinitionBuilder,
+            },
+        },
+        movement::GridDirection,
+        spawning::{spawn_tilemap, update_registered_tilesets, Spawner, TilemapSpawner},
+        tile::{TileId, TileProperties},
+    };
+
+    fn spawner() -> TilemapSpawner {
+        let tileset = TilesetDefinitionBuilder::new(SourceDefinition::new(
+            Path::new("tiles.png"),
+            ImageDimensions::new(64, 32),
+        ))
+        .with_tile_size(32, 32)
+        .add_tile(TileDefinition::new_standard(0, 0, 0))
+        .add_tile(
+            TileDefinition::new_standard(1, 1, 0)
+                .with_property("water", true)
+                .with_property("cost", 3),
+        )
+        .add_tile(
+            AnimatedTileDefBuilder::new(2)
+                .add_position(TilePosition::new(0, 1))
+                .add_position(TilePosition::new(1, 1))
+                .build(),
+        )
+        .add_tile(TileDefinition::new_standard(3, 0, 1).with_property("ramp", "east"))
+        .build()
+        .unwrap();
+
+        let mut spawner = TilemapSpawner::new();
+        spawner.add_tileset(Path::new("tiles.its"), tileset, Handle::default());
+        spawner
+    }
+
+    #[test]
+    fn test_spawn_hierarchy() {
+        let definition = TilemapDefinitionBuilder::new("testmap")
+            .with_tile_size(32, 32)
+            .add_tileset(TilesetLink::new(Path::new("tiles.its"), 't'))
+            .add_layer(LayerDefinition::new(0).with_tiles(vec![
+                vec![TileIdentifier::new(0, 't'), TileIdentifier::new(1, 't')],
+                vec![TileIdentifier::new(1, 't'), TileIdentifier::new(0, 't')],
+            ]))
+            .add_layer(LayerDefinition::new(1).with_tiles(vec![vec![TileIdentifier::new(1, 't')]]))
+            .build();
+
+        let mut world = World::new();
+        let mut queue = CommandQueue::default();
+        let grid = spawner().spawn(&mut Commands::new(&mut queue, &world), definition);
+        queue.apply(&mut world);
+
+        assert!(world.get::<TileSize>(grid).is_some());
+        assert_eq!(GridSize::new(2, 2), *world.get::<GridSize>(grid).unwrap());
+
+        let layers = world.get::<Children>(grid).unwrap().to_vec();
+        assert_eq!(2, layers.len());
+        assert_eq!(4, world.get::<Children>(layers[0]).unwrap().len());
+
+        let top_tile = world.get::<Children>(layers[1]).unwrap()[0];
+        assert_eq!(1, world.get::<TileId>(top_tile).unwrap().id());
+        assert_eq!(
+            GridPosition::new(0, 0, 1),
+            *world.get::<GridPosition>(top_tile).unwrap()
+        );
+        assert_eq!(1, world.get::<TextureAtlasSprite>(top_tile).unwrap().index);
+    }
+
+    #[test]
+    fn test_spawn_skips_unknown_tiles() {
+        let definition = TilemapDefinitionBuilder::new("testmap")
+            .add_tileset(TilesetLink::new(Path::new("tiles.its"), 't'))
+            .add_layer(LayerDefinition::new(0).with_tiles(vec![vec![
+                TileIdentifier::new(0, 't'),
+                TileIdentifier::new(7, 't'),
+                TileIdentifier::new(0, 'x'),
+                TileIdentifier::empty(),
+            ]]))
+            .build();
+
+        let mut world = World::new();
+        let mut queue = CommandQueue::default();
+        let grid = spawner().spawn(&mut Commands::new(&mut queue, &world), definition);
+        queue.apply(&mut world);
+
+        let layer = world.get::<Children>(grid).unwrap()[0];
+        assert_eq!(1, world.get::<Children>(layer).unwrap().len());
+    }
+
+    #[test]
+    fn test_spawn_animated_tile() {
+        let definition = TilemapDefinitionBuilder::new("testmap")
+            .add_tileset(TilesetLink::new(Path::new("tiles.its"), 't'))
+            .add_layer(LayerDefinition::new(0).with_tiles(vec![vec![
+                TileIdentifier::new(2, 't'),
+                TileIdentifier::new(0, 't'),
+            ]]))
+            .build();
+
+        let mut world = World::new();
+        let mut queue = CommandQueue::default();
+        let grid = spawner().spawn(&mut Commands::new(&mut queue, &world), definition);
+        queue.apply(&mut world);
+
+        let layer = world.get::<Children>(grid).unwrap()[0];
+        let tiles = world.get::<Children>(layer).unwrap().to_vec();
+
+        let animation = world.get::<AnimatedTile>(tiles[0]).unwrap();
+        assert_eq!(&[2, 3], animation.frames());
+        assert_eq!(2, world.get::<TextureAtlasSprite>(tiles[0]).unwrap().index);
+        assert!(world.get::<AnimatedTile>(tiles[1]).is_none());
+    }
+
+    #[test]
+    fn test_spawn_tile_properties() {
+        let definition = TilemapDefinitionBuilder::new("testmap")
+            .add_tileset(TilesetLink::new(Path::new("tiles.its"), 't'))
+            .add_layer(LayerDefinition::new(0).with_tiles(vec![vec![
+                TileIdentifier::new(1, 't'),
+                TileIdentifier::new(0, 't'),
+            ]]))
+            .build();
+
+        let mut world = World::new();
+        let mut queue = CommandQueue::default();
+        let grid = spawner().spawn(&mut Commands::new(&mut queue, &world), definition);
+        queue.apply(&mut world);
+
+        let layer = world.get::<Children>(grid).unwrap()[0];
+        let tiles = world.get::<Children>(layer).unwrap().to_vec();
+
+        let properties = world.get::<TileProperties>(tiles[0]).unwrap();
+        assert_eq!(Some(true), properties.get_bool("water"));
+        assert_eq!(Some(3), properties.get_int("cost"));
+        assert!(world.get::<TileProperties>(tiles[1]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_spawn_tile_elevation() {
+        let definition = TilemapDefinitionBuilder::new("testmap")
+            .with_tile_size(32, 32)
+            .add_tileset(TilesetLink::new(Path::new("tiles.its"), 't'))
+            .add_layer(
+                LayerDefinition::new(0)
+                    .with_tiles(vec![vec![
+                        TileIdentifier::new(0, 't'),
+                        TileIdentifier::new(3, 't'),
+                        TileIdentifier::new(0, 't'),
+                    ]])
+                    .with_elevations(vec![vec![0.0, 0.0, 1.0]]),
+            )
+            .build();
+
+        let mut world = World::new();
+        let mut queue = CommandQueue::default();
+        let grid = spawner().spawn(&mut Commands::new(&mut queue, &world), definition);
+        queue.apply(&mut world);
+
+        let layer = world.get::<Children>(grid).unwrap()[0];
+        let tiles = world.get::<Children>(layer).unwrap().to_vec();
+        let y = |tile: Entity| world.get::<Transform>(tile).unwrap().translation.y;
+
+        assert!(world.get::<TileElevation>(tiles[0]).is_none());
+        assert_eq!(
+            Some(&TileElevation::new(0.0).with_ramp(GridDirection::East, 1.0)),
+            world.get::<TileElevation>(tiles[1])
+        );
+        assert_eq!(
+            Some(&TileElevation::new(1.0)),
+            world.get::<TileElevation>(tiles[2])
+        );
+        // Every step along x raises a diamond tile by half of its 16 pixel high surface.
+        assert_eq!(8.0, y(tiles[1]) - y(tiles[0]));
+        assert_eq!(8.0 + 16.0, y(tiles[2]) - y(tiles[1]));
+    }
+
+    #[test]
+    fn test_tilesets_resolved_per_tilemap() {
+        let mut app = App::new();
+        app.add_plugins((TaskPoolPlugin::default(), AssetPlugin::default()))
+            .add_asset::<TilemapDefinition>()
+            .add_asset::<TilesetDefinition>()
+            .init_resource::<TilemapSpawner>()
+            .add_systems(
+                Update,
+                (
+                    update_registered_tilesets,
+                    spawn_tilemap.after(update_registered_tilesets),
+                ),
+            );
+
+        // Both tilemaps link "tiles.its", which resolves to a different tileset in each directory.
+        let tileset = |tiles: &[(u32, usize)]| {
+            tiles
+                .iter()
+                .fold(
+                    TilesetDefinitionBuilder::new(SourceDefinition::new(
+                        Path::new("tiles.png"),
+                        ImageDimensions::new(64, 32),
+                    ))
+                    .with_tile_size(32, 32),
+                    |builder, (id, x)| builder.add_tile(TileDefinition::new_standard(*id, *x, 0)),
+                )
+                .build()
+                .unwrap()
+        };
+        let tilemap = TilemapDefinitionBuilder::new("testmap")
+            .with_tile_size(32, 32)
+            .add_tileset(TilesetLink::new(Path::new("tiles.its"), 't'))
+            .add_layer(LayerDefinition::new(0).with_tiles(vec![vec![TileIdentifier::new(0, 't')]]))
+            .build();
+
+        let mut grids = Vec::new();
+        for (directory, tiles) in [("a", vec![(0, 0)]), ("b", vec![(1, 0), (0, 1)])] {
+            let asset_server = app.world.resource::<AssetServer>().clone();
+            let tileset_handle: Handle<TilesetDefinition> =
+                asset_server.load(format!("{}/tiles.its", directory));
+            let tilemap_handle: Handle<TilemapDefinition> =
+                asset_server.load(format!("{}/map.itm", directory));
+
+            app.world
+                .resource_mut::<Assets<TilesetDefinition>>()
+                .set_untracked(&tileset_handle, tileset(&tiles));
+            app.world
+                .resource_mut::<Assets<TilemapDefinition>>()
+                .set_untracked(&tilemap_handle, tilemap.clone());
+            grids.push(
+                app.world
+                    .spawn(GridBundle::new(Grid {
+                        tilemap_handle,
+                        texture_atlas_handle: None,
+                    }))
+                    .id(),
+            );
+        }
+
+        app.update();
+
+        let sprite_index = |grid: Entity| {
+            let layer = app.world.get::<Children>(grid).unwrap()[0];
+            let tile = app.world.get::<Children>(layer).unwrap()[0];
+            app.world.get::<TextureAtlasSprite>(tile).unwrap().index
+        };
+        assert_eq!(0, sprite_index(grids[0]));
+        assert_eq!(1, sprite_index(grids[1]));
+
+        let asset_server = app.world.resource::<AssetServer>().clone();
+        let modified: Handle<TilesetDefinition> = asset_server.load("a/tiles.its");
+        app.world
+            .resource_mut::<Assets<TilesetDefinition>>()
+            .set_untracked(&modified, tileset(&[(2, 0), (0, 1)]));
+        app.update();
+        app.update();
+
+        let spawner = app.world.resource::<TilemapSpawner>();
+        assert_eq!(
+            Some(1),
+            spawner
+                .tileset(Path::new("a/tiles.its"))
+                .and_then(|tileset| tileset.definition().atlas_index(0))
+        );
+    }
 }
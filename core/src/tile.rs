@@ -1,4 +1,7 @@
+use std::collections::BTreeMap;
+
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{StaticObject, ordering::ZOffset};
 
@@ -12,6 +15,31 @@ pub struct TileMarker;
 #[derive(Component)]
 pub struct TileId(u32);
 
+/// Alias of the tileset a tile was spawned from.
+#[derive(Component, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TilesetAlias(pub char);
+
+/// Height a tile is currently lifted by. The lift is part of the translation of the tile
+/// but is ignored for its z ordering.
+#[derive(Component, Default, Debug, Copy, Clone, PartialEq)]
+pub struct TileLift(pub f32);
+
+/// A single value of a tile property.
+/// In tileset files the values are written without a type name, e.g. `true`, `3`, `0.5` or `"grass"`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum TileProperty {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+/// Custom metadata of a tile, authored in the tileset definition and added to every spawned tile.
+#[derive(Component, Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(transparent)]
+pub struct TileProperties(BTreeMap<String, TileProperty>);
+
 /// Bundle for creating tile entities.
 #[derive(Bundle)]
 pub struct TileBundle {
@@ -34,6 +62,115 @@ impl TileId {
     }
 }
 
+impl TileProperty {
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            TileProperty::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            TileProperty::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Gets the value as float. Int values are converted.
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            TileProperty::Float(value) => Some(*value),
+            TileProperty::Int(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            TileProperty::String(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl From<bool> for TileProperty {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<i64> for TileProperty {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<f64> for TileProperty {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<&str> for TileProperty {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_owned())
+    }
+}
+
+impl From<String> for TileProperty {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl TileProperties {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, key: &str, value: impl Into<TileProperty>) -> Self {
+        self.insert(key, value);
+        self
+    }
+
+    pub fn insert(&mut self, key: &str, value: impl Into<TileProperty>) {
+        self.0.insert(key.to_owned(), value.into());
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<TileProperty> {
+        self.0.remove(key)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&TileProperty> {
+        self.0.get(key)
+    }
+
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        self.get(key).and_then(TileProperty::as_bool)
+    }
+
+    pub fn get_int(&self, key: &str) -> Option<i64> {
+        self.get(key).and_then(TileProperty::as_int)
+    }
+
+    pub fn get_float(&self, key: &str) -> Option<f64> {
+        self.get(key).and_then(TileProperty::as_float)
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(TileProperty::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &TileProperty)> {
+        self.0.iter().map(|(key, value)| (key.as_str(), value))
+    }
+}
+
 impl TileBundle {
     pub fn new(
         id: TileId,
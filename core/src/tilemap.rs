@@ -18,6 +18,7 @@ pub struct TilemapBundle {
     _t: TilemapMarker,
     name: TilemapName,
     order_id: TilemapOrderId,
+    spatial: SpatialBundle,
 }
 
 impl TilemapBundle {
@@ -26,6 +27,19 @@ impl TilemapBundle {
             _t: TilemapMarker,
             name: TilemapName(String::from(name)),
             order_id: TilemapOrderId(id),
+            spatial: SpatialBundle::default(),
         }
     }
-}
\ No newline at end of file
+}
+
+impl TilemapName {
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TilemapOrderId {
+    pub fn id(&self) -> usize {
+        self.0
+    }
+}
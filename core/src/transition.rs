@@ -0,0 +1,271 @@
+use bevy::prelude::*;
+
+use crate::{
+    elevation::TileElevation,
+    grid::{GridSize, TileSize},
+    projection::GridProjection,
+    rotate::GridRotationFinished,
+    tile::TileLift,
+    WorldScale,
+};
+
+/// Animates the objects of a grid from their position before a rotation to their position after it.
+/// Positions are given in grid space, the z axis is the layer.
+pub trait RotationTransition: Send + Sync + 'static {
+    /// Length of the transition in seconds. Transitions with a duration of zero are applied instantly.
+    fn duration(&self) -> f32;
+
+    /// Position of an object at the given progress between 0.0 and 1.0.
+    fn position(&self, path: &TransitionPath, progress: f32) -> Vec3;
+}
+
+/// Start and target of a single object during a rotation transition.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TransitionPath {
+    pub start: Vec3,
+    pub target: Vec3,
+    /// Center of the grid before the rotation.
+    pub start_center: Vec2,
+    /// Center of the grid after the rotation.
+    pub target_center: Vec2,
+    pub clockwise: bool,
+}
+
+/// Moves every object to its new position at once.
+#[derive(Default, Debug, Copy, Clone)]
+pub struct InstantTransition;
+
+/// Moves every object on a straight line to its new position.
+#[derive(Debug, Copy, Clone)]
+pub struct LerpTransition {
+    pub duration: f32,
+}
+
+/// Moves every object on a circle around the center of the grid to its new position.
+#[derive(Debug, Copy, Clone)]
+pub struct OrbitTransition {
+    pub duration: f32,
+}
+
+/// The transition used for rotations of a grid. Grids without it rotate instantly.
+#[derive(Component)]
+pub struct GridRotationTransition {
+    transition: Box<dyn RotationTransition>,
+    running: Option<RunningTransition>,
+}
+
+struct RunningTransition {
+    elapsed: f32,
+    objects: Vec<TransitionObject>,
+}
+
+/// An object which gets moved by a running transition.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct TransitionObject {
+    pub entity: Entity,
+    pub path: TransitionPath,
+    pub offset: Vec2,
+}
+
+impl TransitionPath {
+    /// Creates the path of an object in a grid of the given size before the rotation.
+    pub fn new(start: Vec3, target: Vec3, size: GridSize, clockwise: bool) -> Self {
+        let start_center = Vec2::new(
+            (size.width as f32 - 1.0) / 2.0,
+            (size.height as f32 - 1.0) / 2.0,
+        );
+
+        Self {
+            start,
+            target,
+            start_center,
+            target_center: Vec2::new(start_center.y, start_center.x),
+            clockwise,
+        }
+    }
+}
+
+impl RotationTransition for InstantTransition {
+    fn duration(&self) -> f32 {
+        0.0
+    }
+
+    fn position(&self, path: &TransitionPath, _progress: f32) -> Vec3 {
+        path.target
+    }
+}
+
+impl RotationTransition for LerpTransition {
+    fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    fn position(&self, path: &TransitionPath, progress: f32) -> Vec3 {
+        path.start.lerp(path.target, progress)
+    }
+}
+
+impl RotationTransition for OrbitTransition {
+    fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    fn position(&self, path: &TransitionPath, progress: f32) -> Vec3 {
+        let relative = path.start.truncate() - path.start_center;
+        let angle = match path.clockwise {
+            true => progress * std::f32::consts::FRAC_PI_2,
+            false => -progress * std::f32::consts::FRAC_PI_2,
+        };
+        let (sin, cos) = angle.sin_cos();
+        let rotated = Vec2::new(
+            relative.x * cos + relative.y * sin,
+            relative.y * cos - relative.x * sin,
+        );
+        let center = path.start_center.lerp(path.target_center, progress);
+
+        (center + rotated).extend(path.start.z)
+    }
+}
+
+impl GridRotationTransition {
+    pub fn new(transition: impl RotationTransition) -> Self {
+        Self {
+            transition: Box::new(transition),
+            running: None,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.transition.duration()
+    }
+
+    pub(crate) fn start(&mut self, objects: Vec<TransitionObject>) {
+        self.running = Some(RunningTransition {
+            elapsed: 0.0,
+            objects,
+        });
+    }
+}
+
+/// Moves the objects of grids with a running transition and finishes the transition once its duration passed.
+pub fn animate_rotation_transitions(
+    time: Res<Time>,
+    mut grids: Query<(
+        Entity,
+        &TileSize,
+        &WorldScale,
+        &GridProjection,
+        &mut GridRotationTransition,
+    )>,
+    mut transforms: Query<(&mut Transform, Option<&TileLift>, Option<&TileElevation>)>,
+    mut finished_events: EventWriter<GridRotationFinished>,
+) {
+    for (grid_entity, tilesize, scale, projection, mut grid_transition) in grids.iter_mut() {
+        let duration = grid_transition.duration();
+        let grid_transition = &mut *grid_transition;
+        let Some(running) = grid_transition.running.as_mut() else {
+            continue;
+        };
+
+        running.elapsed += time.delta_seconds();
+        let progress = match duration > 0.0 {
+            true => (running.elapsed / duration).min(1.0),
+            false => 1.0,
+        };
+
+        for object in running.objects.iter() {
+            let Ok((mut transform, lift, elevation)) = transforms.get_mut(object.entity) else {
+                continue;
+            };
+
+            let grid_pos = match progress < 1.0 {
+                true => grid_transition.transition.position(&object.path, progress),
+                false => object.path.target,
+            };
+            let world_pos = projection.elevated_to_world(
+                grid_pos,
+                elevation.map(|e| e.height).unwrap_or_default(),
+                tilesize.width() * scale.0,
+                tilesize.height() * scale.0,
+            );
+
+            transform.translation.x = world_pos.x + object.offset.x;
+            transform.translation.y =
+                world_pos.y + object.offset.y + lift.map(|l| l.0).unwrap_or_default();
+
+            if progress >= 1.0 {
+                transform.translation.z = world_pos.z;
+            }
+        }
+
+        if progress >= 1.0 {
+            grid_transition.running = None;
+            finished_events.send(GridRotationFinished { grid: grid_entity });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use crate::{
+        grid::{GridPosition, GridSize},
+        transition::{LerpTransition, OrbitTransition, RotationTransition, TransitionPath},
+    };
+
+    fn path(clockwise: bool) -> TransitionPath {
+        let size = GridSize::new(4, 2);
+        let start = GridPosition::new(3, 1, 1);
+        let target = match clockwise {
+            true => start.rotate_c_within(size),
+            false => start.rotate_cc_within(size),
+        };
+
+        TransitionPath::new(Vec3::from(start), Vec3::from(target), size, clockwise)
+    }
+
+    #[test]
+    fn test_lerp_transition() {
+        let transition = LerpTransition { duration: 1.0 };
+        let path = path(true);
+
+        assert_eq!(path.start, transition.position(&path, 0.0));
+        assert_eq!(path.target, transition.position(&path, 1.0));
+        assert_eq!(
+            path.start.lerp(path.target, 0.5),
+            transition.position(&path, 0.5)
+        );
+    }
+
+    #[test]
+    fn test_orbit_transition_ends_on_target() {
+        let transition = OrbitTransition { duration: 1.0 };
+
+        for clockwise in [true, false] {
+            let path = path(clockwise);
+
+            assert!(transition
+                .position(&path, 0.0)
+                .abs_diff_eq(path.start, 1e-5));
+            assert!(transition
+                .position(&path, 1.0)
+                .abs_diff_eq(path.target, 1e-5));
+        }
+    }
+
+    #[test]
+    fn test_orbit_transition_keeps_distance_to_center() {
+        let transition = OrbitTransition { duration: 1.0 };
+        let path = path(true);
+        let start_distance = path.start.truncate().distance(path.start_center);
+        let halfway = transition.position(&path, 0.5);
+        let center = path.start_center.lerp(path.target_center, 0.5);
+
+        assert!((halfway.truncate().distance(center) - start_distance).abs() < 1e-5);
+    }
+}